@@ -0,0 +1,177 @@
+use std::collections::BTreeSet;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{bail, ensure, Context, Result};
+use clap::Parser;
+use url::form_urlencoded::byte_serialize;
+
+use crate::http;
+use crate::package_data::*;
+use crate::verify;
+
+/// Programs in the bin directory which rustup manages, rather than `cargo install`
+const RUSTUP_PROXIES: &[&str] = &[
+    "cargo",
+    "cargo-clippy",
+    "cargo-fmt",
+    "cargo-miri",
+    "clippy-driver",
+    "rls",
+    "rust-analyzer",
+    "rust-gdb",
+    "rust-gdbgui",
+    "rust-lldb",
+    "rustc",
+    "rustdoc",
+    "rustfmt",
+    "rustup",
+];
+
+/// Reinstall untracked programs so that they can be updated.
+///
+/// Programs in cargo's bin directory which aren't recorded in .crates2.json (because they were
+/// installed with `cargo install --no-track` or copied there manually) are never updated. This
+/// finds the crates.io package which provides each program and installs it again normally.
+#[derive(Debug, Parser)]
+pub struct AdoptArgs {
+    /// Names of the programs to adopt
+    #[arg(value_name = "BIN", required_unless_present = "list")]
+    bins: Vec<String>,
+
+    /// List the untracked programs in the bin directory instead.
+    #[arg(long, conflicts_with = "bins")]
+    list: bool,
+
+    /// Install this package rather than searching crates.io for the one which provides BIN.
+    #[arg(long = "crate", value_name = "NAME")]
+    krate: Option<String>,
+
+    /// Only show the packages which would be installed.
+    #[arg(short = 'n', long)]
+    dry_run: bool,
+}
+
+/// Find the files in the bin directory which no installed package owns
+pub fn untracked_bins(bin_dir: &Path, crates2: &Crates2) -> Result<Vec<String>> {
+    let tracked: BTreeSet<&str> =
+        crates2.installs.values().flat_map(|d| d.bins.iter().map(|b| exe_stem(b))).collect();
+    let entries =
+        fs::read_dir(bin_dir).with_context(|| format!("Failed to read '{}'", bin_dir.display()))?;
+    let mut bins = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() && !entry.file_type()?.is_symlink() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let stem = exe_stem(&name);
+        if !tracked.contains(stem) && !RUSTUP_PROXIES.contains(&stem) {
+            bins.push(name);
+        }
+    }
+    bins.sort();
+    Ok(bins)
+}
+
+/// Whether the latest version of a crates.io package has a binary target with this name
+fn crate_has_bin(krate: &str, bin: &str) -> Result<bool> {
+    let data = match http::get_json(&format!("https://crates.io/api/v1/crates/{krate}")) {
+        Ok(data) => data,
+        // most likely a 404 for a crate which doesn't exist
+        Err(e) => {
+            dbgmsg!("{e:#}");
+            return Ok(false);
+        }
+    };
+    let bin_names = data["versions"][0]["bin_names"].as_array();
+    Ok(bin_names.is_some_and(|names| names.iter().any(|n| n.as_str() == Some(bin))))
+}
+
+/// Search crates.io for the package which provides a binary
+fn find_crate(bin: &str) -> Result<Option<String>> {
+    // packages are usually named after their program, so try that first
+    if crate_has_bin(bin, bin)? {
+        return Ok(Some(bin.to_owned()));
+    }
+    let query: String = byte_serialize(bin.as_bytes()).collect();
+    let url = format!("https://crates.io/api/v1/crates?q={query}&per_page=10");
+    let results = http::get_json(&url)?;
+    for krate in results["crates"].as_array().into_iter().flatten() {
+        let Some(name) = krate["name"].as_str() else { continue };
+        if name != bin && crate_has_bin(name, bin)? {
+            return Ok(Some(name.to_owned()));
+        }
+    }
+    Ok(None)
+}
+
+pub fn run(args: &AdoptArgs, crates2: &Crates2) -> Result<()> {
+    let bin_dir = cargo_home()?.join("bin");
+    let untracked = untracked_bins(&bin_dir, crates2)?;
+    if args.list {
+        if untracked.is_empty() {
+            msg!("No untracked programs in '{}'", bin_dir.display());
+        }
+        for bin in &untracked {
+            println!("{bin}");
+        }
+        return Ok(());
+    }
+    ensure!(
+        args.krate.is_none() || args.bins.len() == 1,
+        "--crate can only be used when adopting a single program"
+    );
+
+    let cargo_exe = env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let mut failed = Vec::new();
+    for bin in &args.bins {
+        let bin = exe_stem(bin);
+        if !untracked.iter().any(|u| exe_stem(u) == bin) {
+            errmsg!("Error: '{bin}' isn't an untracked program in '{}'", bin_dir.display());
+            failed.push(bin);
+            continue;
+        }
+        let krate = match &args.krate {
+            Some(krate) => krate.clone(),
+            None => match find_crate(bin) {
+                Ok(Some(krate)) => krate,
+                Ok(None) => {
+                    errmsg!("Error: no package on crates.io provides '{bin}', try --crate");
+                    failed.push(bin);
+                    continue;
+                }
+                Err(e) => {
+                    errmsg!("Error: failed to search crates.io for '{bin}': {e:#}");
+                    failed.push(bin);
+                    continue;
+                }
+            },
+        };
+
+        // --force because cargo won't overwrite a program which it doesn't know about
+        let cargo_args = ["install", "--force", "--bin", bin, &krate];
+        msg!("Adopting {bin} from package {krate}");
+        dbgmsg!("{} {}", cargo_exe.to_string_lossy(), cargo_args.join(" "));
+        if args.dry_run {
+            continue;
+        }
+        let status = Command::new(&cargo_exe)
+            .args(cargo_args)
+            .status()
+            .context("Failed to execute `cargo install ...`")?;
+        if !status.success() {
+            errmsg!("Error: failed to install '{krate}'");
+            failed.push(bin);
+        } else if let Err(e) = verify::record(&[&krate]) {
+            warnmsg!("Warning: failed to record program hashes: {e:#}");
+        }
+    }
+
+    if !failed.is_empty() {
+        bail!("Failed to adopt some programs: {}", failed.join(", "));
+    }
+    Ok(())
+}
@@ -0,0 +1,117 @@
+//! Checking the versions that updates would install against RustSec's security advisories, so that
+//! a package isn't moved onto a version with a known vulnerability. Advisories are looked up in the
+//! OSV database like the "unmaintained" ones of `outdated --check`.
+//!
+//! Informational advisories (e.g. unmaintained or unsound) and withdrawn ones are left out, since
+//! they don't mean that one version is any better than another.
+
+use anyhow::Result;
+use serde_json::Value;
+
+use crate::http;
+use crate::semver::Version;
+
+/// One of the version ranges affected by an advisory
+#[derive(Debug)]
+struct Range {
+    introduced: Option<Version>,
+    /// The first fixed version, or the last affected one if `inclusive`
+    end: Option<Version>,
+    inclusive: bool,
+}
+
+impl Range {
+    fn contains(&self, version: &Version) -> bool {
+        let after_start = self.introduced.as_ref().is_none_or(|start| version >= start);
+        let before_end = match &self.end {
+            Some(end) if self.inclusive => version <= end,
+            Some(end) => version < end,
+            None => true,
+        };
+        after_start && before_end
+    }
+}
+
+#[derive(Debug)]
+pub struct Advisory {
+    pub id: String,
+    ranges: Vec<Range>,
+    /// Affected versions which are listed rather than in a range
+    versions: Vec<String>,
+}
+
+impl Advisory {
+    pub fn affects(&self, version: &Version) -> bool {
+        self.ranges.iter().any(|r| r.contains(version))
+            || self.versions.iter().any(|v| v.parse::<Version>().is_ok_and(|v| v == *version))
+    }
+}
+
+/// The ranges of an OSV "affected" entry. Its events are ordered, each "introduced" starting a
+/// range which the next "fixed" or "last_affected" ends.
+fn ranges(affected: &Value) -> Vec<Range> {
+    let mut ranges = Vec::new();
+    let events = affected["ranges"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|r| r["type"] == "SEMVER")
+        .flat_map(|r| r["events"].as_array().into_iter().flatten());
+    let mut start: Option<Option<Version>> = None;
+    for event in events {
+        let version = |key: &str| event[key].as_str().and_then(|v| v.parse::<Version>().ok());
+        if let Some(v) = event["introduced"].as_str() {
+            // "0" is the start of all versions
+            start = Some(v.parse().ok().filter(|_| v != "0"));
+        } else if let Some(end) = version("fixed") {
+            if let Some(introduced) = start.take() {
+                ranges.push(Range { introduced, end: Some(end), inclusive: false });
+            }
+        } else if let Some(end) = version("last_affected") {
+            if let Some(introduced) = start.take() {
+                ranges.push(Range { introduced, end: Some(end), inclusive: true });
+            }
+        }
+    }
+    if let Some(introduced) = start {
+        ranges.push(Range { introduced, end: None, inclusive: false });
+    }
+    ranges
+}
+
+/// Find the security advisories for a crates.io package
+pub fn for_package(name: &str) -> Result<Vec<Advisory>> {
+    let query = serde_json::json!({ "package": { "name": name, "ecosystem": "crates.io" } });
+    let data = http::post_json("https://api.osv.dev/v1/query", &query)?;
+    let mut advisories = Vec::new();
+    for vuln in data["vulns"].as_array().into_iter().flatten() {
+        let affected: Vec<&Value> = vuln["affected"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|a| a["package"]["name"].as_str().is_none_or(|n| n == name))
+            .collect();
+        let informational = |v: &Value| !v["database_specific"]["informational"].is_null();
+        if !vuln["withdrawn"].is_null()
+            || informational(vuln)
+            || affected.iter().any(|a| informational(a))
+        {
+            continue;
+        }
+        advisories.push(Advisory {
+            id: vuln["id"].as_str().unwrap_or("unknown advisory").to_owned(),
+            ranges: affected.iter().flat_map(|a| ranges(a)).collect(),
+            versions: affected
+                .iter()
+                .flat_map(|a| a["versions"].as_array().into_iter().flatten())
+                .filter_map(|v| v.as_str().map(str::to_owned))
+                .collect(),
+        });
+    }
+    Ok(advisories)
+}
+
+/// The IDs of the advisories which affect a version
+pub fn affecting<'a>(advisories: &'a [Advisory], version: &Version) -> Vec<&'a str> {
+    advisories.iter().filter(|a| a.affects(version)).map(|a| a.id.as_str()).collect()
+}
@@ -0,0 +1,179 @@
+//! Archives of the whole bin directory along with cargo's metadata, for `snapshot create` and
+//! `snapshot restore`.
+//!
+//! An archive is a gzipped tar file made with the system's `tar`, holding `bin`, `.crates.toml`,
+//! and `.crates2.json` as they are in CARGO_HOME. The previous versions saved for rollback in
+//! `bin/.previous` are left out, since they're a backup of their own, and are kept as they are by a
+//! restore.
+
+use std::fs;
+use std::io;
+use std::path::{self, Path, PathBuf};
+use std::process::Command;
+use std::time::SystemTime;
+
+use anyhow::{bail, ensure, Context, Result};
+use clap::Parser;
+
+use crate::package_data::*;
+use crate::snapshot;
+use crate::util;
+
+/// What's archived from CARGO_HOME
+const MEMBERS: [&str; 3] = ["bin", ".crates.toml", ".crates2.json"];
+
+/// Archive or restore the whole bin directory along with cargo's records of what's installed.
+///
+/// This is a coarse safety net before a big update run or moving to a new toolchain: restoring an
+/// archive puts back every program exactly as it was, without building anything.
+#[derive(Debug, Parser)]
+pub struct SnapshotArgs {
+    #[command(subcommand)]
+    action: Action,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum Action {
+    /// Archive the bin directory and cargo's metadata into a single file.
+    Create {
+        /// Where to write the archive [default: a new file in the state directory]
+        file: Option<PathBuf>,
+    },
+    /// Replace the bin directory and cargo's metadata with the contents of an archive.
+    ///
+    /// The archive is unpacked next to the bin directory first, which is then swapped for the
+    /// unpacked one, so a failed restore leaves everything as it was.
+    Restore {
+        /// The archive to restore
+        file: PathBuf,
+    },
+}
+
+fn run_tar(cmd: &mut Command) -> Result<()> {
+    dbgmsg!("{cmd:?}");
+    let status = cmd.status().context("Failed to run tar")?;
+    ensure!(status.success(), "tar failed with {status}");
+    Ok(())
+}
+
+fn remove_if_exists(path: &Path) -> Result<()> {
+    let res = if path.is_dir() { fs::remove_dir_all(path) } else { fs::remove_file(path) };
+    match res {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            Err(e).context(format!("Failed to remove '{}'", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn rename(from: &Path, to: &Path) -> Result<()> {
+    fs::rename(from, to)
+        .with_context(|| format!("Failed to move '{}' to '{}'", from.display(), to.display()))
+}
+
+fn create(file: Option<&Path>) -> Result<()> {
+    let home = cargo_home()?;
+    let path = match file {
+        Some(file) => file.to_owned(),
+        None => {
+            let dir = util::state_dir()?.join("archives");
+            fs::create_dir_all(&dir)
+                .with_context(|| format!("Failed to create '{}'", dir.display()))?;
+            // colons aren't allowed in file names on Windows
+            let name = util::format_timestamp(SystemTime::now()).replace(':', "");
+            dir.join(format!("{name}.tar.gz"))
+        }
+    };
+    // tar runs in CARGO_HOME
+    let path = path::absolute(&path).unwrap_or(path);
+    let members: Vec<&str> = MEMBERS.into_iter().filter(|m| home.join(m).exists()).collect();
+    ensure!(members.contains(&"bin"), "'{}' has no bin directory", home.display());
+
+    let res = run_tar(
+        Command::new("tar")
+            .arg("-czf")
+            .arg(&path)
+            .arg("-C")
+            .arg(&home)
+            .args(["--exclude", "bin/.previous"])
+            .args(&members),
+    );
+    if res.is_err() {
+        let _ = fs::remove_file(&path);
+    }
+    res?;
+    msg!("Saved the bin directory and metadata to '{}'", path.display());
+    Ok(())
+}
+
+/// Move the unpacked files into CARGO_HOME. The bin directory is replaced by renaming it, keeping
+/// the saved previous versions, and put back if the new one can't be moved into place.
+fn swap(home: &Path, staging: &Path) -> Result<()> {
+    let bin = home.join("bin");
+    let new_bin = staging.join("bin");
+    let old_bin = staging.join("bin.old");
+    if bin.exists() {
+        rename(&bin, &old_bin)?;
+    }
+    let previous = old_bin.join(".previous");
+    let keep_previous = previous.is_dir() && !new_bin.join(".previous").exists();
+    if keep_previous {
+        rename(&previous, &new_bin.join(".previous"))?;
+    }
+    if let Err(e) = rename(&new_bin, &bin) {
+        if keep_previous {
+            let _ = fs::rename(new_bin.join(".previous"), &previous);
+        }
+        let _ = fs::rename(&old_bin, &bin);
+        return Err(e);
+    }
+
+    for file in [".crates.toml", ".crates2.json"] {
+        let unpacked = staging.join(file);
+        if unpacked.exists() {
+            rename(&unpacked, &home.join(file))?;
+        } else {
+            // it wasn't there when the archive was made, so it shouldn't be now
+            remove_if_exists(&home.join(file))?;
+        }
+    }
+    Ok(())
+}
+
+fn restore(file: &Path) -> Result<()> {
+    let home = cargo_home()?;
+    let file = path::absolute(file).unwrap_or_else(|_| file.to_owned());
+    ensure!(file.is_file(), "'{}' doesn't exist", file.display());
+
+    // unpacked in CARGO_HOME so that everything can be moved into place by renaming
+    let staging = home.join(format!(".snapshot-restore-{}", std::process::id()));
+    remove_if_exists(&staging)?;
+    fs::create_dir_all(&staging)
+        .with_context(|| format!("Failed to create '{}'", staging.display()))?;
+    let res = (|| {
+        run_tar(Command::new("tar").arg("-xzf").arg(&file).arg("-C").arg(&staging))?;
+        if !staging.join("bin").is_dir() || !staging.join(".crates2.json").is_file() {
+            bail!("'{}' isn't an archive made by `snapshot create`", file.display());
+        }
+        let count = Crates2::load_from(&staging.join(".crates2.json"))?.installs.len();
+        if let Err(e) = snapshot::save() {
+            warnmsg!("Warning: failed to save a snapshot of the installed packages: {e:#}");
+        }
+        swap(&home, &staging)?;
+        Ok(count)
+    })();
+    if let Err(e) = remove_if_exists(&staging) {
+        warnmsg!("Warning: {e:#}");
+    }
+    let count = res?;
+    let s = if count == 1 { "" } else { "s" };
+    msg!("Restored {count} package{s} from '{}'", file.display());
+    Ok(())
+}
+
+pub fn run(args: &SnapshotArgs) -> Result<()> {
+    match &args.action {
+        Action::Create { file } => create(file.as_deref()),
+        Action::Restore { file } => restore(file),
+    }
+}
@@ -0,0 +1,301 @@
+//! Copies of packages' programs from before they were updated, so that they can be rolled back.
+//!
+//! Each version is kept in `$CARGO_HOME/bin/.previous/<package>/<version>/`, with a `saved.json`
+//! file recording the package id which cargo had for it. Git packages have the start of the commit
+//! hash added to the version, e.g. `1.0.2+046894ca`, since their version doesn't often change.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::list::print_table;
+use crate::package_data::*;
+use crate::util;
+use crate::verify;
+
+const INFO_FILE: &str = "saved.json";
+
+/// Restore a package's programs to a version saved before it was updated.
+///
+/// Previous versions are only saved when `keep` is set in the `[backup]` table of the config file
+/// (or with --keep-versions), which also limits how many are kept for each package. A total size
+/// limit can be set too, e.g. `max-size = "500M"`, and the oldest versions are removed to stay
+/// under it.
+///
+/// The rolled back version will be updated again by the next update run, unless it's pinned.
+#[derive(Debug, Parser)]
+pub struct RollbackArgs {
+    /// The package to roll back.
+    #[arg(required_unless_present = "list")]
+    name: Option<String>,
+
+    /// The version to restore [default: the most recently saved one]
+    version: Option<String>,
+
+    /// List the saved versions, of every package if no name is given.
+    #[arg(long, conflicts_with = "version")]
+    list: bool,
+}
+
+/// A saved version's `saved.json`
+#[derive(Debug, Deserialize, Serialize)]
+struct Info {
+    /// The package id from .crates2.json
+    id: String,
+    bins: Vec<String>,
+    /// When this version was saved, in seconds since the Unix epoch
+    saved: u64,
+}
+
+/// One saved version of a package
+#[derive(Debug)]
+struct Saved {
+    name: String,
+    /// The directory name, the version with maybe a commit hash
+    version: String,
+    dir: PathBuf,
+    info: Info,
+}
+
+impl Saved {
+    fn size(&self) -> u64 {
+        self.info
+            .bins
+            .iter()
+            .filter_map(|bin| fs::metadata(bin_path(&self.dir, bin)).ok())
+            .map(|m| m.len())
+            .sum()
+    }
+}
+
+fn store_dir() -> Result<PathBuf> {
+    Ok(cargo_home()?.join("bin").join(".previous"))
+}
+
+/// The name of the directory a package version is saved to
+fn version_dir_name(pkg: &Package) -> String {
+    match &pkg.source {
+        PackageSource::Git { rev: Some(rev), .. } => {
+            format!("{}+{}", pkg.version, &rev[..rev.len().min(8)])
+        }
+        _ => pkg.version.clone(),
+    }
+}
+
+fn read_dir_names(dir: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).context(format!("Failed to read '{}'", dir.display())),
+    };
+    let mut names = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if let (Ok(name), true) = (entry.file_name().into_string(), entry.path().is_dir()) {
+            names.push((name, entry.path()));
+        }
+    }
+    Ok(names)
+}
+
+fn read_info(path: &Path) -> Result<Info> {
+    Ok(serde_json::from_slice(&fs::read(path)?)?)
+}
+
+/// List the saved versions of every package, or one package, newest first
+fn saved_versions(name: Option<&str>) -> Result<Vec<Saved>> {
+    let mut saved = Vec::new();
+    for (pkg_name, pkg_dir) in read_dir_names(&store_dir()?)? {
+        if name.is_some_and(|n| n != pkg_name) {
+            continue;
+        }
+        for (version, dir) in read_dir_names(&pkg_dir)? {
+            let path = dir.join(INFO_FILE);
+            let info = match read_info(&path) {
+                Ok(info) => info,
+                Err(e) => {
+                    dbgmsg!("Ignoring '{}': {e:#}", path.display());
+                    continue;
+                }
+            };
+            saved.push(Saved { name: pkg_name.clone(), version, dir, info });
+        }
+    }
+    saved.sort_by_key(|s| std::cmp::Reverse(s.info.saved));
+    Ok(saved)
+}
+
+/// Copy the programs of an installed package into the store, unless that version is already there
+pub fn save(pkg_id: &str, pkg: &Package, details: &PackageDetails) -> Result<()> {
+    let bin_dir = cargo_home()?.join("bin");
+    let dir = store_dir()?.join(&pkg.name).join(version_dir_name(pkg));
+    if dir.join(INFO_FILE).exists() {
+        return Ok(());
+    }
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create '{}'", dir.display()))?;
+    for bin in &details.bins {
+        let (from, to) = (bin_path(&bin_dir, bin), bin_path(&dir, bin));
+        fs::copy(&from, &to).with_context(|| {
+            format!("Failed to copy '{}' to '{}'", from.display(), to.display())
+        })?;
+    }
+    let saved = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let info = Info { id: pkg_id.to_owned(), bins: details.bins.clone(), saved };
+    // written last, so that a partly copied version is ignored
+    let path = dir.join(INFO_FILE);
+    fs::write(&path, serde_json::to_string(&info)? + "\n")
+        .with_context(|| format!("Failed to write '{}'", path.display()))?;
+    dbgmsg!("Saved {} {} to '{}'", pkg.name, pkg.version, dir.display());
+    Ok(())
+}
+
+fn remove(saved: &Saved) {
+    dbgmsg!("Removing saved version {} {}", saved.name, saved.version);
+    if let Err(e) = fs::remove_dir_all(&saved.dir) {
+        warnmsg!("Warning: failed to remove '{}': {e}", saved.dir.display());
+    }
+    // remove the package's directory once it's empty
+    if let Some(parent) = saved.dir.parent() {
+        let _ = fs::remove_dir(parent);
+    }
+}
+
+/// Remove saved versions beyond the `keep` most recent ones of each package (not counting the
+/// currently installed version), then the oldest ones until they fit in `max_size` bytes
+pub fn prune(crates2: &Crates2, keep: u32, max_size: Option<u64>) -> Result<()> {
+    let installed: BTreeMap<String, String> = crates2
+        .installs
+        .keys()
+        .filter_map(|id| id.parse::<Package>().ok())
+        .map(|pkg| (pkg.name.clone(), version_dir_name(&pkg)))
+        .collect();
+
+    let mut counts: BTreeMap<&str, u32> = BTreeMap::new();
+    let (mut kept, mut removed) = (Vec::new(), Vec::new());
+    let saved = saved_versions(None)?;
+    for s in &saved {
+        if installed.get(&s.name) == Some(&s.version) {
+            kept.push(s);
+            continue;
+        }
+        let count = counts.entry(&s.name).or_default();
+        if *count < keep {
+            *count += 1;
+            kept.push(s);
+        } else {
+            removed.push(s);
+        }
+    }
+
+    if let Some(max_size) = max_size {
+        let mut total: u64 = kept.iter().map(|s| s.size()).sum();
+        // oldest first
+        while total > max_size {
+            let Some(s) = kept.pop() else { break };
+            total -= s.size();
+            removed.push(s);
+        }
+    }
+    for s in removed {
+        remove(s);
+    }
+    Ok(())
+}
+
+/// Replace a package's entry in cargo's metadata with the one for the restored version
+fn rewrite_metadata(current_id: &str, saved: &Info) -> Result<()> {
+    rewrite_installs(&[(current_id, Some(&saved.id))], |_, details| {
+        details["bins"] = serde_json::json!(saved.bins);
+    })
+}
+
+fn list(name: Option<&str>) -> Result<()> {
+    let saved = saved_versions(name)?;
+    if saved.is_empty() {
+        msg!("No saved versions");
+        return Ok(());
+    }
+    let rows: Vec<Vec<String>> = saved
+        .iter()
+        .map(|s| {
+            let time = UNIX_EPOCH + std::time::Duration::from_secs(s.info.saved);
+            vec![
+                s.name.clone(),
+                s.version.clone(),
+                util::format_timestamp(time),
+                format!("{:.1} MiB", s.size() as f64 / (1024.0 * 1024.0)),
+            ]
+        })
+        .collect();
+    print_table(&["NAME", "VERSION", "SAVED", "SIZE"], &rows, "");
+    Ok(())
+}
+
+pub fn run(args: &RollbackArgs, crates2: &Crates2, config: &Config) -> Result<()> {
+    if args.list {
+        return list(args.name.as_deref());
+    }
+    let name = args.name.as_deref().unwrap();
+    let (current_id, details, pkg) = crates2
+        .installs
+        .iter()
+        .filter_map(|(id, details)| Some((id, details, id.parse::<Package>().ok()?)))
+        .find(|(.., pkg)| pkg.name == name)
+        .ok_or_else(|| anyhow!("{name} isn't installed"))?;
+    let current = version_dir_name(&pkg);
+
+    let saved = saved_versions(Some(name))?;
+    let target = match &args.version {
+        // a bare version matches git packages' versions with a commit hash too
+        Some(v) => saved
+            .iter()
+            .find(|s| s.version == *v || s.version.starts_with(&format!("{v}+")))
+            .ok_or_else(|| anyhow!("No saved version {v} of {name}, see `rollback --list`"))?,
+        None => saved
+            .iter()
+            .find(|s| s.version != current)
+            .ok_or_else(|| anyhow!("No previous versions of {name} are saved"))?,
+    };
+    if target.version == current {
+        bail!("{name} {current} is already installed");
+    }
+
+    // so that it's possible to go back to the current version too
+    save(current_id, &pkg, details)
+        .with_context(|| format!("Failed to save the current version of {name}"))?;
+
+    msg!("Rolling back {name} from {current} to {}", target.version);
+    let bin_dir = cargo_home()?.join("bin");
+    for bin in &details.bins {
+        if !target.info.bins.contains(bin) {
+            let path = bin_path(&bin_dir, bin);
+            fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove '{}'", path.display()))?;
+        }
+    }
+    for bin in &target.info.bins {
+        let (from, to) = (bin_path(&target.dir, bin), bin_path(&bin_dir, bin));
+        // remove first, in case the program is running
+        let _ = fs::remove_file(&to);
+        fs::copy(&from, &to).with_context(|| {
+            format!("Failed to copy '{}' to '{}'", from.display(), to.display())
+        })?;
+    }
+    rewrite_metadata(current_id, &target.info)?;
+    if let Err(e) = verify::record(&[name]) {
+        warnmsg!("Warning: failed to record program hashes: {e:#}");
+    }
+    if config.backup.keep > 0 {
+        let crates2 = Crates2::load().context("Failed to reload .crates2.json")?;
+        prune(&crates2, config.backup.keep, config.backup.max_size.map(|s| s.0))?;
+    }
+    msg!("Pin {name} in the config file to keep it from being updated again");
+    Ok(())
+}
@@ -0,0 +1,223 @@
+//! Experimental batched builds for `--batch`, which build many crates.io packages at once so that
+//! their shared dependencies are only compiled once.
+//!
+//! The source of each package is downloaded from crates.io and unpacked into a temporary
+//! workspace, which is built with a single `cargo build --release`. The programs are then copied
+//! into the bin directory and recorded in cargo's metadata as if `cargo install` had installed
+//! them. This isn't quite what `cargo install` would do: features are unified across all the
+//! packages, the packages' own lock files and `[profile]` settings aren't used, and cargo doesn't
+//! check the result itself. Packages which can't be built this way are installed as usual.
+
+use std::collections::BTreeSet;
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::time::Instant;
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+
+use crate::http;
+use crate::index;
+use crate::package_data::*;
+use crate::report::{JobResult, Outcome};
+use crate::semver::Version;
+use crate::sha256;
+use crate::util;
+use crate::{Job, PushStr};
+
+/// What a batched build needs to know about a package
+#[derive(Debug, Clone)]
+pub struct Member {
+    /// The package's id in .crates2.json
+    pub pkg_id: String,
+    /// The version to install
+    pub version: Version,
+    pub features: Vec<String>,
+    pub bins: Vec<String>,
+}
+
+impl Member {
+    fn dir_name(&self, name: &str) -> String {
+        format!("{name}-{}", self.version)
+    }
+
+    /// The id of the new version in .crates2.json
+    fn new_pkg_id(&self, name: &str) -> String {
+        let source = self.pkg_id.split_once(" (").map_or("", |(_, source)| source);
+        format!("{name} {} ({source}", self.version)
+    }
+}
+
+/// Download a package's source from crates.io and unpack it in the workspace
+fn unpack(dir: &Path, name: &str, member: &Member) -> Result<()> {
+    let version = &member.version;
+    let url = format!("https://static.crates.io/crates/{name}/{name}-{version}.crate");
+    let archive = dir.join(format!("{}.crate", member.dir_name(name)));
+    fs::write(&archive, http::get_bytes(&url)?)
+        .with_context(|| format!("Failed to write '{}'", archive.display()))?;
+    let expected = index::checksum(name, version)?
+        .ok_or_else(|| anyhow!("{name} {version} isn't in the crates.io index"))?;
+    let actual = sha256::file_hex(&archive)
+        .with_context(|| format!("Failed to hash '{}'", archive.display()))?;
+    ensure!(actual == expected, "the checksum of {name} {version} doesn't match the index");
+
+    let status = Command::new("tar")
+        .arg("-xzf")
+        .arg(&archive)
+        .arg("-C")
+        .arg(dir)
+        .status()
+        .context("Failed to run tar")?;
+    ensure!(status.success(), "tar failed with {status}");
+    let _ = fs::remove_file(&archive);
+    Ok(())
+}
+
+/// Write the workspace's manifest, with each package as a member
+fn write_manifest(dir: &Path, members: &[String]) -> Result<()> {
+    let members: Vec<String> = members.iter().map(|m| format!("    {m:?},\n")).collect();
+    let manifest = format!("[workspace]\nresolver = \"2\"\nmembers = [\n{}]\n", members.concat());
+    let path = dir.join("Cargo.toml");
+    fs::write(&path, manifest).with_context(|| format!("Failed to write '{}'", path.display()))
+}
+
+/// The full output of `rustc -vV`, which cargo records for each package it installs
+fn rustc_info() -> Result<String> {
+    let rustc = env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let output = Command::new(rustc).arg("-vV").output().context("Failed to run `rustc -vV`")?;
+    ensure!(output.status.success(), "`rustc -vV` failed");
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Copy the programs of a package to the bin directory, replacing the installed ones
+fn install_bins(target_dir: &Path, bin_dir: &Path, bins: &[String]) -> Result<()> {
+    // checked first so that a package isn't left half installed
+    for bin in bins {
+        let built = target_dir.join("release").join(bin);
+        ensure!(built.is_file(), "'{bin}' wasn't built");
+    }
+    for bin in bins {
+        let dest = bin_path(bin_dir, bin);
+        let tmp = bin_dir.join(format!(".{bin}.batch-tmp"));
+        fs::copy(target_dir.join("release").join(bin), &tmp)
+            .with_context(|| format!("Failed to copy '{bin}' to '{}'", bin_dir.display()))?;
+        fs::rename(&tmp, &dest)
+            .with_context(|| format!("Failed to replace '{}'", dest.display()))?;
+    }
+    Ok(())
+}
+
+/// Record new versions of packages in .crates2.json and .crates.toml, in place of the old ones
+fn record(installed: &[(String, String)], rustc: &str) -> Result<()> {
+    let changes: Vec<(&str, Option<&str>)> =
+        installed.iter().map(|(old, new)| (old.as_str(), Some(new.as_str()))).collect();
+    rewrite_installs(&changes, |_, details| {
+        details["rustc"] = rustc.into();
+        details["profile"] = "release".into();
+    })
+}
+
+/// Build the packages of the jobs together and install them. Returns the results of those which
+/// were installed, and the jobs which have to be run as usual instead.
+pub fn run(
+    cargo_exe: &OsStr,
+    jobs: Vec<Job>,
+    build_dir: Option<&Path>,
+    build_jobs: Option<u32>,
+) -> Result<(Vec<JobResult>, Vec<Job>)> {
+    let dir = match build_dir {
+        Some(dir) => dir.join("batch"),
+        None => util::state_dir()?.join("batch"),
+    };
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create '{}'", dir.display()))?;
+    let res = build(cargo_exe, jobs, &dir, build_jobs);
+    if let Err(e) = fs::remove_dir_all(&dir) {
+        dbgmsg!("Failed to remove '{}': {e}", dir.display());
+    }
+    res
+}
+
+fn build(
+    cargo_exe: &OsStr,
+    jobs: Vec<Job>,
+    dir: &Path,
+    build_jobs: Option<u32>,
+) -> Result<(Vec<JobResult>, Vec<Job>)> {
+    let start = Instant::now();
+    let unpacked = http::map_concurrent(&jobs, |job| {
+        let Some(member) = &job.batch else { return false };
+        unpack(dir, &job.name, member)
+            .map_err(|e| warnmsg!("Warning: couldn't batch {}: {e:#}", job.name))
+            .is_ok()
+    });
+    let mut unpacked = unpacked.into_iter();
+    let (batched, mut rest): (Vec<Job>, Vec<Job>) =
+        jobs.into_iter().partition(|_| unpacked.next().unwrap_or(false));
+    if batched.len() < 2 {
+        rest.extend(batched);
+        return Ok((Vec::new(), rest));
+    }
+
+    let members: Vec<(&Job, &Member)> =
+        batched.iter().filter_map(|job| Some((job, job.batch.as_ref()?))).collect();
+    write_manifest(dir, &members.iter().map(|(job, m)| m.dir_name(&job.name)).collect::<Vec<_>>())?;
+    let names: Vec<&str> = members.iter().map(|(job, _)| job.name.as_str()).collect();
+    msg!("Building {} packages together: {}", members.len(), names.join(", "));
+
+    let target_dir = dir.join("target");
+    let mut args = Vec::new();
+    args.push_str("build").push_str("--release").push_str("--workspace").push_str("--bins");
+    args.push_str("--manifest-path").push_str(dir.join("Cargo.toml").to_string_lossy());
+    args.push_str("--target-dir").push_str(target_dir.to_string_lossy());
+    if let Some(jobs) = build_jobs {
+        args.push_str("--jobs").push_str(jobs.to_string());
+    }
+    let features: BTreeSet<String> = members
+        .iter()
+        .flat_map(|(job, m)| m.features.iter().map(move |f| format!("{}/{f}", job.name)))
+        .collect();
+    if !features.is_empty() {
+        args.push_str("--features").push_str(features.into_iter().collect::<Vec<_>>().join(","));
+    }
+    let mut cmd = Command::new(cargo_exe);
+    cmd.args(&args);
+    // the jobs all have the same settings for the environment, e.g. a rustc wrapper
+    if let Some(job) = batched.first() {
+        cmd.envs(job.env.iter().map(|(k, v)| (k, v)));
+    }
+    dbgmsg!("{} {}", cargo_exe.to_string_lossy(), args.join(" "));
+    let status = cmd.status().context("Failed to run `cargo build`")?;
+    if !status.success() {
+        warnmsg!("Warning: the batched build failed, installing the packages one at a time");
+        rest.extend(batched);
+        return Ok((Vec::new(), rest));
+    }
+
+    let bin_dir = cargo_home()?.join("bin");
+    let rustc = rustc_info()?;
+    let mut installed = Vec::new();
+    let mut results = Vec::new();
+    let duration = start.elapsed() / members.len() as u32;
+    let mut command = vec![cargo_exe.to_string_lossy().into_owned()];
+    command.extend(args);
+    for job in batched {
+        let Some(member) = &job.batch else { continue };
+        if let Err(e) = install_bins(&target_dir, &bin_dir, &member.bins) {
+            warnmsg!("Warning: couldn't install {} from the batch: {e:#}", job.name);
+            rest.push(job);
+            continue;
+        }
+        installed.push((member.pkg_id.clone(), member.new_pkg_id(&job.name)));
+        let mut res = JobResult::new(cargo_exe, &job, Outcome::Updated, duration, None);
+        res.command = command.clone();
+        okmsg!("Finished {} {}", job.name, member.version);
+        results.push(res);
+    }
+    if let Err(e) = record(&installed, &rustc) {
+        bail!("the programs were installed but cargo's metadata couldn't be updated: {e:#}");
+    }
+    Ok((results, rest))
+}
@@ -0,0 +1,710 @@
+//! The optional config file, by default `~/.config/cargo-update-installed/config.toml`.
+//!
+//! Example:
+//! ```toml
+//! [defaults]
+//! locked = true
+//! jobs = 4
+//! rustc-wrapper = "sccache"
+//!
+//! [source.git]
+//! locked = true
+//!
+//! [source.path]
+//! skip = true
+//!
+//! [package.ripgrep]
+//! extra-args = ["--features", "pcre2"]
+//!
+//! [package.some-nightly-tool]
+//! toolchain = "nightly"
+//!
+//! [package.cargo-nextest]
+//! backend = "binstall"
+//!
+//! [package.my-tool]
+//! command = ["my-tool-installer", "--version", "{version}"]
+//!
+//! [pin]
+//! cargo-edit = "0.12.2"
+//!
+//! [priority]
+//! ripgrep = 10
+//! "cargo-*" = -10
+//!
+//! [groups]
+//! cli-tools = ["ripgrep", "fd-find", "bat"]
+//! cargo = ["cargo-*"]
+//!
+//! [backup]
+//! keep = 3
+//! max-size = "500M"
+//!
+//! [cross.images]
+//! aarch64-unknown-linux-musl = "ghcr.io/cross-rs/aarch64-unknown-linux-musl:main"
+//!
+//! [container]
+//! engine = "podman"
+//! image = "docker.io/library/rust:1-slim"
+//! args = ["--memory", "4g"]
+//!
+//! [vet]
+//! store = "/home/me/src/policy/supply-chain"
+//! criteria = "safe-to-run"
+//!
+//! [target.'cfg(target_os = "macos")'.defaults]
+//! exclude = ["cargo-watch"]
+//!
+//! [target.'cfg(windows)'.package.ripgrep]
+//! backend = "binstall"
+//!
+//! [profile.laptop.defaults]
+//! jobs = 2
+//! exclude = ["cargo-*"]
+//!
+//! [profile.buildserver]
+//! hosts = ["build-*"]
+//!
+//! [profile.buildserver.defaults]
+//! jobs = 32
+//! ```
+//!
+//! The `[target]` tables are used on the platforms their `cfg(...)` expression matches, with
+//! `unix`, `windows`, `target_os`, `target_family`, and `target_arch` as in cargo, so that one file
+//! can be shared between machines running different systems. Their settings override the rest of
+//! the file like a profile's, in the order they're written, and a profile overrides them.
+//!
+//! A profile is used on the machine with the same hostname, or whose hostname matches one of its
+//! `hosts` patterns, or when it's chosen with --profile. Its settings override the rest of the
+//! file: tables are merged key by key, and anything else, including lists, is replaced.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use once_cell::sync::OnceCell;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use crate::filter::Filter;
+use crate::package_data::{Package, PackageSource};
+use crate::platform;
+use crate::theme::{ColorName, Preset};
+use crate::toml::{self, Lines};
+use crate::util::{self, Size};
+use crate::SortOrder;
+
+/// The profile chosen with --profile, otherwise it's picked by hostname
+pub static PROFILE: OnceCell<String> = OnceCell::new();
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    /// Defaults for command-line options, which the options themselves override
+    pub defaults: Defaults,
+    /// Defaults for packages installed from each kind of source, which override `defaults`
+    pub source: SourceDefaults,
+    /// Settings for individual packages, by name
+    pub package: BTreeMap<String, PackageConfig>,
+    /// Exact versions to keep packages at, by name
+    pub pin: BTreeMap<String, String>,
+    /// How many previous versions of packages to keep, for rolling back
+    pub backup: BackupConfig,
+    /// Named lists of package names or patterns, for --group
+    pub groups: BTreeMap<String, Vec<Filter>>,
+    /// Priorities for `--sort priority` by package name or pattern, higher goes first
+    pub priority: BTreeMap<String, i32>,
+    /// Settings for building packages for other targets with `cross`
+    pub cross: CrossConfig,
+    /// Settings for building packages in a container with docker or podman
+    pub container: ContainerConfig,
+    /// Checking updates against a cargo-vet audit store
+    pub vet: VetConfig,
+    /// Prefixes of git URLs to replace, for repos which have moved since packages were installed
+    /// from them
+    pub git_rewrite: BTreeMap<String, String>,
+    /// Proxy and certificate settings for our own requests
+    pub http: HttpConfig,
+    /// The colors of messages
+    pub colors: ColorsConfig,
+    /// The profile which was used, if any
+    #[serde(skip)]
+    pub profile: Option<String>,
+    /// Where the config was loaded from, if it was
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+}
+
+/// The colors of messages, see [`theme`](crate::theme)
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ColorsConfig {
+    /// The set of colors to start from
+    pub theme: Preset,
+    pub info: Option<ColorName>,
+    pub success: Option<ColorName>,
+    pub skip: Option<ColorName>,
+    pub warning: Option<ColorName>,
+    pub error: Option<ColorName>,
+    /// Make every message bold, or none of them, whatever the theme does
+    pub bold: Option<bool>,
+}
+
+/// Settings for the requests made by the network features. Cargo's own settings are used for
+/// those which aren't set, see [`http`](crate::http).
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct HttpConfig {
+    /// The proxy to use, e.g. "http://proxy.example.com:3128"
+    pub proxy: Option<String>,
+    /// A bundle of CA certificates to verify servers with
+    pub cainfo: Option<PathBuf>,
+    /// Verify servers with the operating system's certificate store, instead of curl's bundle
+    pub native_ca: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct VetConfig {
+    /// The store's directory, with audits.toml, imports.lock, and config.toml
+    pub store: Option<PathBuf>,
+    /// The criteria versions have to be audited for
+    pub criteria: String,
+    /// Skip updates to unvetted versions instead of only warning about them
+    pub require: bool,
+}
+
+impl Default for VetConfig {
+    fn default() -> Self {
+        Self { store: None, criteria: "safe-to-deploy".into(), require: false }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct CrossConfig {
+    /// Container images to use instead of cross's own, by target triple
+    pub images: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ContainerConfig {
+    /// The docker or podman program, instead of whichever is found first in PATH
+    pub engine: Option<PathBuf>,
+    /// The image to build in, which has to have cargo in its PATH
+    pub image: Option<String>,
+    /// Extra arguments for `docker run`, e.g. to limit its memory or network
+    pub args: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct BackupConfig {
+    /// Previous versions to keep of each package, 0 to not save them at all
+    pub keep: u32,
+    /// Limit on the total size of saved versions, the oldest are removed to stay under it
+    pub max_size: Option<Size>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct PackageConfig {
+    /// Arguments added to the end of this package's `cargo install` command
+    pub extra_args: Vec<String>,
+    /// Rustup toolchain to build this package with, e.g. "nightly"
+    pub toolchain: Option<String>,
+    /// Build this package even if it needs a newer Rust than the toolchain
+    pub ignore_rust_version: bool,
+    /// How to install this package, instead of `cargo install`
+    pub backend: Option<Backend>,
+    /// A command to run instead of any backend, where "{name}", "{version}", and "{target}" are
+    /// replaced by the package's name, installed version, and target
+    pub command: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// `cargo install`
+    Cargo,
+    /// `cargo binstall`, which downloads prebuilt programs when the package has them
+    Binstall,
+    /// `cross install`, which builds in a container
+    Cross,
+    /// `cargo install` in a docker or podman container, see `[container]`
+    Container,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Defaults {
+    pub include: Vec<Filter>,
+    pub exclude: Vec<Filter>,
+    pub force: Option<bool>,
+    pub locked: Option<bool>,
+    pub ignore_rust_version: Option<bool>,
+    pub jobs: Option<u32>,
+    pub sort: Option<SortOrder>,
+    pub rustc_wrapper: Option<PathBuf>,
+    pub auditable: Option<bool>,
+    pub check_publisher: Option<bool>,
+    pub cross: Option<bool>,
+    pub container: Option<bool>,
+    pub yes: Option<bool>,
+    /// Check for a newer version of this program
+    pub check_self: Option<bool>,
+    /// Keep updates off versions with RustSec security advisories
+    pub check_advisories: Option<bool>,
+    /// Hosts (or patterns) to fetch git packages from over SSH, like --git-ssh for only them
+    pub git_ssh_hosts: Vec<String>,
+    pub cooldown: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct SourceDefaults {
+    pub registry: SourceConfig,
+    pub git: SourceConfig,
+    pub path: SourceConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct SourceConfig {
+    pub force: Option<bool>,
+    pub locked: Option<bool>,
+    pub jobs: Option<u32>,
+    /// Skip these packages unless they're matched by an --include pattern
+    pub skip: bool,
+}
+
+/// Whether a profile's name or `hosts` pattern matches a hostname, with or without its domain
+fn matches_hostname(pattern: &str, hostname: &str) -> bool {
+    let short = hostname.split('.').next().unwrap_or(hostname);
+    glob::Pattern::new(&pattern.to_lowercase())
+        .is_ok_and(|p| p.matches(&hostname.to_lowercase()) || p.matches(&short.to_lowercase()))
+}
+
+/// Merge a profile's settings over the rest of the config: tables key by key, and other values,
+/// including lists, by replacing them
+fn merge(base: &mut Value, over: Value) {
+    match (base, over) {
+        (Value::Object(base), Value::Object(over)) => {
+            for (key, value) in over {
+                merge(base.entry(key).or_insert(Value::Null), value);
+            }
+        }
+        (base, over) => *base = over,
+    }
+}
+
+/// Remove the `[target]` tables from the parsed config file, merging those which match this
+/// platform over the rest
+fn apply_targets(config: &mut Value) -> Result<()> {
+    let targets = match config.as_object_mut().and_then(|t| t.remove("target")) {
+        Some(Value::Object(targets)) => targets,
+        Some(_) => bail!("`target` isn't a table"),
+        None => return Ok(()),
+    };
+    for (cfg, settings) in targets {
+        if platform::matches(&cfg)? {
+            dbgmsg!("Using the config for {cfg}");
+            merge(config, settings);
+        }
+    }
+    Ok(())
+}
+
+/// Remove the profiles from the parsed config file, merging the one for this machine over the rest.
+/// Returns the name of the profile, if one was used.
+fn apply_profile(config: &mut Value) -> Result<Option<String>> {
+    let profiles = match config.as_object_mut().and_then(|t| t.remove("profile")) {
+        Some(Value::Object(profiles)) => profiles,
+        Some(_) => bail!("`profile` isn't a table"),
+        None => Map::new(),
+    };
+    let hostname = util::hostname();
+    let mut chosen = None;
+    for (name, profile) in &profiles {
+        let Value::Object(profile) = profile else { bail!("[profile.{name}] isn't a table") };
+        let mut profile = profile.clone();
+        // already checked by `validate`
+        let hosts: Vec<String> = profile
+            .remove("hosts")
+            .and_then(|h| serde_json::from_value(h).ok())
+            .unwrap_or_default();
+        let wanted = match (PROFILE.get(), &hostname) {
+            (Some(wanted), _) => wanted == name,
+            (None, Some(hostname)) => {
+                matches_hostname(name, hostname)
+                    || hosts.iter().any(|h| matches_hostname(h, hostname))
+            }
+            (None, None) => false,
+        };
+        if wanted && chosen.is_none() {
+            chosen = Some((name, profile));
+        }
+    }
+
+    match (chosen, PROFILE.get()) {
+        (Some((name, profile)), _) => {
+            dbgmsg!("Using config profile '{name}'");
+            merge(config, Value::Object(profile));
+            return Ok(Some(name.clone()));
+        }
+        (None, Some(wanted)) if profiles.is_empty() => {
+            bail!("No profiles are defined in the config file, so there's no '{wanted}'")
+        }
+        (None, Some(wanted)) => bail!(
+            "Unknown profile '{wanted}', the profiles are: {}",
+            profiles.keys().map(String::as_str).collect::<Vec<_>>().join(", ")
+        ),
+        (None, None) => (),
+    }
+    Ok(None)
+}
+
+/// Write a key path the way it would be in the file, e.g. `priority."cargo-*"`
+fn dotted(path: &[String]) -> String {
+    let bare = |k: &str| {
+        !k.is_empty() && k.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    };
+    let parts: Vec<String> = path
+        .iter()
+        .map(|k| if bare(k) { k.clone() } else { Value::from(k.as_str()).to_string() })
+        .collect();
+    parts.join(".")
+}
+
+/// An error about a setting, with the line it's on
+fn error_at(lines: &Lines, path: &[String], e: impl fmt::Display) -> anyhow::Error {
+    match lines.line(path) {
+        _ if path.is_empty() => anyhow!("{e}"),
+        Some(line) => anyhow!("line {line}: `{}`: {e}", dotted(path)),
+        None => anyhow!("`{}`: {e}", dotted(path)),
+    }
+}
+
+/// Find the innermost setting which is invalid by itself. Settings are tried one at a time, with
+/// only the tables leading to them around them, which works because every setting is optional.
+fn locate(settings: &Value, e: serde_json::Error) -> (Vec<String>, serde_json::Error) {
+    let mut path = Vec::new();
+    let mut error = e;
+    let mut current = settings;
+    'descend: while let Value::Object(table) = current {
+        for (key, value) in table {
+            path.push(key.clone());
+            let alone = path.iter().rev().fold(value.clone(), |v, k| json!({ k: v }));
+            if let Err(e) = serde_json::from_value::<Config>(alone) {
+                error = e;
+                current = value;
+                continue 'descend;
+            }
+            path.pop();
+        }
+        break;
+    }
+    (path, error)
+}
+
+/// Check one set of settings, the main ones or a profile's at `prefix`, so that typos and wrong
+/// types are reported with where they are rather than ignored or found later
+fn check(settings: &Map<String, Value>, prefix: &[String], lines: &Lines) -> Result<()> {
+    let value = Value::Object(settings.clone());
+    if let Err(e) = serde_json::from_value::<Config>(value.clone()) {
+        let (path, e) = locate(&value, e);
+        return Err(error_at(lines, &[prefix, &path].concat(), e));
+    }
+    let path = |keys: &[&String]| -> Vec<String> {
+        prefix.iter().chain(keys.iter().copied()).cloned().collect()
+    };
+    if let Some(Value::Object(priority)) = settings.get("priority") {
+        let table = "priority".to_owned();
+        for pattern in priority.keys() {
+            if let Err(e) = glob::Pattern::new(pattern) {
+                return Err(error_at(lines, &path(&[&table, pattern]), e));
+            }
+        }
+    }
+    if let Some(Value::Object(packages)) = settings.get("package") {
+        let table = "package".to_owned();
+        for (name, package) in packages {
+            let command = package.get("command").and_then(Value::as_array);
+            if package.get("backend").is_some() && command.is_some_and(|c| !c.is_empty()) {
+                let e = "can't have both a backend and a command";
+                return Err(error_at(lines, &path(&[&table, name]), e));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check the whole config file, including the profiles and `[target]` tables which aren't used on
+/// this machine
+fn validate(config: &Value, lines: &Lines) -> Result<()> {
+    let Value::Object(config) = config else { bail!("The config isn't a table") };
+    let mut settings = config.clone();
+    let profile = "profile".to_owned();
+    let profiles = match settings.remove(&profile) {
+        Some(Value::Object(profiles)) => profiles,
+        Some(_) => return Err(error_at(lines, &[profile], "expected a table of profiles")),
+        None => Map::new(),
+    };
+    let target = "target".to_owned();
+    let targets = match settings.remove(&target) {
+        Some(Value::Object(targets)) => targets,
+        Some(_) => return Err(error_at(lines, &[target], "expected a table of `cfg(...)` tables")),
+        None => Map::new(),
+    };
+    check(&settings, &[], lines)?;
+    for (cfg, settings) in targets {
+        let prefix = [target.clone(), cfg];
+        platform::matches(&prefix[1]).map_err(|e| error_at(lines, &prefix, e))?;
+        let Value::Object(settings) = settings else {
+            return Err(error_at(lines, &prefix, "expected a table"));
+        };
+        check(&settings, &prefix, lines)?;
+    }
+    for (name, settings) in profiles {
+        let prefix = [profile.clone(), name];
+        let Value::Object(mut settings) = settings else {
+            return Err(error_at(lines, &prefix, "expected a table"));
+        };
+        if let Some(hosts) = settings.remove("hosts") {
+            let path = [&prefix[..], &["hosts".to_owned()]].concat();
+            let hosts: Vec<String> =
+                serde_json::from_value(hosts).map_err(|e| error_at(lines, &path, e))?;
+            for host in &hosts {
+                glob::Pattern::new(host).map_err(|e| error_at(lines, &path, e))?;
+            }
+        }
+        check(&settings, &prefix, lines)?;
+    }
+    Ok(())
+}
+
+impl Config {
+    /// Where the config file is read from when --config isn't given
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("cargo-update-installed").join("config.toml"))
+    }
+
+    /// Load the config file. An explicitly given path must exist, but the default one is optional.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let (path, required) = match path {
+            Some(path) => (path.to_owned(), true),
+            None => match Self::default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Self::default()),
+            },
+        };
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            // a profile can't be used without a config file to have it in
+            Err(e)
+                if e.kind() == io::ErrorKind::NotFound && !required && PROFILE.get().is_none() =>
+            {
+                return Ok(Self::default())
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read '{}'", path.display()))
+            }
+        };
+        dbgmsg!("Loading config from {}", path.display());
+        let mut config =
+            Self::parse(&text).with_context(|| format!("Failed to parse '{}'", path.display()))?;
+        config.path = Some(path);
+        Ok(config)
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let (mut value, lines) = toml::parse_with_lines(text)?;
+        validate(&value, &lines)?;
+        apply_targets(&mut value)?;
+        let profile = apply_profile(&mut value)?;
+        let mut config: Self = serde_json::from_value(value)?;
+        config.profile = profile;
+        // a profile's backend or command can meet the other in the main settings
+        for (name, package) in &config.package {
+            ensure!(
+                package.backend.is_none() || package.command.is_empty(),
+                "[package.{name}] can't have both a backend and a command, one of them from the profile"
+            );
+        }
+        Ok(config)
+    }
+
+    /// Get the defaults for packages from a kind of source
+    pub fn source(&self, source: &PackageSource) -> &SourceConfig {
+        match source {
+            PackageSource::Registry(_) => &self.source.registry,
+            PackageSource::Git { .. } => &self.source.git,
+            PackageSource::Path(_) => &self.source.path,
+        }
+    }
+
+    /// Get the version a package is pinned to, if any
+    pub fn pin(&self, name: &str) -> Option<&str> {
+        self.pin.get(name).map(|v| v.trim_start_matches('='))
+    }
+
+    /// Get a package's priority. An exact name takes precedence over patterns, and a longer
+    /// pattern over a shorter one because it's probably more specific.
+    pub fn priority(&self, name: &str) -> i32 {
+        if let Some(&p) = self.priority.get(name) {
+            return p;
+        }
+        self.priority
+            .iter()
+            .filter(|(pattern, _)| glob::Pattern::new(pattern).is_ok_and(|p| p.matches(name)))
+            .max_by_key(|(pattern, _)| pattern.len())
+            .map_or(0, |(_, &p)| p)
+    }
+
+    /// Where a git repo has moved to according to `[git-rewrite]`, replacing the longest prefix of
+    /// its URL which has a rule. Returns None if none of them match.
+    pub fn rewrite_git_url(&self, url: &str) -> Option<String> {
+        self.git_rewrite
+            .iter()
+            .filter(|(old, _)| url.starts_with(old.as_str()))
+            .max_by_key(|(old, _)| old.len())
+            .map(|(old, new)| format!("{new}{}", &url[old.len()..]))
+    }
+
+    /// Point a git package at where its repo has moved to, if it has. Returns whether it was
+    /// changed.
+    pub fn rewrite_git_source(&self, pkg: &mut Package) -> bool {
+        let PackageSource::Git { url, .. } = &mut pkg.source else {
+            return false;
+        };
+        let Some(new) = self.rewrite_git_url(url) else {
+            return false;
+        };
+        dbgmsg!("{} has moved from {url} to {new}", pkg.name);
+        *url = new;
+        true
+    }
+
+    /// Get the patterns of a group
+    pub fn group(&self, name: &str) -> Result<&[Filter]> {
+        match self.groups.get(name) {
+            Some(filters) => Ok(filters),
+            None if self.groups.is_empty() => {
+                bail!("No package groups are defined in the config file")
+            }
+            None => bail!(
+                "Unknown group '{name}', the groups are: {}",
+                self.groups.keys().map(String::as_str).collect::<Vec<_>>().join(", ")
+            ),
+        }
+    }
+
+    /// Get the settings for a package, or the defaults if there aren't any
+    pub fn package(&self, name: &str) -> &PackageConfig {
+        static DEFAULT: PackageConfig = PackageConfig {
+            extra_args: Vec::new(),
+            toolchain: None,
+            ignore_rust_version: false,
+            backend: None,
+            command: Vec::new(),
+        };
+        self.package.get(name).unwrap_or(&DEFAULT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Always true and never true, whatever the platform
+    const EVERYWHERE: &str = r#"'cfg(not(target_os = "none-such"))'"#;
+    const NOWHERE: &str = r#"'cfg(target_os = "none-such")'"#;
+
+    fn strings(filters: &[Filter]) -> Vec<String> {
+        filters.iter().map(Filter::to_string).collect()
+    }
+
+    #[test]
+    fn targets_override_in_order() {
+        let config = Config::parse(&format!(
+            r#"
+            [defaults]
+            jobs = 1
+            locked = true
+            exclude = ["a", "b"]
+
+            [target.{EVERYWHERE}.defaults]
+            jobs = 2
+            exclude = ["c"]
+
+            [target.{NOWHERE}.defaults]
+            jobs = 3
+            yes = true
+            "#
+        ))
+        .unwrap();
+        assert_eq!(config.defaults.jobs, Some(2));
+        assert_eq!(config.defaults.locked, Some(true));
+        // lists are replaced rather than added to
+        assert_eq!(strings(&config.defaults.exclude), ["c"]);
+        assert_eq!(config.defaults.yes, None);
+        assert_eq!(config.profile, None);
+    }
+
+    #[test]
+    fn profile_overrides_targets() {
+        if util::hostname().is_none() {
+            return;
+        }
+        let config = Config::parse(&format!(
+            r#"
+            [defaults]
+            jobs = 1
+
+            [package.ripgrep]
+            extra-args = ["--features", "pcre2"]
+            toolchain = "stable"
+
+            [target.{EVERYWHERE}.defaults]
+            jobs = 2
+            cross = true
+
+            [profile.anywhere]
+            hosts = ["*"]
+
+            [profile.anywhere.defaults]
+            jobs = 4
+
+            [profile.anywhere.package.ripgrep]
+            toolchain = "nightly"
+
+            [profile.nowhere]
+            hosts = ["no-such-host-*"]
+
+            [profile.nowhere.defaults]
+            yes = true
+            "#
+        ))
+        .unwrap();
+        assert_eq!(config.profile.as_deref(), Some("anywhere"));
+        assert_eq!(config.defaults.jobs, Some(4));
+        assert_eq!(config.defaults.cross, Some(true));
+        assert_eq!(config.defaults.yes, None);
+        // tables are merged key by key
+        let ripgrep = config.package("ripgrep");
+        assert_eq!(ripgrep.extra_args, ["--features", "pcre2"]);
+        assert_eq!(ripgrep.toolchain.as_deref(), Some("nightly"));
+    }
+
+    #[test]
+    fn hostname_patterns() {
+        assert!(matches_hostname("laptop", "laptop"));
+        assert!(matches_hostname("laptop", "Laptop.example.com"));
+        assert!(matches_hostname("build-*", "build-07"));
+        assert!(!matches_hostname("build-*", "laptop"));
+    }
+}
@@ -0,0 +1,83 @@
+//! Building packages in a docker or podman container, so that their build scripts and proc macros
+//! can't touch anything on the host except where the programs are installed.
+//!
+//! Cargo runs in the container with `--root /install`, where CARGO_HOME's bin directory and
+//! cargo's metadata files are mounted, so the host's records are updated the same way as by a
+//! normal install. Nothing else of CARGO_HOME is mounted, so the container downloads its own copy
+//! of the registry index and can't read registry tokens. Local path sources are mounted read-only
+//! at the same path, since that's what cargo records.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+
+use crate::config::ContainerConfig;
+use crate::package_data::*;
+use crate::util;
+use crate::PushStr;
+
+/// The image used when `[container]` doesn't set one
+pub const DEFAULT_IMAGE: &str = "docker.io/library/rust:latest";
+
+/// Where CARGO_HOME's bin directory and metadata are mounted in the container, for `--root`
+pub const INSTALL_ROOT: &str = "/install";
+
+/// Find the container engine, from the config or else podman or docker in PATH
+pub fn engine(config: &ContainerConfig) -> Result<PathBuf> {
+    if let Some(engine) = &config.engine {
+        return Ok(util::find_program(&engine.to_string_lossy()).unwrap_or_else(|| engine.clone()));
+    }
+    util::find_program("podman").or_else(|| util::find_program("docker")).ok_or_else(|| {
+        anyhow!(
+            "Neither podman nor docker is installed for the container backend, install one or set \
+             `engine` in the [container] table"
+        )
+    })
+}
+
+fn is_podman(engine: &Path) -> bool {
+    engine.file_stem().is_some_and(|s| s.to_string_lossy().starts_with("podman"))
+}
+
+/// Add the volume argument to mount a host path in the container
+fn mount(args: &mut Vec<String>, host: &Path, container: &str, options: &str) {
+    args.push_str("--volume").push_str(format!("{}:{container}{options}", host.display()));
+}
+
+/// The `run` arguments for the engine, up to and including the `cargo` to run in the container
+pub fn run_args(engine: &Path, config: &ContainerConfig, pkg: &Package) -> Result<Vec<String>> {
+    let cargo_home = cargo_home()?;
+    let mut args = Vec::new();
+    args.push_str("run").push_str("--rm");
+
+    // run as the user so that the installed programs belong to them rather than root
+    if is_podman(engine) {
+        args.push_str("--userns=keep-id");
+    } else {
+        #[cfg(unix)]
+        {
+            // SAFETY: these can't fail
+            let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+            args.push_str("--user").push_str(format!("{uid}:{gid}"));
+        }
+    }
+    // the image's CARGO_HOME usually belongs to root, and path sources are mounted read-only
+    args.push_str("--env").push_str("CARGO_HOME=/tmp/cargo-home");
+    args.push_str("--env").push_str("CARGO_TARGET_DIR=/tmp/target");
+
+    mount(&mut args, &cargo_home.join("bin"), &format!("{INSTALL_ROOT}/bin"), "");
+    for file in [".crates2.json", ".crates.toml"] {
+        let path = cargo_home.join(file);
+        if path.is_file() {
+            mount(&mut args, &path, &format!("{INSTALL_ROOT}/{file}"), "");
+        }
+    }
+    if let PackageSource::Path(path) = &pkg.source {
+        mount(&mut args, path, &path.to_string_lossy(), ":ro");
+    }
+
+    args.extend(config.args.iter().cloned());
+    args.push_str(config.image.as_deref().unwrap_or(DEFAULT_IMAGE));
+    args.push_str("cargo");
+    Ok(args)
+}
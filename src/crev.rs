@@ -0,0 +1,181 @@
+//! Reading cargo-crev reviews, to show what others thought of a version before updating to it.
+//!
+//! Proofs are read from crev's local proof repository and the repositories it has fetched
+//! (`cargo crev repo fetch ...`). Their signatures aren't checked again here. Reviewers count as
+//! trusted if one of the user's own identities has a trust proof for them, without following
+//! trust any further.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+
+/// A package review
+struct Review {
+    from: String,
+    name: String,
+    version: String,
+    rating: String,
+}
+
+/// The review counts for one version of a package
+#[derive(Debug, Default)]
+pub struct Reviews {
+    pub positive: usize,
+    pub neutral: usize,
+    pub negative: usize,
+    /// How many of the reviews are from trusted reviewers
+    pub trusted: usize,
+}
+
+impl fmt::Display for Reviews {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        for (count, kind) in
+            [(self.positive, "positive"), (self.neutral, "neutral"), (self.negative, "negative")]
+        {
+            if count > 0 {
+                parts.push(format!("{count} {kind}"));
+            }
+        }
+        if parts.is_empty() {
+            return f.write_str("none");
+        }
+        write!(f, "{}", parts.join(", "))?;
+        if self.trusted > 0 {
+            write!(f, " ({} trusted)", self.trusted)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Crev {
+    reviews: Vec<Review>,
+    trusted: BTreeSet<String>,
+}
+
+/// Flatten the YAML of a proof into ("package.name", "value") pairs. Proofs only use simple
+/// mappings and lists of mappings, so this doesn't need to handle much of YAML.
+fn fields(body: &str) -> Vec<(String, String)> {
+    let mut fields = Vec::new();
+    let mut path: Vec<(usize, String)> = Vec::new();
+    for line in body.lines().filter(|l| !l.trim().is_empty()) {
+        let mut indent = line.len() - line.trim_start().len();
+        let mut text = line.trim_start();
+        if let Some(item) = text.strip_prefix("- ") {
+            indent += 2;
+            text = item;
+        }
+        let Some((key, value)) = text.split_once(':') else { continue };
+        while path.last().is_some_and(|(i, _)| *i >= indent) {
+            path.pop();
+        }
+        let full: Vec<&str> =
+            path.iter().map(|(_, k)| k.as_str()).chain(std::iter::once(key.trim())).collect();
+        let value = value.trim().trim_matches('"');
+        if value.is_empty() {
+            path.push((indent, key.trim().to_owned()));
+        } else {
+            fields.push((full.join("."), value.to_owned()));
+        }
+    }
+    fields
+}
+
+/// Find the proof files under a directory
+fn proof_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            proof_files(&path, files);
+        } else if path.extension().is_some_and(|e| e == "crev") {
+            files.push(path);
+        }
+    }
+}
+
+impl Crev {
+    /// Load the reviews, if the user has a crev identity
+    pub fn load() -> Result<Option<Self>> {
+        let Some(ids_dir) = dirs::config_dir().map(|d| d.join("crev").join("ids")) else {
+            return Ok(None);
+        };
+        let own: BTreeSet<String> = match fs::read_dir(&ids_dir) {
+            Ok(entries) => entries
+                .flatten()
+                .filter_map(|e| Some(e.path().file_stem()?.to_str()?.to_owned()))
+                .collect(),
+            Err(_) => return Ok(None),
+        };
+        if own.is_empty() {
+            return Ok(None);
+        }
+
+        let mut files = Vec::new();
+        for dir in [dirs::data_dir(), dirs::cache_dir()].into_iter().flatten() {
+            proof_files(&dir.join("crev"), &mut files);
+        }
+        dbgmsg!("Reading {} crev proof files", files.len());
+
+        let mut reviews = Vec::new();
+        let mut trusted = own.clone();
+        for file in files {
+            let Ok(text) = fs::read_to_string(&file) else { continue };
+            for proof in text.split("----- BEGIN CREV ").skip(1) {
+                let Some((kind, rest)) = proof.split_once(" -----") else { continue };
+                // the signature comes after the body
+                let body = rest.split("----- SIGN CREV").next().unwrap_or(rest);
+                let body = body.split("----- BEGIN CREV").next().unwrap_or(body);
+                let fields = fields(body);
+                let get = |key: &str| fields.iter().find(|(k, _)| k == key).map(|(_, v)| v.clone());
+                let Some(from) = get("from.id") else { continue };
+                match kind {
+                    "PACKAGE REVIEW" => {
+                        if get("package.source").as_deref() != Some("https://crates.io") {
+                            continue;
+                        }
+                        let (Some(name), Some(version)) =
+                            (get("package.name"), get("package.version"))
+                        else {
+                            continue;
+                        };
+                        let rating = get("review.rating").unwrap_or_else(|| "neutral".into());
+                        reviews.push(Review { from, name, version, rating });
+                    }
+                    "TRUST" if own.contains(&from) => {
+                        let level = get("trust").unwrap_or_default();
+                        if matches!(level.as_str(), "low" | "medium" | "high") {
+                            trusted.extend(
+                                fields
+                                    .iter()
+                                    .filter(|(k, _)| k == "ids.id")
+                                    .map(|(_, v)| v.clone()),
+                            );
+                        }
+                    }
+                    _ => (),
+                }
+            }
+        }
+        Ok(Some(Self { reviews, trusted }))
+    }
+
+    /// Count the reviews of a version of a crates.io package
+    pub fn reviews(&self, name: &str, version: &str) -> Reviews {
+        let mut counts = Reviews::default();
+        for review in self.reviews.iter().filter(|r| r.name == name && r.version == version) {
+            match review.rating.as_str() {
+                "strong" | "positive" => counts.positive += 1,
+                "negative" | "dangerous" => counts.negative += 1,
+                _ => counts.neutral += 1,
+            }
+            if self.trusted.contains(&review.from) {
+                counts.trusted += 1;
+            }
+        }
+        counts
+    }
+}
@@ -0,0 +1,67 @@
+//! Removing stale duplicate entries from cargo's metadata.
+
+use anyhow::Result;
+use clap::Parser;
+
+use crate::package_data::*;
+use crate::snapshot;
+
+/// Remove stale entries for packages which are recorded more than once.
+///
+/// Old versions of cargo, or editing .crates2.json by hand, can leave a package recorded at more
+/// than one version. Updates only use the newest entry, and this removes the others from
+/// .crates2.json and .crates.toml. Entries with programs which the newest one doesn't have are
+/// kept, since removing them would leave those programs untracked.
+#[derive(Debug, Parser)]
+pub struct DedupeArgs {
+    /// Only list the entries which would be removed.
+    #[arg(short = 'n', long)]
+    dry_run: bool,
+}
+
+/// Remove entries from .crates2.json and .crates.toml
+fn remove_entries(ids: &[&str]) -> Result<()> {
+    let changes: Vec<(&str, Option<&str>)> = ids.iter().map(|id| (*id, None)).collect();
+    rewrite_installs(&changes, |_, _| ())
+}
+
+pub fn run(args: &DedupeArgs, crates2: &Crates2) -> Result<()> {
+    let duplicates = crates2.duplicates();
+    if duplicates.is_empty() {
+        msg!("No packages are recorded more than once");
+        return Ok(());
+    }
+
+    let mut stale = Vec::new();
+    for (newest, others) in &duplicates {
+        let bins = &crates2.installs[*newest].bins;
+        for id in others {
+            let extra: Vec<&str> = crates2.installs[*id]
+                .bins
+                .iter()
+                .filter(|b| !bins.contains(b))
+                .map(String::as_str)
+                .collect();
+            if extra.is_empty() {
+                println!("{id} (superseded by {newest})");
+                stale.push(*id);
+            } else {
+                warnmsg!(
+                    "Warning: keeping '{id}', it has programs '{newest}' doesn't: {}",
+                    extra.join(", ")
+                );
+            }
+        }
+    }
+    if args.dry_run || stale.is_empty() {
+        return Ok(());
+    }
+
+    if let Err(e) = snapshot::save() {
+        warnmsg!("Warning: failed to save a snapshot of the installed packages: {e:#}");
+    }
+    remove_entries(&stale)?;
+    let s = if stale.len() == 1 { "y" } else { "ies" };
+    msg!("Removed {} stale entr{s}", stale.len());
+    Ok(())
+}
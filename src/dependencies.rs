@@ -0,0 +1,210 @@
+//! Estimating how many crates a package depends on, for dry runs to show how much heavier an update
+//! would make it to build.
+//!
+//! This resolves the dependencies of a version from the crates.io index, roughly the way cargo
+//! would: the newest release matching each requirement, with each crate's default features, and
+//! only the platform-specific dependencies for this platform. Features enabled by other crates and
+//! the unification of compatible versions aren't taken into account, so the count is approximate.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use anyhow::{ensure, Context, Result};
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+
+use crate::index;
+use crate::platform;
+use crate::semver::{self, Version};
+use crate::util;
+use crate::Job;
+
+#[derive(Debug, Deserialize)]
+struct Entry {
+    vers: String,
+    #[serde(default)]
+    yanked: bool,
+    #[serde(default)]
+    deps: Vec<Dependency>,
+    #[serde(default)]
+    features: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    features2: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Dependency {
+    /// The name the crate is used by, which is its name unless it's renamed
+    name: String,
+    req: String,
+    #[serde(default)]
+    optional: bool,
+    target: Option<String>,
+    kind: Option<String>,
+    /// The real name of a renamed crate
+    package: Option<String>,
+}
+
+impl Entry {
+    /// The names of the optional dependencies which the default features enable
+    fn default_optional_deps(&self) -> BTreeSet<&str> {
+        let features = |name: &str| self.features.get(name).or_else(|| self.features2.get(name));
+        let mut enabled = BTreeSet::new();
+        let mut deps = BTreeSet::new();
+        let mut todo = vec!["default"];
+        while let Some(feature) = todo.pop() {
+            if !enabled.insert(feature) {
+                continue;
+            }
+            let Some(values) = features(feature) else {
+                // an optional dependency's implicit feature
+                deps.insert(feature);
+                continue;
+            };
+            for value in values {
+                if let Some(dep) = value.strip_prefix("dep:") {
+                    deps.insert(dep);
+                } else if let Some((dep, _)) = value.split_once('/') {
+                    // "dep?/feature" only turns on a feature of a dependency that's already on
+                    if !dep.ends_with('?') {
+                        deps.insert(dep);
+                    }
+                } else {
+                    todo.push(value);
+                }
+            }
+        }
+        deps
+    }
+
+    /// The crates this version needs to build, as their real names and version requirements
+    fn build_deps(&self) -> impl Iterator<Item = (&str, &str)> {
+        let optional = self.default_optional_deps();
+        self.deps
+            .iter()
+            .filter(|d| d.kind.as_deref() != Some("dev"))
+            .filter(move |d| !d.optional || optional.contains(d.name.as_str()))
+            .filter(|d| d.target.as_deref().is_none_or(for_this_platform))
+            .map(|d| (d.package.as_deref().unwrap_or(&d.name), d.req.as_str()))
+    }
+}
+
+/// Whether a platform-specific dependency is for this platform, given as a `cfg(...)` expression
+/// or a target triple
+fn for_this_platform(target: &str) -> bool {
+    if target.starts_with("cfg(") {
+        platform::matches(target).unwrap_or(false)
+    } else {
+        // rustc is only asked once; without a host, like an unknown cfg, the dependency is skipped
+        static HOST: Lazy<Option<String>> = Lazy::new(|| {
+            util::host_target().map_err(|e| dbgmsg!("Couldn't find the host target: {e:#}")).ok()
+        });
+        HOST.as_deref() == Some(target)
+    }
+}
+
+/// Index entries of each crate looked up so far, shared between the packages being counted
+#[derive(Default)]
+struct Resolver {
+    entries: HashMap<String, Vec<(Version, Entry)>>,
+}
+
+impl Resolver {
+    /// Fetch the index files of these crates all at once, if they haven't been already
+    fn fetch(&mut self, names: &BTreeSet<&str>) {
+        let new: Vec<&str> =
+            names.iter().copied().filter(|n| !self.entries.contains_key(*n)).collect();
+        index::prefetch(new.iter().copied());
+        for name in new {
+            let entries = index::index_file(name)
+                .and_then(|body| parse(name, &body))
+                .map_err(|e| dbgmsg!("Couldn't look up the dependency {name}: {e:#}"))
+                .unwrap_or_default();
+            self.entries.insert(name.to_owned(), entries);
+        }
+    }
+
+    /// The newest release of a crate matching a requirement, or the exact version given
+    fn pick(&self, name: &str, req: &Req) -> Option<&(Version, Entry)> {
+        self.entries.get(name)?.iter().filter(|(v, _)| req.matches(v)).max_by(|a, b| a.0.cmp(&b.0))
+    }
+
+    /// Count the crates which a version of a package depends on, directly or not
+    fn count(&mut self, name: &str, version: &Version) -> Result<usize> {
+        let mut seen: BTreeSet<(String, Version)> = BTreeSet::new();
+        let mut layer = vec![(name.to_owned(), Req::Exact(version.clone()))];
+        while !layer.is_empty() {
+            self.fetch(&layer.iter().map(|(n, _)| n.as_str()).collect());
+            let mut next = Vec::new();
+            for (dep, req) in &layer {
+                let Some((version, entry)) = self.pick(dep, req) else {
+                    continue;
+                };
+                if seen.insert((dep.clone(), version.clone())) {
+                    next.extend(
+                        entry
+                            .build_deps()
+                            .map(|(dep, req)| (dep.to_owned(), Req::Matching(req.to_owned()))),
+                    );
+                }
+            }
+            layer = next;
+        }
+        let root = seen.iter().any(|(dep, v)| dep == name && v == version);
+        ensure!(root, "{name} {version} isn't in the crates.io index");
+        Ok(seen.len() - 1)
+    }
+}
+
+enum Req {
+    /// The version of the package itself
+    Exact(Version),
+    Matching(String),
+}
+
+impl Req {
+    fn matches(&self, version: &Version) -> bool {
+        match self {
+            Self::Exact(exact) => version == exact,
+            Self::Matching(req) => !version.is_prerelease() && semver::matches_req(req, version),
+        }
+    }
+}
+
+fn parse(name: &str, body: &str) -> Result<Vec<(Version, Entry)>> {
+    let mut entries = Vec::new();
+    for line in body.lines().filter(|line| !line.trim().is_empty()) {
+        let entry: Entry = serde_json::from_str(line)
+            .with_context(|| format!("Invalid index entry for {name}"))?;
+        if let Ok(version) = entry.vers.parse() {
+            if !entry.yanked {
+                entries.push((version, entry));
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// Print roughly how many crates each update would depend on, compared to the installed version
+pub fn print(jobs: &[Job]) {
+    let mut resolver = Resolver::default();
+    for job in jobs {
+        let Some((target, _)) = &job.update else { continue };
+        let new = match resolver.count(&job.name, target) {
+            Ok(n) => n,
+            Err(e) => {
+                dbgmsg!("Couldn't count the dependencies of {}: {e:#}", job.name);
+                continue;
+            }
+        };
+        let old = job.version.parse().ok().and_then(|v| resolver.count(&job.name, &v).ok());
+        match old {
+            Some(old) => msg!(
+                "{} {target} depends on about {new} crates, {} from {old} for {}",
+                job.name,
+                if new >= old { "up" } else { "down" },
+                job.version
+            ),
+            None => msg!("{} {target} depends on about {new} crates", job.name),
+        }
+    }
+}
@@ -0,0 +1,193 @@
+//! Working out why a package failed to install, from the output of `cargo install`, and which
+//! system package to install when it's missing a native library.
+
+use std::fmt;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::util;
+
+/// What kind of problem made a build fail
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Reason {
+    /// Downloading the package or its dependencies failed
+    Network,
+    /// No versions of the dependencies fit together
+    DependencyResolution,
+    /// The package or a dependency needs a newer Rust than the toolchain
+    RustVersion,
+    /// A build script couldn't find a library or its headers, e.g. with pkg-config, or a tool like
+    /// cmake
+    MissingLibrary,
+    /// Everything compiled but linking failed
+    Linker,
+    /// The package or a dependency didn't compile
+    Compile,
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Network => "network error",
+            Self::DependencyResolution => "dependency resolution",
+            Self::RustVersion => "needs a newer Rust",
+            Self::MissingLibrary => "missing system library",
+            Self::Linker => "linker error",
+            Self::Compile => "compile error",
+        })
+    }
+}
+
+/// Messages which show each kind of problem, checked in order since the later ones are often
+/// caused by the earlier ones, e.g. a missing library makes the linker fail too
+const PATTERNS: &[(Reason, &[&str])] = &[
+    (
+        Reason::Network,
+        &[
+            "failed to download",
+            "spurious network error",
+            "Could not resolve host",
+            "failed to fetch",
+            "Couldn't connect to server",
+            "Connection refused",
+            "Connection reset",
+            "failed to query replaced source registry",
+            "SSL connect error",
+        ],
+    ),
+    (
+        Reason::DependencyResolution,
+        &[
+            "failed to select a version",
+            "no matching package named",
+            "cyclic package dependency",
+            "failed to resolve patches",
+            "` in registry `",
+        ],
+    ),
+    (Reason::RustVersion, &["it requires rustc", "is not supported by the following package"]),
+    (
+        Reason::MissingLibrary,
+        &[
+            "Could not find system library",
+            "was not found in the pkg-config search path",
+            "', required by '",
+            "pkg-config exited with status",
+            "Could not run `PKG_CONFIG",
+            "The system library `",
+            ".h: No such file or directory",
+            ".h' file not found",
+            "ld: cannot find -l",
+            "ld: library not found for -l",
+            "Could not find directory of OpenSSL installation",
+            "Could not find `protoc`",
+            "is `cmake` not installed?",
+        ],
+    ),
+    (Reason::Linker, &["linking with `", "error: linker `", "undefined reference to"]),
+    (Reason::Compile, &["error[E", "could not compile"]),
+];
+
+/// Work out why a build failed from its output, if it's recognized
+pub fn classify(output: &str) -> Option<Reason> {
+    PATTERNS
+        .iter()
+        .find(|(_, patterns)| patterns.iter().any(|p| output.contains(p)))
+        .map(|(reason, _)| *reason)
+}
+
+/// The system packages which provide a library, by the names it's looked up with (its pkg-config
+/// name, linker name, or header directory), for apt, dnf, brew, and pacman
+#[rustfmt::skip]
+const SYSTEM_PACKAGES: &[(&[&str], [Option<&str>; 4])] = &[
+    (&["openssl", "ssl", "crypto"],
+        [Some("libssl-dev"), Some("openssl-devel"), Some("openssl"), Some("openssl")]),
+    (&["zlib", "z"], [Some("zlib1g-dev"), Some("zlib-devel"), Some("zlib"), Some("zlib")]),
+    (&["libgit2", "git2"],
+        [Some("libgit2-dev"), Some("libgit2-devel"), Some("libgit2"), Some("libgit2")]),
+    (&["libssh2", "ssh2"],
+        [Some("libssh2-1-dev"), Some("libssh2-devel"), Some("libssh2"), Some("libssh2")]),
+    (&["sqlite3"], [Some("libsqlite3-dev"), Some("sqlite-devel"), Some("sqlite"), Some("sqlite")]),
+    (&["libcurl", "curl"],
+        [Some("libcurl4-openssl-dev"), Some("libcurl-devel"), Some("curl"), Some("curl")]),
+    (&["libpq", "pq"],
+        [Some("libpq-dev"), Some("libpq-devel"), Some("libpq"), Some("postgresql-libs")]),
+    (&["libzstd", "zstd"], [Some("libzstd-dev"), Some("libzstd-devel"), Some("zstd"), Some("zstd")]),
+    (&["liblzma", "lzma"], [Some("liblzma-dev"), Some("xz-devel"), Some("xz"), Some("xz")]),
+    (&["bzip2", "bz2"], [Some("libbz2-dev"), Some("bzip2-devel"), Some("bzip2"), Some("bzip2")]),
+    (&["dbus-1", "dbus"], [Some("libdbus-1-dev"), Some("dbus-devel"), Some("dbus"), Some("dbus")]),
+    (&["alsa", "asound"], [Some("libasound2-dev"), Some("alsa-lib-devel"), None, Some("alsa-lib")]),
+    (&["libudev", "udev"], [Some("libudev-dev"), Some("systemd-devel"), None, Some("systemd")]),
+    (&["x11", "X11"], [Some("libx11-dev"), Some("libX11-devel"), None, Some("libx11")]),
+    (&["xcb"], [Some("libxcb1-dev"), Some("libxcb-devel"), None, Some("libxcb")]),
+    (&["fontconfig"],
+        [Some("libfontconfig1-dev"), Some("fontconfig-devel"), Some("fontconfig"), Some("fontconfig")]),
+    (&["freetype2", "freetype", "ft2build"],
+        [Some("libfreetype6-dev"), Some("freetype-devel"), Some("freetype"), Some("freetype2")]),
+    (&["gtk+-3.0", "gtk"], [Some("libgtk-3-dev"), Some("gtk3-devel"), Some("gtk+3"), Some("gtk3")]),
+    (&["libusb-1.0", "usb-1.0"],
+        [Some("libusb-1.0-0-dev"), Some("libusb1-devel"), Some("libusb"), Some("libusb")]),
+    (&["protoc"],
+        [Some("protobuf-compiler"), Some("protobuf-compiler"), Some("protobuf"), Some("protobuf")]),
+    (&["cmake"], [Some("cmake"), Some("cmake"), Some("cmake"), Some("cmake")]),
+];
+
+/// The package managers in the order of [`SYSTEM_PACKAGES`], with the program to look for and the
+/// command to install a package with
+const PACKAGE_MANAGERS: [(&str, &str); 4] = [
+    ("apt-get", "sudo apt install"),
+    ("dnf", "sudo dnf install"),
+    ("brew", "brew install"),
+    ("pacman", "sudo pacman -S"),
+];
+
+/// Find the name of the library a build was missing, e.g. "openssl"
+fn missing_library(output: &str) -> Option<String> {
+    static PATTERNS: Lazy<Vec<Regex>> = Lazy::new(|| {
+        [
+            r"The system library `([^`]+)` required by crate",
+            r"Package '?([^'\s,]+)'? was not found in the pkg-config search path",
+            r"Package '([^']+)', required by '[^']*', not found",
+            r"fatal error: '?([\w./+-]+)\.h'?: No such file",
+            r"fatal error: '([\w./+-]+)\.h' file not found",
+            r"ld: cannot find -l([\w.+-]+)",
+            r"ld: library not found for -l([\w.+-]+)",
+            r"Could not find directory of (OpenSSL) installation",
+            r"Could not find `(protoc)`",
+            r"is `(cmake)` not installed\?",
+        ]
+        .iter()
+        .map(|re| Regex::new(re).unwrap())
+        .collect()
+    });
+    let name = PATTERNS.iter().find_map(|re| Some(re.captures(output)?[1].to_owned()))?;
+    // headers are looked up by their directory, e.g. "openssl" for "openssl/ssl.h"
+    Some(name.split('/').next().unwrap_or_default().to_owned())
+}
+
+/// Suggest how to install the native library a failed build was missing, if it was missing one
+pub fn native_hint(output: &str) -> Option<String> {
+    let lib = missing_library(output)?;
+    let Some((_, packages)) = SYSTEM_PACKAGES
+        .iter()
+        .find(|(names, _)| names.iter().any(|n| n.eq_ignore_ascii_case(&lib)))
+    else {
+        return Some(format!("it needs `{lib}`, try installing its development package"));
+    };
+    let installed =
+        PACKAGE_MANAGERS.iter().position(|(prog, _)| util::find_program(prog).is_some());
+    let commands: Vec<String> = PACKAGE_MANAGERS
+        .iter()
+        .zip(packages)
+        .enumerate()
+        .filter(|(i, _)| installed.is_none_or(|idx| idx == *i))
+        .filter_map(|(_, ((_, install), package))| Some(format!("`{install} {}`", (*package)?)))
+        .collect();
+    if commands.is_empty() {
+        return Some(format!("it needs `{lib}`, try installing its development package"));
+    }
+    Some(format!("it needs `{lib}`, try {}", commands.join(" or ")))
+}
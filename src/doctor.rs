@@ -0,0 +1,201 @@
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::config::Config;
+use crate::package_data::*;
+
+/// Collects the results of each check, and how to fix any problems
+#[derive(Default)]
+struct Report {
+    errors: usize,
+    warnings: usize,
+}
+
+impl Report {
+    fn ok(&self, what: impl AsRef<str>) {
+        msg!("ok: {}", what.as_ref());
+    }
+
+    fn warn(&mut self, what: impl AsRef<str>, fix: impl AsRef<str>) {
+        warnmsg!("warning: {}", what.as_ref());
+        eprintln!("  fix: {}", fix.as_ref());
+        self.warnings += 1;
+    }
+
+    fn error(&mut self, what: impl AsRef<str>, fix: impl AsRef<str>) {
+        errmsg!("error: {}", what.as_ref());
+        eprintln!("  fix: {}", fix.as_ref());
+        self.errors += 1;
+    }
+}
+
+/// Run `<program> --version`, returning the first line of output
+fn version_of(program: impl AsRef<std::ffi::OsStr>) -> Result<String> {
+    let out = Command::new(program).arg("--version").output()?;
+    if !out.status.success() {
+        return Err(anyhow!("exited with {}", out.status));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).lines().next().unwrap_or_default().to_owned())
+}
+
+/// Check that we can create files in a directory
+fn check_writable(dir: &Path) -> std::io::Result<()> {
+    let probe = dir.join(".cargo-update-installed-probe");
+    OpenOptions::new().write(true).create_new(true).open(&probe)?;
+    fs::remove_file(&probe)
+}
+
+fn check_crates2(report: &mut Report) {
+    let path = match Crates2::path() {
+        Ok(path) => path,
+        Err(e) => return report.error(format!("{e:#}"), "set CARGO_HOME or HOME"),
+    };
+    let data = match fs::read_to_string(&path) {
+        Ok(data) => data,
+        Err(e) => {
+            return report.error(
+                format!("Can't read '{}': {e}", path.display()),
+                "install a package with `cargo install` first, or check CARGO_HOME",
+            )
+        }
+    };
+    let installs = match serde_json::from_str::<Value>(&data) {
+        Ok(Value::Object(mut root)) => match root.remove("installs") {
+            Some(Value::Object(installs)) => installs,
+            _ => {
+                return report.error(
+                    format!("'{}' has no \"installs\" table", path.display()),
+                    "this version of cargo may use an unsupported metadata format",
+                )
+            }
+        },
+        Ok(_) => {
+            return report.error(
+                format!("'{}' isn't a JSON object", path.display()),
+                "the file is corrupt, reinstall packages with `cargo install --force`",
+            )
+        }
+        Err(e) => {
+            return report.error(
+                format!("Can't parse '{}': {e}", path.display()),
+                "the file is corrupt, reinstall packages with `cargo install --force`",
+            )
+        }
+    };
+
+    // check every entry, rather than stopping at the first bad one like a normal run would
+    let mut bad = 0;
+    for (pkg_id, details) in &installs {
+        if let Err(e) = pkg_id.parse::<Package>() {
+            report.error(
+                format!("Can't parse package ID '{pkg_id}': {e:#}"),
+                "exclude it with --exclude, or reinstall it with `cargo install --force`",
+            );
+            bad += 1;
+        } else if let Err(e) = PackageDetails::deserialize(details) {
+            report.error(
+                format!("Can't parse install details for '{pkg_id}': {e}"),
+                "exclude it with --exclude, or reinstall it with `cargo install --force`",
+            );
+            bad += 1;
+        }
+    }
+    if bad == 0 {
+        report.ok(format!("Parsed {} packages from '{}'", installs.len(), path.display()));
+    }
+    if let Ok(crates2) = Crates2::load_from(&path) {
+        for (newest, stale) in crates2.duplicates() {
+            report.warn(
+                format!("'{newest}' also has stale entries: {}", stale.join(", ")),
+                "remove them with the dedupe subcommand",
+            );
+        }
+    }
+
+    if let Err(e) = OpenOptions::new().append(true).open(&path) {
+        report.error(
+            format!("'{}' isn't writable: {e}", path.display()),
+            "cargo needs to update this file when installing, check its ownership and permissions",
+        );
+    }
+}
+
+/// Check the environment for common problems which would prevent updating packages.
+pub fn run(config_path: Option<&Path>) -> Result<()> {
+    let mut report = Report::default();
+
+    match cargo_home() {
+        Ok(home) => {
+            let from = if env::var_os("CARGO_HOME").is_some() { "$CARGO_HOME" } else { "default" };
+            if home.is_dir() {
+                report.ok(format!("CARGO_HOME is '{}' ({from})", home.display()));
+            } else {
+                report.error(
+                    format!("CARGO_HOME '{}' ({from}) doesn't exist", home.display()),
+                    "set CARGO_HOME to the directory where cargo installs packages",
+                );
+            }
+
+            let bin_dir = home.join("bin");
+            let in_path = env::var_os("PATH")
+                .is_some_and(|path| env::split_paths(&path).any(|p| p == bin_dir));
+            if in_path {
+                report.ok(format!("'{}' is in PATH", bin_dir.display()));
+            } else {
+                report.warn(
+                    format!("'{}' isn't in PATH", bin_dir.display()),
+                    "add it to PATH in your shell's startup file so installed programs can be run",
+                );
+            }
+
+            if bin_dir.is_dir() {
+                match check_writable(&bin_dir) {
+                    Ok(()) => report.ok(format!("'{}' is writable", bin_dir.display())),
+                    Err(e) => report.error(
+                        format!("Can't write to '{}': {e}", bin_dir.display()),
+                        "fix the directory's ownership and permissions",
+                    ),
+                }
+            }
+        }
+        Err(e) => report.error(format!("{e:#}"), "set CARGO_HOME or HOME"),
+    }
+
+    check_crates2(&mut report);
+
+    match Config::load(config_path) {
+        Ok(_) => report.ok("Loaded the config file"),
+        Err(e) => {
+            report.error(format!("{e:#}"), "fix the config file or pass a different --config")
+        }
+    }
+
+    let cargo_exe = env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    match version_of(&cargo_exe) {
+        Ok(v) => report.ok(format!("Found {v}")),
+        Err(e) => report.error(
+            format!("Can't run '{}': {e}", cargo_exe.to_string_lossy()),
+            "install Rust from https://rustup.rs or add cargo to PATH",
+        ),
+    }
+    match version_of("rustup") {
+        Ok(v) => report.ok(format!("Found {v}")),
+        Err(_) => report.warn(
+            "rustup isn't available",
+            "this is fine if Rust is installed another way, but toolchains can't be managed",
+        ),
+    }
+
+    if report.errors > 0 {
+        Err(anyhow!("Found {} errors and {} warnings", report.errors, report.warnings))
+    } else {
+        msg!("No problems found ({} warnings)", report.warnings);
+        Ok(())
+    }
+}
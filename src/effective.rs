@@ -0,0 +1,189 @@
+//! Printing the effective settings of an update run for --print-config, after the command line,
+//! the environment, the config file and its profile, and the defaults have all been merged, to
+//! find out why two machines behave differently.
+
+use std::env;
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::config::Config;
+use crate::filter::Filter;
+use crate::http;
+use crate::list::print_table;
+use crate::package_data::cargo_home;
+use crate::{util, Args};
+
+/// Where a setting came from, given whether it was set on the command line and in the config file
+fn origin(cli: bool, config: bool, config_name: &str) -> String {
+    match (cli, config) {
+        (true, _) => "command line".into(),
+        (_, true) => format!("`{config_name}` in `[defaults]`"),
+        _ => "default".into(),
+    }
+}
+
+/// A setting which can be set by a flag, a negated flag, or the config file
+fn flag(name: &str, on: bool, off: bool, config: Option<bool>) -> Vec<String> {
+    let value = match (on, off) {
+        (true, _) => true,
+        (_, true) => false,
+        _ => config.unwrap_or(false),
+    };
+    vec![name.into(), value.to_string(), origin(on || off, config.is_some(), name)]
+}
+
+fn path_row(name: &str, path: &Path, from: &str) -> Vec<String> {
+    vec![name.into(), path.display().to_string(), from.into()]
+}
+
+fn env_or(name: &str, default: &str) -> (String, String) {
+    match env::var_os(name) {
+        Some(value) => (value.to_string_lossy().into_owned(), format!("${name}")),
+        None => (default.into(), "default".into()),
+    }
+}
+
+pub fn print(args: &Args, config: &Config) -> Result<()> {
+    let home = cargo_home()?;
+    let home_from = if env::var_os("CARGO_HOME").is_some() { "$CARGO_HOME" } else { "default" };
+    let (cargo, cargo_from) = env_or("CARGO", "cargo");
+    let (rustc, rustc_from) = env_or("RUSTC", "rustc");
+    let mut rows = vec![
+        match &config.path {
+            Some(path) if args.config.is_some() => path_row("config file", path, "--config"),
+            Some(path) => path_row("config file", path, "default"),
+            None => vec!["config file".into(), "-".into(), "not found".into()],
+        },
+        match (&config.profile, &args.profile) {
+            (Some(name), Some(_)) => vec!["profile".into(), name.clone(), "--profile".into()],
+            (Some(name), None) => vec!["profile".into(), name.clone(), "hostname".into()],
+            (None, _) => vec!["profile".into(), "-".into(), "none matched".into()],
+        },
+        path_row("CARGO_HOME", &home, home_from),
+        path_row("install root", &home.join("bin"), "CARGO_HOME"),
+        path_row("metadata", &home.join(".crates2.json"), "CARGO_HOME"),
+        path_row("index cache", &home.join("registry").join("index"), "CARGO_HOME"),
+        path_row("git checkouts", &home.join("git").join("checkouts"), "CARGO_HOME"),
+        path_row("state directory", &util::state_dir()?, "XDG_STATE_HOME or default"),
+        match &args.build_dir {
+            Some(dir) => path_row("build directory", dir, "command line"),
+            None => vec!["build directory".into(), "-".into(), "cargo's default".into()],
+        },
+        vec!["cargo".into(), cargo, cargo_from],
+        vec!["rustc".into(), rustc, rustc_from],
+    ];
+
+    let defaults = &config.defaults;
+    rows.extend([
+        flag("force", args.force, args.no_force, defaults.force),
+        flag("locked", args.locked, args.no_locked, defaults.locked),
+        flag(
+            "ignore-rust-version",
+            args.ignore_rust_version,
+            args.no_ignore_rust_version,
+            defaults.ignore_rust_version,
+        ),
+        flag("auditable", args.auditable, false, defaults.auditable),
+        flag("cross", args.cross, false, defaults.cross),
+        flag("container", args.container, false, defaults.container),
+        flag("check-publisher", args.check_publisher, false, defaults.check_publisher),
+        flag("yes", args.yes, false, defaults.yes),
+        vec![
+            "check-self".into(),
+            defaults.check_self.unwrap_or(true).to_string(),
+            origin(false, defaults.check_self.is_some(), "check-self"),
+        ],
+        vec![
+            "check-advisories".into(),
+            defaults.check_advisories.unwrap_or(true).to_string(),
+            origin(false, defaults.check_advisories.is_some(), "check-advisories"),
+        ],
+        vec![
+            "jobs".into(),
+            args.jobs.or(defaults.jobs).map_or("cargo's default".into(), |j| j.to_string()),
+            origin(args.jobs.is_some(), defaults.jobs.is_some(), "jobs"),
+        ],
+        vec![
+            "cooldown".into(),
+            args.cooldown.or(defaults.cooldown).map_or("-".into(), |days| format!("{days} days")),
+            origin(args.cooldown.is_some(), defaults.cooldown.is_some(), "cooldown"),
+        ],
+        vec![
+            "parallel".into(),
+            args.parallel.to_string(),
+            if args.parallel > 1 { "command line" } else { "default" }.into(),
+        ],
+    ]);
+    let sort = args.sort.or(defaults.sort).map_or_else(
+        || if args.parallel > 1 { "duration".into() } else { "name".into() },
+        |s| format!("{s:?}").to_lowercase(),
+    );
+    rows.push(vec![
+        "sort".into(),
+        sort,
+        origin(args.sort.is_some(), defaults.sort.is_some(), "sort"),
+    ]);
+    rows.push(match (&args.rustc_wrapper, &defaults.rustc_wrapper, env::var_os("RUSTC_WRAPPER")) {
+        (Some(w), ..) => path_row("rustc-wrapper", w, "command line"),
+        (None, Some(w), _) => path_row("rustc-wrapper", w, "`rustc-wrapper` in `[defaults]`"),
+        (None, None, Some(w)) => path_row("rustc-wrapper", Path::new(&w), "$RUSTC_WRAPPER"),
+        (None, None, None) => vec!["rustc-wrapper".into(), "-".into(), "default".into()],
+    });
+    let list = |filters: &[Filter]| {
+        let filters: Vec<String> = filters.iter().map(ToString::to_string).collect();
+        if filters.is_empty() {
+            "-".into()
+        } else {
+            filters.join(", ")
+        }
+    };
+    rows.push(if args.exclude.is_empty() {
+        vec![
+            "exclude".into(),
+            list(&defaults.exclude),
+            origin(false, !defaults.exclude.is_empty(), "exclude"),
+        ]
+    } else {
+        vec!["exclude".into(), list(&args.exclude), "command line".into()]
+    });
+    rows.push(if args.include.is_empty() {
+        vec![
+            "include".into(),
+            list(&defaults.include),
+            origin(false, !defaults.include.is_empty(), "include"),
+        ]
+    } else {
+        vec!["include".into(), list(&args.include), "command line".into()]
+    });
+    rows.push(if args.git_ssh {
+        vec!["git-ssh".into(), "all hosts".into(), "command line".into()]
+    } else if !defaults.git_ssh_hosts.is_empty() {
+        let hosts = defaults.git_ssh_hosts.join(", ");
+        vec!["git-ssh".into(), hosts, "`git-ssh-hosts` in `[defaults]`".into()]
+    } else {
+        vec!["git-ssh".into(), "-".into(), "default".into()]
+    });
+    rows.push(vec![
+        "keep-versions".into(),
+        args.keep_versions.unwrap_or(config.backup.keep).to_string(),
+        match args.keep_versions {
+            Some(_) => "command line".into(),
+            None => "`keep` in `[backup]`, or default".into(),
+        },
+    ]);
+    let offline = env::var("CARGO_NET_OFFLINE").is_ok_and(|v| v == "true" || v == "1");
+    rows.push(vec![
+        "offline".into(),
+        offline.to_string(),
+        if offline { "$CARGO_NET_OFFLINE" } else { "default" }.into(),
+    ]);
+    for (name, setting) in [("proxy", http::proxy()), ("cainfo", http::cainfo())] {
+        rows.push(match setting {
+            Some((value, from)) => vec![name.into(), value.to_string_lossy().into(), from.into()],
+            None => vec![name.into(), "-".into(), "curl's default".into()],
+        });
+    }
+    print_table(&["SETTING", "VALUE", "FROM"], &rows, "");
+    Ok(())
+}
@@ -0,0 +1,260 @@
+//! Explaining the command an update would run for a package, argument by argument.
+//!
+//! The command is built exactly as for an update, then each argument is matched up with the
+//! setting it came from, checking them in the same order of priority as the update does.
+
+use std::env;
+use std::ffi::OsStr;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use clap::Parser;
+
+use crate::config::{Backend, Config};
+use crate::container;
+use crate::install_flags::InstallFlags;
+use crate::list::print_table;
+use crate::package_data::*;
+use crate::{Args, Job};
+
+/// Show the command an update would run for a package, and where each of its arguments came from.
+///
+/// Update options given before the subcommand are taken into account, e.g.
+/// `cargo update-installed --locked explain ripgrep`. Nothing is installed.
+#[derive(Debug, Parser)]
+pub struct ExplainArgs {
+    #[arg(value_name = "NAME")]
+    pub name: String,
+}
+
+/// The first of the places a setting can come from which set it, in order of priority
+fn origin(places: &[(bool, String)]) -> String {
+    places.iter().find(|(set, _)| *set).map_or_else(|| "?".into(), |(_, place)| place.clone())
+}
+
+/// Flags which take a value as the next argument
+const VALUE_FLAGS: &[&str] = &[
+    "--jobs",
+    "--version",
+    "--target-dir",
+    "--features",
+    "--target",
+    "--targets",
+    "--bin",
+    "--index",
+    "--git",
+    "--branch",
+    "--tag",
+    "--path",
+    "--root",
+];
+
+/// Flags of the container engine's `run` which take a value as the next argument
+const RUN_VALUE_FLAGS: &[&str] = &["--user", "--env", "--volume"];
+
+pub fn print(
+    jobs: &[Job],
+    cargo_exe: &OsStr,
+    crates2: &Crates2,
+    config: &Config,
+    args: &Args,
+    install_flags: &InstallFlags,
+) -> Result<()> {
+    let Some(job) = jobs.first() else {
+        msg!("{} wouldn't be updated", args.names.join(", "));
+        return Ok(());
+    };
+    let (pkg, details) = crates2
+        .installs
+        .iter()
+        .find_map(|(id, details)| {
+            let pkg = id.parse::<Package>().ok()?;
+            (pkg.name == job.name).then_some((pkg, details))
+        })
+        .with_context(|| format!("{} isn't installed", job.name))?;
+
+    let package_config = config.package(&pkg.name);
+    let source_config = config.source(&pkg.source);
+    let package_table = format!("`[package.{}]`", pkg.name);
+    let source_table = format!("`[source.{}]`", pkg.source.kind());
+    let recorded = "recorded in .crates2.json when it was installed".to_owned();
+
+    let program = job.cargo_exe(cargo_exe).to_string_lossy().into_owned();
+    println!("{program} {}\n", job.args.join(" "));
+
+    let mut rows = Vec::new();
+    let is_cross = Path::new(&program).file_stem() == Some(OsStr::new("cross"));
+    let is_container = job.args.windows(2).any(|w| w == ["--root", container::INSTALL_ROOT]);
+    rows.push(vec![
+        program.clone(),
+        if !package_config.command.is_empty() {
+            format!("`command` in {package_table}")
+        } else if is_container {
+            let why = origin(&[
+                (
+                    package_config.backend == Some(Backend::Container),
+                    format!("`backend` in {package_table}"),
+                ),
+                (args.container, "--container".into()),
+                (true, "`container` in `[defaults]`".into()),
+            ]);
+            let engine = if config.container.engine.is_some() {
+                "`engine` in `[container]`"
+            } else {
+                "the first of podman and docker in PATH"
+            };
+            format!("{why}, using {engine}")
+        } else if job.args.first().is_some_and(|a| a.starts_with('+')) {
+            "cargo from PATH, so that rustup can pick the toolchain".into()
+        } else if is_cross {
+            origin(&[
+                (
+                    package_config.backend == Some(Backend::Cross),
+                    format!("`backend` in {package_table}"),
+                ),
+                (args.cross, "--cross, since it's installed for another target".into()),
+                (true, "`cross` in `[defaults]`, since it's installed for another target".into()),
+            ])
+        } else if env::var_os("CARGO").is_some() {
+            "$CARGO".into()
+        } else {
+            "cargo from PATH".into()
+        },
+    ]);
+
+    if !package_config.command.is_empty() {
+        for arg in &job.args {
+            rows.push(vec![arg.clone(), format!("`command` in {package_table}")]);
+        }
+    } else {
+        // the extra arguments and the name always come last
+        let extra_start = job.args.len().saturating_sub(package_config.extra_args.len() + 1);
+        let mut i = 0;
+        if is_container {
+            // everything up to the image's cargo is for the engine
+            let cargo = job.args.iter().position(|a| a == "cargo").unwrap_or(0);
+            let image = cargo.saturating_sub(1);
+            let config_args = image.saturating_sub(config.container.args.len());
+            while i < config_args {
+                let arg = job.args[i].as_str();
+                let value = RUN_VALUE_FLAGS.contains(&arg).then(|| job.args.get(i + 1)).flatten();
+                let shown = match value {
+                    Some(value) => format!("{arg} {value}"),
+                    None => arg.to_owned(),
+                };
+                rows.push(vec![shown, "the container backend".into()]);
+                i += if value.is_some() { 2 } else { 1 };
+            }
+            for arg in &job.args[config_args..image] {
+                rows.push(vec![arg.clone(), "`args` in `[container]`".into()]);
+            }
+            rows.push(vec![
+                job.args[image].clone(),
+                if config.container.image.is_some() {
+                    "`image` in `[container]`".into()
+                } else {
+                    "the container backend's default image".into()
+                },
+            ]);
+            rows.push(vec!["cargo".into(), "the image's cargo".into()]);
+            i = cargo + 1;
+        }
+        while i < job.args.len() {
+            let arg = job.args[i].as_str();
+            if i == job.args.len() - 1 {
+                rows.push(vec![arg.to_owned(), "the package".into()]);
+                break;
+            }
+            if i >= extra_start {
+                rows.push(vec![arg.to_owned(), format!("`extra-args` in {package_table}")]);
+                i += 1;
+                continue;
+            }
+            let value = VALUE_FLAGS.contains(&arg).then(|| job.args.get(i + 1)).flatten();
+            let from = match arg {
+                _ if arg.starts_with('+') => format!("`toolchain` in {package_table}"),
+                "auditable" => origin(&[
+                    (args.auditable, "--auditable".into()),
+                    (true, "`auditable` in `[defaults]`".into()),
+                ]),
+                "install" => "to build it from source".into(),
+                "binstall" => format!("`backend` in {package_table}"),
+                "--no-confirm" => "since nobody's asked to confirm".into(),
+                "--force" => origin(&[
+                    (args.force, "--force".into()),
+                    (args.rebuild_broken, "--rebuild-broken".into()),
+                    (source_config.force == Some(true), format!("`force` in {source_table}")),
+                    (true, "`force` in `[defaults]`".into()),
+                ]),
+                "--locked" => origin(&[
+                    (args.locked, "--locked".into()),
+                    (
+                        install_flags.get(&pkg.name).locked == Some(true),
+                        "an earlier update with --locked".into(),
+                    ),
+                    (source_config.locked == Some(true), format!("`locked` in {source_table}")),
+                    (true, "`locked` in `[defaults]`".into()),
+                ]),
+                "--ignore-rust-version" => origin(&[
+                    (args.ignore_rust_version, "--ignore-rust-version".into()),
+                    (
+                        install_flags.get(&pkg.name).ignore_rust_version == Some(true),
+                        "an earlier update with --ignore-rust-version".into(),
+                    ),
+                    (
+                        package_config.ignore_rust_version,
+                        format!("`ignore-rust-version` in {package_table}"),
+                    ),
+                    (true, "`ignore-rust-version` in `[defaults]`".into()),
+                ]),
+                "--jobs" => origin(&[
+                    (args.jobs.is_some(), "--jobs".into()),
+                    (source_config.jobs.is_some(), format!("`jobs` in {source_table}")),
+                    (true, "`jobs` in `[defaults]`".into()),
+                ]),
+                "--version" if config.pin(&job.name).is_some() => "`[pin]`".into(),
+                "--version" => origin(&[
+                    (args.cooldown.is_some(), "--cooldown".into()),
+                    (config.defaults.cooldown.is_some(), "`cooldown` in `[defaults]`".into()),
+                    (true, "an advisory for a newer version".into()),
+                ]),
+                "--target-dir" => "--build-dir".into(),
+                "--features" | "--all-features" | "--no-default-features" => recorded.clone(),
+                "--target" | "--targets" => "the target recorded in .crates2.json".into(),
+                "--bin" => "only some of its programs were installed".into(),
+                "--root" => "the container backend, where CARGO_HOME is mounted".into(),
+                "--index" | "--git" | "--branch" | "--tag" | "--path" => {
+                    format!("its source, {}", pkg.source)
+                }
+                _ => "?".into(),
+            };
+            let shown = match value {
+                Some(value) => format!("{arg} {value}"),
+                None => arg.to_owned(),
+            };
+            rows.push(vec![shown, from]);
+            i += if value.is_some() { 2 } else { 1 };
+        }
+    }
+
+    for (var, value) in &job.env {
+        let from = if var == "RUSTC_WRAPPER" {
+            origin(&[
+                (args.rustc_wrapper.is_some(), "--rustc-wrapper".into()),
+                (true, "`rustc-wrapper` in `[defaults]`".into()),
+            ])
+        } else {
+            format!("`[cross.images]`, for {}", details.target)
+        };
+        rows.push(vec![format!("{var}={}", value.to_string_lossy()), from]);
+    }
+    print_table(&["ARGUMENT", "FROM"], &rows, "");
+
+    if job.retry_locked {
+        println!("\nIf it fails to build, it's tried again with --locked.");
+    }
+    if !job.missing_bins.is_empty() {
+        println!("Some of its programs are missing, so it's reinstalled with --force if needed.");
+    }
+    Ok(())
+}
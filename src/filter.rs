@@ -0,0 +1,188 @@
+//! Selecting packages by name with --include/--exclude patterns.
+
+use std::fmt;
+use std::str::FromStr;
+
+use glob::{Pattern, PatternError};
+use serde::Deserialize;
+
+/// A glob pattern matched against package names, negated by a leading '!'
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Filter {
+    negated: bool,
+    pattern: Pattern,
+}
+
+impl FromStr for Filter {
+    type Err = PatternError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (negated, pattern) = match s.strip_prefix('!') {
+            Some(p) => (true, p),
+            None => (false, s),
+        };
+        Ok(Self { negated, pattern: Pattern::new(pattern)? })
+    }
+}
+
+impl TryFrom<String> for Filter {
+    type Error = PatternError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.negated {
+            f.write_str("!")?;
+        }
+        f.write_str(self.pattern.as_str())
+    }
+}
+
+impl Filter {
+    /// Whether this is a pattern which removes packages, like "!cargo-*"
+    pub fn is_negated(&self) -> bool {
+        self.negated
+    }
+
+    /// Whether the pattern matches a name, regardless of whether it's negated
+    pub fn matches(&self, name: &str) -> bool {
+        self.pattern.matches(name)
+    }
+}
+
+/// The number of single-character edits to turn one string into the other
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let cost = if ca == cb { prev } else { prev + 1 };
+            prev = row[j + 1];
+            row[j + 1] = cost.min(row[j] + 1).min(prev + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// The names which look most like a name or pattern that didn't match anything, closest first
+pub fn suggestions<'a>(wanted: &str, names: &[&'a str]) -> Vec<&'a str> {
+    let wanted: String = wanted.chars().filter(|c| !"!*?[]".contains(*c)).collect();
+    let max = (wanted.len() / 3).max(1);
+    let mut close: Vec<(usize, &str)> = names
+        .iter()
+        .map(|&name| {
+            let sub = wanted.len() >= 3 && name.contains(wanted.as_str());
+            (if sub { 0 } else { edit_distance(&wanted, name) }, name)
+        })
+        .filter(|&(d, _)| d <= max)
+        .collect();
+    close.sort();
+    close.into_iter().take(3).map(|(_, name)| name).collect()
+}
+
+/// Match a name against an ordered list of filters, where the last matching one wins. Returns
+/// whether the name was selected, or None if no filter matched.
+fn last_match(filters: &[Filter], name: &str) -> Option<bool> {
+    filters.iter().rev().find(|f| f.pattern.matches(name)).map(|f| !f.negated)
+}
+
+/// Which packages to update, from the package names and filters on the command line or config
+pub struct Selection<'a> {
+    pub names: &'a [String],
+    pub include: &'a [Filter],
+    pub exclude: &'a [Filter],
+}
+
+impl Selection<'_> {
+    /// Decide whether to include a package. --exclude overrides --include, and when there are no
+    /// names or positive include patterns, everything not excluded is included.
+    pub fn should_include(&self, name: &str) -> bool {
+        if last_match(self.exclude, name) == Some(true) {
+            false
+        } else if self.names.is_empty() && self.include.iter().all(|f| f.negated) {
+            last_match(self.include, name) != Some(false)
+        } else {
+            self.explicitly_included(name)
+        }
+    }
+
+    /// Whether a package was selected by name or an --include pattern, rather than by default
+    pub fn explicitly_included(&self, name: &str) -> bool {
+        self.names.iter().any(|n| n == name) || last_match(self.include, name) == Some(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filters(patterns: &[&str]) -> Vec<Filter> {
+        patterns.iter().map(|p| p.parse().unwrap()).collect()
+    }
+
+    fn selected(names: &[&str], include: &[&str], exclude: &[&str]) -> Vec<&'static str> {
+        let names: Vec<String> = names.iter().map(|&n| n.to_owned()).collect();
+        let (include, exclude) = (filters(include), filters(exclude));
+        let selection = Selection { names: &names, include: &include, exclude: &exclude };
+        ["cargo-edit", "cargo-expand", "ripgrep"]
+            .into_iter()
+            .filter(|name| selection.should_include(name))
+            .collect()
+    }
+
+    #[test]
+    fn last_matching_pattern_wins() {
+        assert_eq!(selected(&[], &["cargo-*", "!cargo-expand"], &[]), ["cargo-edit"]);
+        assert_eq!(selected(&[], &["!cargo-*", "cargo-expand"], &[]), ["cargo-expand"]);
+        assert_eq!(selected(&[], &["cargo-*", "!cargo-*", "cargo-edit"], &[]), ["cargo-edit"]);
+    }
+
+    #[test]
+    fn only_negated_patterns_start_from_everything() {
+        assert_eq!(selected(&[], &[], &[]), ["cargo-edit", "cargo-expand", "ripgrep"]);
+        assert_eq!(selected(&[], &["!cargo-*"], &[]), ["ripgrep"]);
+        assert_eq!(selected(&[], &["!cargo-*", "cargo-edit"], &[]), ["cargo-edit"]);
+    }
+
+    #[test]
+    fn exclude_overrides_include() {
+        assert_eq!(selected(&[], &["cargo-*"], &["cargo-expand"]), ["cargo-edit"]);
+        assert_eq!(selected(&[], &[], &["cargo-*", "!cargo-edit"]), ["cargo-edit", "ripgrep"]);
+        assert_eq!(selected(&["cargo-edit"], &[], &["cargo-*"]), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn names_select_only_themselves() {
+        assert_eq!(selected(&["ripgrep"], &[], &[]), ["ripgrep"]);
+        assert_eq!(selected(&["ripgrep"], &["cargo-edit"], &[]), ["cargo-edit", "ripgrep"]);
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for pattern in ["cargo-*", "!cargo-expand"] {
+            assert_eq!(pattern.parse::<Filter>().unwrap().to_string(), pattern);
+        }
+        assert!("!".parse::<Filter>().unwrap().is_negated());
+        assert!("[".parse::<Filter>().is_err());
+    }
+
+    #[test]
+    fn suggestions_for_typos() {
+        let names = ["ripgrep", "cargo-edit", "cargo-expand", "bat"];
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(suggestions("ripgrap", &names), ["ripgrep"]);
+        // pattern characters are left out
+        assert_eq!(suggestions("!cargo-edt*", &names), ["cargo-edit"]);
+        // a long enough part of a name comes first
+        assert_eq!(suggestions("cargo-exp", &names), ["cargo-expand", "cargo-edit"]);
+        assert_eq!(suggestions("grep", &names), ["ripgrep"]);
+        assert!(suggestions("fd-find", &names).is_empty());
+    }
+}
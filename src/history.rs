@@ -0,0 +1,84 @@
+//! How long each package took to install in previous runs, used to estimate how long a run will
+//! take, and totals of all runs for the stats subcommand.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::report::{JobResult, Outcome};
+use crate::util;
+
+/// How many of the most recent durations are kept for each package
+const MAX_SAMPLES: usize = 5;
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct History {
+    /// Successful install durations in seconds for each package, oldest first
+    packages: BTreeMap<String, Vec<f64>>,
+    /// Totals over all runs for each package, since this was first recorded
+    pub totals: BTreeMap<String, Totals>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Totals {
+    /// Successful installs, whether or not the version changed
+    pub builds: u64,
+    /// Successful installs which changed the version
+    pub updates: u64,
+    pub failures: u64,
+    /// Time spent on successful installs, in seconds
+    pub build_secs: f64,
+    /// Time spent on failed installs, in seconds
+    pub failed_secs: f64,
+}
+
+impl History {
+    /// Load the saved history, which is empty if there isn't any yet
+    pub fn load() -> Result<Self> {
+        util::load_state("history.json")
+    }
+
+    pub fn save(&self) -> Result<()> {
+        util::save_state("history.json", self)
+    }
+
+    /// The expected install time of a package, the average of its recent durations
+    pub fn estimate(&self, name: &str) -> Option<Duration> {
+        let samples = self.packages.get(name).filter(|s| !s.is_empty())?;
+        let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+        Duration::try_from_secs_f64(mean).ok()
+    }
+
+    /// Add the durations of the packages which were installed successfully, and count the
+    /// results of the ones which were run
+    pub fn record(&mut self, results: &[JobResult]) {
+        for res in results {
+            let secs = res.duration.as_secs_f64();
+            match res.outcome {
+                Outcome::Updated => {
+                    let samples = self.packages.entry(res.name.clone()).or_default();
+                    samples.push(secs);
+                    let excess = samples.len().saturating_sub(MAX_SAMPLES);
+                    samples.drain(..excess);
+
+                    let totals = self.totals.entry(res.name.clone()).or_default();
+                    totals.builds += 1;
+                    totals.build_secs += secs;
+                    if res.new_version.as_ref().is_some_and(|v| *v != res.version) {
+                        totals.updates += 1;
+                    }
+                }
+                Outcome::Failed => {
+                    let totals = self.totals.entry(res.name.clone()).or_default();
+                    totals.failures += 1;
+                    totals.failed_secs += secs;
+                }
+                _ => (),
+            }
+        }
+    }
+}
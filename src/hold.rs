@@ -0,0 +1,108 @@
+//! Holding packages back from updates until a given version is published, to skip a known-bad
+//! release without having to remember to start updating the package again afterwards.
+
+use std::collections::BTreeMap;
+
+use anyhow::{bail, ensure, Result};
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+
+use crate::list::print_table;
+use crate::package_data::*;
+use crate::semver::Version;
+use crate::util;
+
+const FILE_NAME: &str = "holds.json";
+
+/// Skip updates of a package until a newer version than a bad release is published.
+///
+/// Held packages are skipped by update runs until the latest version on crates.io is at least the
+/// one given with --until, and are then updated as usual. Naming a held package on the command
+/// line updates it anyway. With no package, the held packages are listed.
+#[derive(Debug, Parser)]
+pub struct HoldArgs {
+    /// The package to hold or release
+    name: Option<String>,
+
+    /// Hold the package until this version or a newer one is published
+    #[arg(long, value_name = "VERSION", requires = "name")]
+    until: Option<Version>,
+
+    /// Stop holding the package
+    #[arg(long, requires = "name", conflicts_with = "until")]
+    release: bool,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Holds {
+    /// The version each held package is waiting for
+    packages: BTreeMap<String, String>,
+}
+
+impl Holds {
+    pub fn load() -> Result<Self> {
+        util::load_state(FILE_NAME)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        util::save_state(FILE_NAME, self)
+    }
+
+    /// The version a package is held until, if it's held
+    pub fn until(&self, name: &str) -> Option<Version> {
+        self.packages.get(name)?.parse().ok()
+    }
+
+    /// Stop holding a package, returning whether it was held
+    pub fn release(&mut self, name: &str) -> bool {
+        self.packages.remove(name).is_some()
+    }
+}
+
+pub fn run(args: &HoldArgs, crates2: &Crates2) -> Result<()> {
+    let mut holds = Holds::load()?;
+    let installed: BTreeMap<String, String> = crates2
+        .installs
+        .keys()
+        .filter_map(|id| id.parse::<Package>().ok())
+        .map(|pkg| (pkg.name, pkg.version))
+        .collect();
+
+    let Some(name) = &args.name else {
+        if holds.packages.is_empty() {
+            msg!("No packages are held");
+            return Ok(());
+        }
+        let rows: Vec<Vec<String>> = holds
+            .packages
+            .iter()
+            .map(|(name, until)| {
+                let version = installed.get(name).map_or("-", String::as_str);
+                vec![name.clone(), version.to_owned(), until.clone()]
+            })
+            .collect();
+        print_table(&["NAME", "INSTALLED", "HELD UNTIL"], &rows, "");
+        return Ok(());
+    };
+
+    if args.release {
+        ensure!(holds.release(name), "{name} isn't held");
+        holds.save()?;
+        msg!("{name} is no longer held");
+        return Ok(());
+    }
+    let Some(until) = &args.until else {
+        bail!("Use --until VERSION to hold {name}, or --release to stop holding it");
+    };
+    let Some(version) = installed.get(name) else {
+        bail!("{name} isn't installed");
+    };
+    if version.parse::<Version>().is_ok_and(|v| v >= *until) {
+        bail!("{name} {version} is already installed, which isn't older than {until}");
+    }
+    holds.packages.insert(name.clone(), until.to_string());
+    holds.save()?;
+    msg!("{name} won't be updated until version {until} or newer is published");
+    Ok(())
+}
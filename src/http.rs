@@ -0,0 +1,393 @@
+//! Minimal HTTP client for the network features.
+//!
+//! Requests are made by running `curl`, which is available nearly everywhere and picks up the
+//! system's TLS and proxy configuration without us needing a full HTTP stack. Batches of index
+//! files are fetched with a single curl so that it can reuse connections, see [`get_all`].
+//!
+//! To be polite to crates.io, requests to each host are spaced out (following the crates.io
+//! crawler policy of one API request per second), and rate limit or server errors are retried with
+//! exponential backoff, honoring any `Retry-After` header.
+//!
+//! Requests to the GitHub API use the token in `GITHUB_TOKEN` if it's set, which raises its rate
+//! limit from 60 requests an hour.
+//!
+//! The proxy and CA certificates are found the way cargo finds them, so that anything which works
+//! for cargo works for us too: the `[http]` table of our config file, then `CARGO_HTTP_PROXY` and
+//! `CARGO_HTTP_CAINFO`, then `http.proxy` and `http.cainfo` in cargo's config file, then (for the
+//! proxy) `HTTPS_PROXY`, `https_proxy`, `http_proxy`, and `ALL_PROXY`.
+
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Context, Result};
+use once_cell::sync::{Lazy, OnceCell};
+use serde_json::Value;
+use url::Url;
+
+use crate::config::HttpConfig;
+use crate::package_data::cargo_home;
+use crate::toml;
+
+const USER_AGENT: &str = concat!(
+    env!("CARGO_PKG_NAME"),
+    "/",
+    env!("CARGO_PKG_VERSION"),
+    " (https://github.com/aswild/cargo-update-installed)"
+);
+
+/// How many times a failed request is retried
+const RETRIES: u32 = 4;
+/// Delay before the first retry, which doubles after each attempt
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+/// Longest Retry-After delay we're willing to wait
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+/// Maximum number of requests in flight at once with [`map_concurrent`]
+const MAX_CONCURRENT: usize = 16;
+/// Maximum number of requests in flight at once with [`get_all`]
+const MAX_PARALLEL: &str = "50";
+
+/// curl exit codes for network problems which may go away on their own: failed to connect,
+/// timeout, TLS handshake failure, empty reply, and send or receive errors
+const TRANSIENT_CURL_ERRORS: &[i32] = &[7, 28, 35, 52, 55, 56];
+
+/// Minimum time between starting two requests to a host
+fn min_interval(host: &str) -> Duration {
+    match host {
+        // the API is rate limited, while the index is static files on a CDN
+        "crates.io" => Duration::from_secs(1),
+        // cargo itself makes hundreds of index requests at once
+        "index.crates.io" => Duration::from_millis(10),
+        _ => Duration::from_millis(50),
+    }
+}
+
+/// The settings from our config file, once it's been loaded
+static SETTINGS: OnceCell<HttpConfig> = OnceCell::new();
+
+/// Use the `[http]` settings of the config file for requests. Only the first call has any effect.
+pub fn configure(config: &HttpConfig) {
+    SETTINGS.set(config.clone()).ok();
+}
+
+/// A setting in the `[http]` table of cargo's config file in CARGO_HOME
+fn cargo_setting(key: &str) -> Option<String> {
+    let home = cargo_home().ok()?;
+    let path = ["config.toml", "config"].iter().map(|f| home.join(f)).find(|p| p.is_file())?;
+    let config = toml::parse(&fs::read_to_string(path).ok()?)
+        .map_err(|e| dbgmsg!("Failed to parse cargo's config file: {e:#}"))
+        .ok()?;
+    config["http"][key].as_str().map(str::to_owned)
+}
+
+/// The proxy to use, if any, with where it's set
+pub fn proxy() -> Option<(OsString, &'static str)> {
+    let env_var = |name: &str| env::var_os(name).filter(|v| !v.is_empty());
+    if let Some(proxy) = SETTINGS.get().and_then(|s| s.proxy.clone()) {
+        return Some((proxy.into(), "`proxy` in `[http]`"));
+    }
+    if let Some(proxy) = env_var("CARGO_HTTP_PROXY") {
+        return Some((proxy, "$CARGO_HTTP_PROXY"));
+    }
+    if let Some(proxy) = cargo_setting("proxy") {
+        return Some((proxy.into(), "`http.proxy` in cargo's config"));
+    }
+    // curl itself ignores http_proxy for https URLs, but cargo doesn't
+    [
+        ("HTTPS_PROXY", "$HTTPS_PROXY"),
+        ("https_proxy", "$https_proxy"),
+        ("http_proxy", "$http_proxy"),
+        ("ALL_PROXY", "$ALL_PROXY"),
+        ("all_proxy", "$all_proxy"),
+    ]
+    .into_iter()
+    .find_map(|(name, from)| Some((env_var(name)?, from)))
+}
+
+/// The bundle of CA certificates to use instead of curl's default, with where it's set
+pub fn cainfo() -> Option<(OsString, &'static str)> {
+    if let Some(cainfo) = SETTINGS.get().and_then(|s| s.cainfo.clone()) {
+        return Some((cainfo.into(), "`cainfo` in `[http]`"));
+    }
+    if let Some(cainfo) = env::var_os("CARGO_HTTP_CAINFO").filter(|v| !v.is_empty()) {
+        return Some((cainfo, "$CARGO_HTTP_CAINFO"));
+    }
+    cargo_setting("cainfo").map(|cainfo| (cainfo.into(), "`http.cainfo` in cargo's config"))
+}
+
+/// curl's options for the proxy and CA certificates
+static TLS_PROXY_ARGS: Lazy<Vec<OsString>> = Lazy::new(|| {
+    let mut args = Vec::new();
+    if let Some((proxy, _)) = proxy() {
+        dbgmsg!("Using proxy {}", proxy.to_string_lossy());
+        args.extend(["--proxy".into(), proxy]);
+    }
+    if let Some((cainfo, _)) = cainfo() {
+        args.extend(["--cacert".into(), cainfo]);
+    }
+    if SETTINGS.get().is_some_and(|s| s.native_ca) {
+        args.push("--ca-native".into());
+    }
+    args
+});
+
+/// When the next request to each host can start
+static NEXT_REQUEST: Lazy<Mutex<HashMap<String, Instant>>> = Lazy::new(Default::default);
+
+/// Wait until it's our turn to make a request to this URL's host
+fn wait_for_turn(url: &str) {
+    let Some(host) = Url::parse(url).ok().and_then(|u| u.host_str().map(str::to_owned)) else {
+        return;
+    };
+    let now = Instant::now();
+    let start = {
+        let mut next = NEXT_REQUEST.lock().unwrap();
+        let start = next.get(&host).map_or(now, |&t| t.max(now));
+        next.insert(host.clone(), start + min_interval(&host));
+        start
+    };
+    thread::sleep(start - now);
+}
+
+/// The outcome of one attempt at a request
+enum Attempt {
+    /// The server responded with this status
+    Response { status: u32, retry_after: Option<Duration>, body: Vec<u8> },
+    /// A network error which might not happen again
+    Transient(String),
+}
+
+/// Split curl's `--include` output into the status, Retry-After header, and body
+fn parse_response(mut out: &[u8]) -> Result<Attempt> {
+    let mut head = "";
+    // there's a header block for each redirect that was followed, so use the last one
+    while out.starts_with(b"HTTP/") {
+        let end = out.windows(4).position(|w| w == b"\r\n\r\n").unwrap_or(out.len());
+        head = std::str::from_utf8(&out[..end]).context("Invalid response headers")?;
+        out = out.get(end + 4..).unwrap_or_default();
+    }
+    let mut lines = head.lines();
+    let status = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| anyhow!("Invalid HTTP response"))?;
+    // only the delay-seconds form is supported, not an HTTP date
+    let retry_after = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("retry-after"))
+        .and_then(|(_, value)| value.trim().parse().ok())
+        .map(Duration::from_secs);
+    Ok(Attempt::Response { status, retry_after, body: out.to_vec() })
+}
+
+/// An `Authorization` header for APIs which allow more requests with a token
+fn auth_header(url: &str) -> Option<String> {
+    let host = Url::parse(url).ok()?.host_str()?.to_owned();
+    match host.as_str() {
+        "api.github.com" => env::var("GITHUB_TOKEN").ok().map(|t| format!("Bearer {t}")),
+        _ => None,
+    }
+}
+
+fn attempt(method: &str, url: &str, body: Option<&str>) -> Result<Attempt> {
+    wait_for_turn(url);
+    dbgmsg!("{method} {url}");
+    let mut cmd = Command::new("curl");
+    cmd.args(["--silent", "--show-error", "--include", "--location", "--max-time", "30"])
+        .args(["--user-agent", USER_AGENT])
+        .args(&*TLS_PROXY_ARGS)
+        .stdin(Stdio::null());
+    if let Some(body) = body {
+        cmd.args(["--header", "Content-Type: application/json", "--data-binary", body]);
+    }
+    let auth = auth_header(url);
+    if auth.is_some() {
+        // read from stdin so that the token isn't visible in the process list
+        cmd.args(["--header", "@-"]).stdin(Stdio::piped());
+    }
+    cmd.arg(url).stdout(Stdio::piped()).stderr(Stdio::piped());
+    let mut child = cmd.spawn().context("Failed to run curl")?;
+    if let Some(auth) = auth {
+        let mut stdin = child.stdin.take().unwrap();
+        let _ = writeln!(stdin, "Authorization: {auth}");
+    }
+    let out = child.wait_with_output().context("Failed to run curl")?;
+    if !out.status.success() {
+        let err = String::from_utf8_lossy(&out.stderr).trim().to_owned();
+        if out.status.code().is_some_and(|c| TRANSIENT_CURL_ERRORS.contains(&c)) {
+            return Ok(Attempt::Transient(err));
+        }
+        bail!("{method} {url} failed: {err}");
+    }
+    parse_response(&out.stdout).with_context(|| format!("{method} {url} failed"))
+}
+
+/// Make a request, retrying if it fails in a way which might not happen again
+fn request(url: &str, body: Option<&str>) -> Result<String> {
+    let method = if body.is_some() { "POST" } else { "GET" };
+    String::from_utf8(request_bytes(url, body)?)
+        .map_err(|_| anyhow!("{method} {url} returned invalid UTF-8"))
+}
+
+fn request_bytes(url: &str, body: Option<&str>) -> Result<Vec<u8>> {
+    let method = if body.is_some() { "POST" } else { "GET" };
+    let mut backoff = INITIAL_BACKOFF;
+    for tries in 1.. {
+        let (err, retry_after) = match attempt(method, url, body)? {
+            Attempt::Response { status: 200..=299, body, .. } => return Ok(body),
+            Attempt::Response { status, retry_after, .. } if status == 429 || status >= 500 => {
+                (format!("HTTP {status}"), retry_after)
+            }
+            Attempt::Response { status, .. } => bail!("{method} {url} failed: HTTP {status}"),
+            Attempt::Transient(err) => (err, None),
+        };
+        if tries > RETRIES {
+            bail!("{method} {url} failed: {err}");
+        }
+        let delay = retry_after.map_or(backoff, |d| d.min(MAX_RETRY_AFTER));
+        dbgmsg!("{method} {url} failed: {err}, retrying in {:.1}s", delay.as_secs_f64());
+        thread::sleep(delay);
+        backoff *= 2;
+    }
+    unreachable!()
+}
+
+/// Where a URL permanently redirects to (with 301 or 308), following any further permanent
+/// redirects, or None if it doesn't
+pub fn permanent_redirect(url: &str) -> Result<Option<String>> {
+    let null = if cfg!(windows) { "NUL" } else { "/dev/null" };
+    let mut current = url.to_owned();
+    // a few hops at most, so that a redirect loop can't go on forever
+    for _ in 0..5 {
+        wait_for_turn(&current);
+        dbgmsg!("GET {current} (checking for redirects)");
+        let out = Command::new("curl")
+            .args(["--silent", "--show-error", "--max-time", "30", "--output", null])
+            .args(["--write-out", "%{http_code} %{redirect_url}"])
+            .args(["--user-agent", USER_AGENT])
+            .args(&*TLS_PROXY_ARGS)
+            .arg(&current)
+            .stdin(Stdio::null())
+            .output()
+            .context("Failed to run curl")?;
+        if !out.status.success() {
+            bail!("GET {current} failed: {}", String::from_utf8_lossy(&out.stderr).trim());
+        }
+        let out = String::from_utf8_lossy(&out.stdout);
+        match out.trim().split_once(' ') {
+            Some(("301" | "308", location)) if !location.is_empty() => current = location.into(),
+            _ => break,
+        }
+    }
+    Ok(Some(current).filter(|current| current != url))
+}
+
+/// Fetch a URL and return the response body
+pub fn get(url: &str) -> Result<String> {
+    request(url, None)
+}
+
+/// Fetch a URL and return the response body as it is, e.g. for an archive
+pub fn get_bytes(url: &str) -> Result<Vec<u8>> {
+    request_bytes(url, None)
+}
+
+/// Fetch a URL and parse the response as JSON
+pub fn get_json(url: &str) -> Result<Value> {
+    let body = get(url)?;
+    serde_json::from_str(&body).with_context(|| format!("Invalid JSON from {url}"))
+}
+
+/// POST JSON to a URL and parse the response as JSON
+pub fn post_json(url: &str, body: &Value) -> Result<Value> {
+    let body = request(url, Some(&body.to_string()))?;
+    serde_json::from_str(&body).with_context(|| format!("Invalid JSON from {url}"))
+}
+
+/// Fetch several URLs with a single curl, which reuses its connections and multiplexes the
+/// requests over HTTP/2 where the server supports it. This is for static files like the index, so
+/// the requests aren't spaced out. URLs which fail in a way that might not happen again are
+/// fetched again with [`get`], which retries them.
+pub fn get_all(urls: &[String]) -> Vec<Result<String>> {
+    if urls.is_empty() {
+        return Vec::new();
+    }
+    if urls.iter().any(|url| auth_header(url).is_some()) {
+        return map_concurrent(urls, |url| get(url));
+    }
+    static BATCH: AtomicUsize = AtomicUsize::new(0);
+    let dir = env::temp_dir().join(format!(
+        "{}-{}-{}",
+        env!("CARGO_PKG_NAME"),
+        std::process::id(),
+        BATCH.fetch_add(1, Ordering::Relaxed)
+    ));
+    // the name is predictable, so a directory which is already there could be someone else's
+    if let Err(e) = fs::create_dir(&dir) {
+        dbgmsg!("Failed to create '{}': {e}", dir.display());
+        return map_concurrent(urls, |url| get(url));
+    }
+
+    wait_for_turn(&urls[0]);
+    dbgmsg!("GET {} URLs, starting with {}", urls.len(), urls[0]);
+    let mut cmd = Command::new("curl");
+    cmd.args(["--silent", "--include", "--location", "--max-time", "30"])
+        .args(["--user-agent", USER_AGENT])
+        .args(["--parallel", "--parallel-max", MAX_PARALLEL, "--http2"])
+        .args(&*TLS_PROXY_ARGS)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    for (i, url) in urls.iter().enumerate() {
+        cmd.arg("--output").arg(dir.join(i.to_string())).arg(url);
+    }
+    // failures are handled per URL from what was written
+    if let Err(e) = cmd.status() {
+        dbgmsg!("Failed to run curl: {e}");
+    }
+
+    let results = map_concurrent(&urls.iter().enumerate().collect::<Vec<_>>(), |&(i, url)| {
+        let response =
+            fs::read(dir.join(i.to_string())).ok().and_then(|out| parse_response(&out).ok());
+        match response {
+            Some(Attempt::Response { status: 200..=299, body, .. }) => {
+                String::from_utf8(body).map_err(|_| anyhow!("GET {url} returned invalid UTF-8"))
+            }
+            Some(Attempt::Response { status, .. }) if status != 429 && status < 500 => {
+                Err(anyhow!("GET {url} failed: HTTP {status}"))
+            }
+            _ => get(url),
+        }
+    });
+    let _ = fs::remove_dir_all(&dir);
+    results
+}
+
+/// Call `f`, which makes requests, on every item using a bounded number of threads. The results
+/// are in the same order as the items.
+pub fn map_concurrent<T: Sync, R: Send>(items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::with_capacity(items.len()));
+    let worker = || loop {
+        let idx = next.fetch_add(1, Ordering::SeqCst);
+        let Some(item) = items.get(idx) else { return };
+        let res = f(item);
+        results.lock().unwrap().push((idx, res));
+    };
+    thread::scope(|s| {
+        for _ in 0..MAX_CONCURRENT.min(items.len()) {
+            s.spawn(worker);
+        }
+    });
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(idx, _)| *idx);
+    results.into_iter().map(|(_, res)| res).collect()
+}
@@ -0,0 +1,295 @@
+//! Look up the versions of packages published to crates.io, using its sparse index or cargo's
+//! local cache of it.
+//!
+//! See <https://doc.rust-lang.org/cargo/reference/registry-index.html> for the index format.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::io;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use anyhow::{bail, Context, Result};
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+
+use crate::http;
+use crate::package_data::cargo_home;
+use crate::semver::Version;
+use crate::util;
+
+const SPARSE_INDEX_URL: &str = "https://index.crates.io";
+
+/// One line of a package's index file, describing a published version
+#[derive(Debug, Deserialize)]
+struct IndexEntry {
+    vers: String,
+    #[serde(default)]
+    yanked: bool,
+}
+
+/// The parts of an index entry about the Rust version it needs
+#[derive(Debug, Deserialize)]
+struct RustVersionEntry {
+    vers: String,
+    #[serde(default)]
+    yanked: bool,
+    rust_version: Option<String>,
+}
+
+/// The parts of an index entry about the archive which was published
+#[derive(Debug, Deserialize)]
+struct ChecksumEntry {
+    vers: String,
+    /// SHA-256 of the `.crate` file
+    cksum: String,
+}
+
+/// The parts of an index entry about when it was published
+#[derive(Debug, Deserialize)]
+struct PublishedEntry {
+    vers: String,
+    #[serde(default)]
+    yanked: bool,
+    /// Only recorded for versions published since 2025
+    pubtime: Option<String>,
+}
+
+/// The parts of an index entry about features
+#[derive(Debug, Deserialize)]
+struct FeaturesEntry {
+    vers: String,
+    #[serde(default)]
+    yanked: bool,
+    #[serde(default)]
+    features: BTreeMap<String, serde_json::Value>,
+    /// Features using newer syntax, which older cargo versions can't read
+    #[serde(default)]
+    features2: BTreeMap<String, serde_json::Value>,
+    #[serde(default)]
+    deps: Vec<Dependency>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Dependency {
+    name: String,
+    #[serde(default)]
+    optional: bool,
+}
+
+/// The path of a package's file within the index, e.g. "3/s/syn" or "se/rd/serde"
+fn index_path(name: &str) -> String {
+    let name = name.to_ascii_lowercase();
+    match name.len() {
+        1 => format!("1/{name}"),
+        2 => format!("2/{name}"),
+        3 => format!("3/{}/{name}", &name[..1]),
+        _ => format!("{}/{}/{name}", &name[..2], &name[2..4]),
+    }
+}
+
+/// Add the version from an index entry if it's not yanked
+fn add_entry(versions: &mut Vec<Version>, name: &str, json: &[u8]) -> Result<()> {
+    let entry: IndexEntry =
+        serde_json::from_slice(json).with_context(|| format!("Invalid index entry for {name}"))?;
+    if !entry.yanked {
+        // skip versions we can't parse rather than failing the whole package
+        if let Ok(v) = entry.vers.parse() {
+            versions.push(v);
+        }
+    }
+    Ok(())
+}
+
+/// Index files fetched ahead of time by [`prefetch`], by package name
+static PREFETCHED: Lazy<Mutex<HashMap<String, String>>> = Lazy::new(Default::default);
+
+fn index_url(name: &str) -> String {
+    format!("{SPARSE_INDEX_URL}/{}", index_path(name))
+}
+
+/// Fetch the index files of many packages at once, which is much faster than one at a time since
+/// the connection to the index is shared. Later lookups of these packages use what was fetched,
+/// and any which failed are fetched again when they're looked up.
+pub fn prefetch<'a>(names: impl IntoIterator<Item = &'a str>) {
+    let names: Vec<&str> = names.into_iter().collect();
+    let urls: Vec<String> = names.iter().map(|name| index_url(name)).collect();
+    let bodies = http::get_all(&urls);
+    let mut prefetched = PREFETCHED.lock().unwrap();
+    for (name, body) in names.into_iter().zip(bodies) {
+        if let Ok(body) = body {
+            prefetched.insert(name.to_owned(), body);
+        }
+    }
+}
+
+/// Fetch a package's index file, unless it was prefetched
+pub fn index_file(name: &str) -> Result<String> {
+    let prefetched = PREFETCHED.lock().unwrap().get(name).cloned();
+    match prefetched {
+        Some(body) => Ok(body),
+        None => http::get(&index_url(name)),
+    }
+}
+
+/// List the published, non-yanked versions of a package
+pub fn versions(name: &str) -> Result<Vec<Version>> {
+    let body = index_file(name)?;
+    let mut versions = Vec::new();
+    for line in body.lines().filter(|line| !line.trim().is_empty()) {
+        add_entry(&mut versions, name, line.as_bytes())?;
+    }
+    Ok(versions)
+}
+
+/// List the non-yanked versions of a package in cargo's local index cache, which is as recent as
+/// the last time cargo fetched it (e.g. when building something that depends on the package).
+///
+/// Cargo keeps a cache file for each package it has looked up, under
+/// `$CARGO_HOME/registry/index/<registry>/.cache`. The file starts with a cache format version
+/// byte, a 4-byte index format version, and a NUL-terminated index version (e.g. an ETag), then has
+/// a NUL-terminated version string and JSON index entry for each published version.
+pub fn cached_versions(name: &str) -> Result<Vec<Version>> {
+    let index_dir = cargo_home()?.join("registry").join("index");
+    let registries = match fs::read_dir(&index_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            bail!("{name} isn't in cargo's local index cache")
+        }
+        Err(e) => return Err(e).context(format!("Failed to read '{}'", index_dir.display())),
+    };
+
+    let mut found = false;
+    let mut versions = Vec::new();
+    for registry in registries {
+        let registry = registry?.file_name();
+        let registry = registry.to_string_lossy();
+        // both the sparse index and the older git index have a cache
+        if !registry.starts_with("index.crates.io-") && !registry.starts_with("github.com-") {
+            continue;
+        }
+        let path = index_dir.join(&*registry).join(".cache").join(index_path(name));
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e).context(format!("Failed to read '{}'", path.display())),
+        };
+        dbgmsg!("Reading cached index entries from '{}'", path.display());
+        // only cache format 3 is known to have this layout, but older ones are similar enough
+        if data.len() < 5 || !(1..=3).contains(&data[0]) {
+            bail!("Unsupported index cache format in '{}'", path.display());
+        }
+        // skip the index version, then the fields alternate between version strings and entries
+        let mut fields = data[5..].split(|&b| b == 0).skip(1);
+        while let (Some(_), Some(json)) = (fields.next(), fields.next()) {
+            add_entry(&mut versions, name, json)?;
+        }
+        found = true;
+    }
+    if !found {
+        bail!("{name} isn't in cargo's local index cache");
+    }
+    Ok(versions)
+}
+
+/// The latest version, ignoring pre-releases unless `current` is one
+fn latest(versions: Vec<Version>, current: &Version) -> Option<Version> {
+    versions.into_iter().filter(|v| !v.is_prerelease() || current.is_prerelease()).max()
+}
+
+/// Find the latest version of a package. Pre-releases are only considered if `current` is one.
+pub fn latest_version(name: &str, current: &Version) -> Result<Option<Version>> {
+    Ok(latest(versions(name)?, current))
+}
+
+/// Find the latest version of a package in cargo's local index cache, like [`latest_version`]
+pub fn cached_latest_version(name: &str, current: &Version) -> Result<Option<Version>> {
+    Ok(latest(cached_versions(name)?, current))
+}
+
+/// Find the latest version of a package which was published before `cutoff`, like
+/// [`latest_version`]. The index doesn't have the publish times of versions from before 2025,
+/// which are old enough for any cutoff.
+pub fn latest_published_before(
+    name: &str,
+    current: &Version,
+    cutoff: SystemTime,
+) -> Result<Option<Version>> {
+    let body = index_file(name)?;
+    let mut versions = Vec::new();
+    for line in body.lines().filter(|line| !line.trim().is_empty()) {
+        let entry: PublishedEntry = serde_json::from_str(line)
+            .with_context(|| format!("Invalid index entry for {name}"))?;
+        let Ok(version) = entry.vers.parse() else { continue };
+        let published = entry.pubtime.as_deref().and_then(util::parse_timestamp);
+        if !entry.yanked && published.is_none_or(|t| t < cutoff) {
+            versions.push(version);
+        }
+    }
+    Ok(latest(versions, current))
+}
+
+/// Find the latest version of a package like [`latest_version`], along with its features. Optional
+/// dependencies are included, since they're features too unless the package says otherwise.
+pub fn latest_features(
+    name: &str,
+    current: &Version,
+) -> Result<Option<(Version, BTreeSet<String>)>> {
+    let body = index_file(name)?;
+    let mut latest: Option<(Version, FeaturesEntry)> = None;
+    for line in body.lines().filter(|line| !line.trim().is_empty()) {
+        let entry: FeaturesEntry = serde_json::from_str(line)
+            .with_context(|| format!("Invalid index entry for {name}"))?;
+        let Ok(version) = entry.vers.parse::<Version>() else { continue };
+        if entry.yanked || (version.is_prerelease() && !current.is_prerelease()) {
+            continue;
+        }
+        if latest.as_ref().is_none_or(|(v, _)| version > *v) {
+            latest = Some((version, entry));
+        }
+    }
+    Ok(latest.map(|(version, entry)| {
+        let mut features: BTreeSet<String> =
+            entry.features.into_keys().chain(entry.features2.into_keys()).collect();
+        features.extend(entry.deps.into_iter().filter(|d| d.optional).map(|d| d.name));
+        (version, features)
+    }))
+}
+
+/// List the non-yanked versions of a package with the Rust version each needs, if they say
+pub fn rust_versions(name: &str) -> Result<Vec<(Version, Option<Version>)>> {
+    let body = index_file(name)?;
+    let mut versions = Vec::new();
+    for line in body.lines().filter(|line| !line.trim().is_empty()) {
+        let entry: RustVersionEntry = serde_json::from_str(line)
+            .with_context(|| format!("Invalid index entry for {name}"))?;
+        let Ok(version) = entry.vers.parse() else { continue };
+        if !entry.yanked {
+            let rust_version = entry.rust_version.and_then(|v| parse_rust_version(&v).ok());
+            versions.push((version, rust_version));
+        }
+    }
+    Ok(versions)
+}
+
+/// The SHA-256 checksum of a version's `.crate` file, as a hex string
+pub fn checksum(name: &str, version: &Version) -> Result<Option<String>> {
+    let body = index_file(name)?;
+    for line in body.lines().filter(|line| !line.trim().is_empty()) {
+        let entry: ChecksumEntry = serde_json::from_str(line)
+            .with_context(|| format!("Invalid index entry for {name}"))?;
+        if entry.vers.parse::<Version>().is_ok_and(|v| v == *version) {
+            return Ok(Some(entry.cksum));
+        }
+    }
+    Ok(None)
+}
+
+/// Parse a Rust version, which can leave out the patch version like `rust-version` does
+pub fn parse_rust_version(s: &str) -> Result<Version> {
+    if s.matches('.').count() == 1 {
+        format!("{s}.0").parse()
+    } else {
+        s.parse()
+    }
+}
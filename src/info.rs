@@ -0,0 +1,111 @@
+use anyhow::{anyhow, Result};
+use clap::Parser;
+
+use crate::install_flags::InstallFlags;
+use crate::package_data::*;
+use crate::version_source::{self, Available, GitLsRemote, LocalPath, SparseIndex, VersionSource};
+
+/// Show everything cargo recorded about an installed package, and whether it's up to date.
+#[derive(Debug, Parser)]
+pub struct InfoArgs {
+    /// Name of the package
+    name: String,
+
+    /// Don't check for the latest version over the network.
+    #[arg(long)]
+    offline: bool,
+}
+
+/// Describe the latest available version compared to the installed one
+fn latest(pkg: &Package) -> Result<String> {
+    let index = SparseIndex::new(false);
+    let sources: [&dyn VersionSource; 3] = [&index, &GitLsRemote, &LocalPath];
+    let Some(latest) = version_source::latest(&sources, pkg)? else {
+        return Ok(match &pkg.source {
+            src if src.is_crates_io() => "not found on crates.io".into(),
+            PackageSource::Registry(_) => "unknown (only crates.io is supported)".into(),
+            _ => "unknown".into(),
+        });
+    };
+    let cached = if index.cache_fallbacks() > 0 { ", from the local index cache" } else { "" };
+    Ok(match (latest, &pkg.source) {
+        (Available::Version(v), _) if v > pkg.version.parse()? => {
+            format!("{v} (update available{cached})")
+        }
+        (Available::Version(v), _) => format!("{v} (up to date{cached})"),
+        (Available::Commit(latest), PackageSource::Git { rev: Some(rev), .. }) => {
+            if latest.starts_with(rev.as_str()) {
+                format!("{latest} (up to date)")
+            } else {
+                format!("{latest} (update available)")
+            }
+        }
+        (Available::Commit(latest), _) => latest,
+    })
+}
+
+pub fn run(args: &InfoArgs, crates2: &Crates2) -> Result<()> {
+    let (pkg, details) = crates2
+        .installs
+        .iter()
+        .find_map(|(pkg_id, details)| {
+            let pkg = pkg_id.parse::<Package>().ok()?;
+            (pkg.name == args.name).then_some((pkg, details))
+        })
+        .ok_or_else(|| anyhow!("Package '{}' isn't installed", args.name))?;
+
+    let mut fields: Vec<(&str, String)> = vec![("version", pkg.version.clone())];
+    match &pkg.source {
+        PackageSource::Registry(url) => fields.push(("registry", url.clone())),
+        PackageSource::Git { url, branch, tag, rev } => {
+            fields.push(("git", url.clone()));
+            if let Some(b) = branch {
+                fields.push(("branch", b.clone()));
+            }
+            if let Some(t) = tag {
+                fields.push(("tag", t.clone()));
+            }
+            if let Some(r) = rev {
+                fields.push(("rev", r.clone()));
+            }
+        }
+        PackageSource::Path(path) => fields.push(("path", path.display().to_string())),
+    }
+    if let Some(req) = &details.version_req {
+        fields.push(("version req", req.clone()));
+    }
+    let features =
+        if details.features.is_empty() { "(none)".into() } else { details.features.join(", ") };
+    fields.push(("features", features));
+    fields.push(("all features", details.all_features.to_string()));
+    fields.push(("default features", (!details.no_default_features).to_string()));
+    fields.push(("profile", details.profile.clone()));
+    fields.push(("target", details.target.clone()));
+    fields.push(("rustc", details.rustc.lines().next().unwrap_or_default().to_owned()));
+    if let Some(locked) = InstallFlags::load()?.get(&pkg.name).locked {
+        fields.push(("locked", locked.to_string()));
+    }
+
+    let bin_dir = cargo_home()?.join("bin");
+    for (i, bin) in details.bins.iter().enumerate() {
+        let path = bin_path(&bin_dir, bin);
+        let missing = if path.exists() { "" } else { " (missing)" };
+        fields.push((if i == 0 { "bins" } else { "" }, format!("{}{missing}", path.display())));
+    }
+
+    if !args.offline {
+        let latest = latest(&pkg).unwrap_or_else(|e| {
+            warnmsg!("Warning: couldn't check for the latest version: {e:#}");
+            "unknown".into()
+        });
+        fields.push(("latest", latest));
+    }
+
+    println!("{}", pkg.name);
+    let width = fields.iter().map(|(k, _)| k.len()).max().unwrap_or(0) + 1;
+    for (key, value) in fields {
+        let key = if key.is_empty() { String::new() } else { format!("{key}:") };
+        println!("  {key:width$} {value}");
+    }
+    Ok(())
+}
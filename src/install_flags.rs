@@ -0,0 +1,67 @@
+//! Options which packages were installed with that cargo doesn't record in .crates2.json, so that
+//! updates can keep using them without having to give them on the command line every time.
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::util;
+
+const FILE_NAME: &str = "install-flags.json";
+
+/// Options for one package. These are only recorded when given explicitly, e.g. with --locked or
+/// --no-locked, and not when they came from the config file's defaults.
+///
+/// --force isn't recorded since it's about one run rather than how a package is built, and
+/// recording it would reinstall the package on every run. Neither is the install root: each root
+/// has its own .crates2.json, so the root a package is in is already known from which one lists it.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Flags {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locked: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ignore_rust_version: Option<bool>,
+}
+
+impl Flags {
+    /// Whether no options were given
+    pub fn is_empty(&self) -> bool {
+        self.locked.is_none() && self.ignore_rust_version.is_none()
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct InstallFlags {
+    packages: BTreeMap<String, Flags>,
+}
+
+impl InstallFlags {
+    pub fn load() -> Result<Self> {
+        util::load_state(FILE_NAME)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        util::save_state(FILE_NAME, self)
+    }
+
+    /// The recorded options for a package, if any
+    pub fn get(&self, name: &str) -> Flags {
+        self.packages.get(name).cloned().unwrap_or_default()
+    }
+
+    /// Record the options that were given explicitly for a package's install, keeping any
+    /// previously recorded ones which weren't
+    pub fn record(&mut self, name: &str, flags: Flags) {
+        let entry = self.packages.entry(name.to_owned()).or_default();
+        entry.locked = flags.locked.or(entry.locked);
+        entry.ignore_rust_version = flags.ignore_rust_version.or(entry.ignore_rust_version);
+    }
+
+    /// Forget about an uninstalled package
+    pub fn remove(&mut self, name: &str) {
+        self.packages.remove(name);
+    }
+}
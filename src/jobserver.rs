@@ -0,0 +1,56 @@
+//! A GNU make jobserver shared by concurrent `cargo install` processes.
+//!
+//! Cargo and rustc take a token from the jobserver for each compiler job beyond the first, so
+//! with a shared jobserver the total number of jobs stays bounded no matter how many packages are
+//! building at once. See <https://www.gnu.org/software/make/manual/html_node/Job-Slots.html>.
+
+use std::io;
+use std::process::Command;
+
+#[cfg(unix)]
+pub struct Jobserver {
+    read: std::os::fd::OwnedFd,
+    write: std::os::fd::OwnedFd,
+}
+
+#[cfg(unix)]
+impl Jobserver {
+    /// Create a jobserver with this many tokens. Every client also has one implicit token, so the
+    /// total parallelism is `tokens` plus the number of clients.
+    pub fn new(tokens: usize) -> io::Result<Self> {
+        use std::io::Write;
+        use std::os::fd::{FromRawFd, OwnedFd};
+
+        let mut fds = [0; 2];
+        // SAFETY: fds has room for the two file descriptors. They're deliberately not close-on-exec
+        // so that cargo inherits them.
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: pipe() just created these, and nothing else owns them
+        let (read, write) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+        std::fs::File::from(write.try_clone()?).write_all(&vec![b'|'; tokens])?;
+        Ok(Self { read, write })
+    }
+
+    /// Pass the jobserver to a command through the environment
+    pub fn configure(&self, cmd: &mut Command) {
+        use std::os::fd::AsRawFd;
+
+        let fds = format!("{},{}", self.read.as_raw_fd(), self.write.as_raw_fd());
+        cmd.env("CARGO_MAKEFLAGS", format!("-j --jobserver-fds={fds} --jobserver-auth={fds}"));
+    }
+}
+
+/// Jobservers use named semaphores on Windows, which aren't supported yet
+#[cfg(not(unix))]
+pub struct Jobserver;
+
+#[cfg(not(unix))]
+impl Jobserver {
+    pub fn new(_tokens: usize) -> io::Result<Self> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "not supported on this platform"))
+    }
+
+    pub fn configure(&self, _cmd: &mut Command) {}
+}
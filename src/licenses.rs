@@ -0,0 +1,166 @@
+use std::collections::BTreeMap;
+use std::fs;
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Parser;
+
+use crate::http;
+use crate::package_data::*;
+
+/// Show the license of each installed package.
+///
+/// License information is read from the package's Cargo.toml when its source is available locally,
+/// and otherwise fetched from the crates.io API.
+#[derive(Debug, Parser)]
+pub struct LicensesArgs {
+    /// Fail if any package can only be used under this license (an SPDX identifier like
+    /// "GPL-3.0-only"). Can be given multiple times.
+    ///
+    /// License expressions are evaluated, e.g. "MIT OR GPL-3.0-only" is allowed when only
+    /// GPL-3.0-only is denied, since the package can be used under the MIT license instead.
+    #[arg(long, value_name = "LICENSE")]
+    deny: Vec<String>,
+
+    /// Also fail if the license of any package can't be determined.
+    #[arg(long)]
+    deny_unknown: bool,
+
+    /// Don't query crates.io, only use locally available package sources.
+    #[arg(long)]
+    offline: bool,
+}
+
+/// Evaluate whether an SPDX license expression is satisfiable without using any denied license.
+/// Supports AND, OR, WITH, parentheses, and the legacy "/" separator meaning OR.
+fn is_allowed(expr: &str, denied: &[String]) -> Result<bool> {
+    let expr = expr.replace('/', " OR ").replace('(', " ( ").replace(')', " ) ");
+    let tokens: Vec<&str> = expr.split_whitespace().collect();
+    let mut pos = 0;
+    let allowed = parse_or(&tokens, &mut pos, denied)?;
+    if pos != tokens.len() {
+        bail!("unexpected '{}'", tokens[pos]);
+    }
+    Ok(allowed)
+}
+
+fn parse_or(tokens: &[&str], pos: &mut usize, denied: &[String]) -> Result<bool> {
+    let mut allowed = parse_and(tokens, pos, denied)?;
+    while tokens.get(*pos).is_some_and(|t| t.eq_ignore_ascii_case("OR")) {
+        *pos += 1;
+        allowed |= parse_and(tokens, pos, denied)?;
+    }
+    Ok(allowed)
+}
+
+fn parse_and(tokens: &[&str], pos: &mut usize, denied: &[String]) -> Result<bool> {
+    let mut allowed = parse_term(tokens, pos, denied)?;
+    while tokens.get(*pos).is_some_and(|t| t.eq_ignore_ascii_case("AND")) {
+        *pos += 1;
+        allowed &= parse_term(tokens, pos, denied)?;
+    }
+    Ok(allowed)
+}
+
+fn parse_term(tokens: &[&str], pos: &mut usize, denied: &[String]) -> Result<bool> {
+    let tok = *tokens.get(*pos).ok_or_else(|| anyhow!("unexpected end of expression"))?;
+    *pos += 1;
+    if tok == "(" {
+        let allowed = parse_or(tokens, pos, denied)?;
+        if tokens.get(*pos) != Some(&")") {
+            bail!("missing ')'");
+        }
+        *pos += 1;
+        return Ok(allowed);
+    }
+    // license exceptions only grant extra permissions, so "X WITH exception" is as good as X
+    if tokens.get(*pos).is_some_and(|t| t.eq_ignore_ascii_case("WITH")) {
+        *pos += 2;
+    }
+    let id = tok.trim_end_matches('+');
+    Ok(!denied.iter().any(|d| d.eq_ignore_ascii_case(tok) || d.eq_ignore_ascii_case(id)))
+}
+
+/// Find a package's license and where we found it
+fn find_license(pkg: &Package, args: &LicensesArgs) -> Result<Option<(String, &'static str)>> {
+    if let Some(dir) = pkg.source_dir(&cargo_home()?) {
+        let manifest = dir.join("Cargo.toml");
+        if let Ok(text) = fs::read_to_string(&manifest) {
+            if let Some(license) = manifest_field(&text, "license") {
+                return Ok(Some((license, "Cargo.toml")));
+            }
+            if manifest_field(&text, "license-file").is_some() {
+                return Ok(Some(("non-standard (license-file)".into(), "Cargo.toml")));
+            }
+        }
+    }
+
+    if pkg.source.is_crates_io() && !args.offline {
+        let url = format!("https://crates.io/api/v1/crates/{}/{}", pkg.name, pkg.version);
+        let data = http::get_json(&url)?;
+        if let Some(license) = data["version"]["license"].as_str() {
+            return Ok(Some((license.into(), "crates.io")));
+        }
+    }
+    Ok(None)
+}
+
+pub fn run(args: &LicensesArgs, crates2: &Crates2) -> Result<()> {
+    let mut rows = Vec::new();
+    for pkg_id in crates2.installs.keys() {
+        let pkg = pkg_id
+            .parse::<Package>()
+            .with_context(|| format!("Failed to parse package id '{pkg_id}'"))?;
+        let license = match find_license(&pkg, args) {
+            Ok(license) => license,
+            Err(e) => {
+                warnmsg!("Warning: couldn't look up the license of {}: {e:#}", pkg.name);
+                None
+            }
+        };
+        rows.push((pkg, license));
+    }
+
+    let name_width = rows.iter().map(|(p, _)| p.name.len()).max().unwrap_or(0).max(7);
+    let ver_width = rows.iter().map(|(p, _)| p.version.len()).max().unwrap_or(0).max(7);
+    println!("{:name_width$}  {:ver_width$}  LICENSE", "PACKAGE", "VERSION");
+
+    let mut summary: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut problems = Vec::new();
+    for (pkg, license) in &rows {
+        let (text, from) = match license {
+            Some((l, from)) => (l.as_str(), *from),
+            None => ("unknown", ""),
+        };
+        let from = if from.is_empty() { String::new() } else { format!(" ({from})") };
+        println!("{:name_width$}  {:ver_width$}  {text}{from}", pkg.name, pkg.version);
+        *summary.entry(text).or_default() += 1;
+
+        match license {
+            Some((l, _)) if !args.deny.is_empty() => match is_allowed(l, &args.deny) {
+                Ok(true) => (),
+                Ok(false) => problems.push(format!("{} is licensed under {l}", pkg.name)),
+                Err(e) => warnmsg!("Warning: can't parse license '{l}' of {}: {e}", pkg.name),
+            },
+            None if args.deny_unknown => {
+                problems.push(format!("{} has no known license", pkg.name))
+            }
+            _ => (),
+        }
+    }
+
+    println!("\nSummary:");
+    let mut summary: Vec<_> = summary.into_iter().collect();
+    summary.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    for (license, count) in summary {
+        println!("{count:5}  {license}");
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        for p in &problems {
+            errmsg!("Error: {p}");
+        }
+        Err(anyhow!("{} packages failed the license check", problems.len()))
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result};
+use clap::{Parser, ValueEnum};
+use serde_json::Value;
+
+use crate::package_data::*;
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum GroupBy {
+    /// Kind of source: registry, git, or path
+    Source,
+    /// Target triple the package was built for
+    Target,
+    /// Cargo profile the package was built with
+    Profile,
+}
+
+impl GroupBy {
+    /// The group a package belongs in
+    pub fn key(self, pkg: &Package, details: &PackageDetails) -> String {
+        match self {
+            Self::Source => pkg.source.kind().to_owned(),
+            Self::Target => details.target.clone(),
+            Self::Profile => details.profile.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Aligned columns
+    Table,
+    /// A `[[packages]]` array of tables
+    Toml,
+    /// A `packages` list of mappings, e.g. for Ansible variables
+    Yaml,
+    /// Comma-separated values with a header line, e.g. for spreadsheets
+    Csv,
+}
+
+impl Format {
+    fn records(self) -> Option<RecordFormat> {
+        match self {
+            Self::Table => None,
+            Self::Toml => Some(RecordFormat::Toml),
+            Self::Yaml => Some(RecordFormat::Yaml),
+            Self::Csv => Some(RecordFormat::Csv),
+        }
+    }
+}
+
+/// Machine-readable formats for a list of packages, see [`print_records`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordFormat {
+    Toml,
+    Yaml,
+    Csv,
+}
+
+/// List installed packages.
+#[derive(Debug, Parser)]
+pub struct ListArgs {
+    /// Organize the list into sections, with the number of packages in each. The other formats
+    /// get a `group` field instead.
+    #[arg(long, value_enum, value_name = "KEY")]
+    group_by: Option<GroupBy>,
+
+    /// Output format.
+    #[arg(long, value_enum, default_value_t = Format::Table)]
+    format: Format,
+}
+
+/// Print rows as aligned columns, with a header line
+pub fn print_table(header: &[&str], rows: &[Vec<String>], indent: &str) {
+    let mut widths: Vec<usize> = header.iter().map(|h| h.len()).collect();
+    for row in rows {
+        for (w, cell) in widths.iter_mut().zip(row) {
+            *w = (*w).max(cell.len());
+        }
+    }
+    let print_row = |cells: &mut dyn Iterator<Item = &str>| {
+        let mut line = String::from(indent);
+        for (i, (cell, w)) in cells.zip(&widths).enumerate() {
+            if i + 1 == widths.len() {
+                line.push_str(cell);
+            } else {
+                line.push_str(&format!("{cell:w$}  "));
+            }
+        }
+        println!("{}", line.trim_end());
+    };
+    print_row(&mut header.iter().copied());
+    for row in rows {
+        print_row(&mut row.iter().map(String::as_str));
+    }
+}
+
+/// Quote a CSV field if it needs it
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// Print a list of packages, each with the same fields in the same order. Strings are written
+/// as JSON strings, which are valid TOML basic strings and YAML double-quoted ones.
+pub fn print_records(format: RecordFormat, records: &[Vec<(&str, Value)>]) {
+    match format {
+        RecordFormat::Toml => {
+            for (i, record) in records.iter().enumerate() {
+                if i > 0 {
+                    println!();
+                }
+                println!("[[packages]]");
+                for (key, value) in record {
+                    println!("{key} = {value}");
+                }
+            }
+        }
+        RecordFormat::Yaml if records.is_empty() => println!("packages: []"),
+        RecordFormat::Yaml => {
+            println!("packages:");
+            for record in records {
+                for (i, (key, value)) in record.iter().enumerate() {
+                    let dash = if i == 0 { "-" } else { " " };
+                    println!("  {dash} {key}: {value}");
+                }
+            }
+        }
+        RecordFormat::Csv => {
+            let Some(first) = records.first() else { return };
+            let header: Vec<&str> = first.iter().map(|(key, _)| *key).collect();
+            println!("{}", header.join(","));
+            for record in records {
+                let fields: Vec<String> = record
+                    .iter()
+                    .map(|(_, value)| match value {
+                        Value::String(s) => csv_field(s),
+                        value => value.to_string(),
+                    })
+                    .collect();
+                println!("{}", fields.join(","));
+            }
+        }
+    }
+}
+
+pub fn run(args: &ListArgs, crates2: &Crates2) -> Result<()> {
+    if let Some(format) = args.format.records() {
+        let mut records = Vec::new();
+        for (pkg_id, details) in &crates2.installs {
+            let pkg = pkg_id
+                .parse::<Package>()
+                .with_context(|| format!("Failed to parse package id '{pkg_id}'"))?;
+            let mut record = vec![
+                ("name", Value::from(pkg.name.as_str())),
+                ("version", pkg.version.as_str().into()),
+                ("source", pkg.source.to_string().into()),
+            ];
+            if let Some(group_by) = args.group_by {
+                record.push(("group", group_by.key(&pkg, details).into()));
+            }
+            records.push(record);
+        }
+        print_records(format, &records);
+        return Ok(());
+    }
+
+    let mut groups: BTreeMap<String, Vec<Vec<String>>> = BTreeMap::new();
+    for (pkg_id, details) in &crates2.installs {
+        let pkg = pkg_id
+            .parse::<Package>()
+            .with_context(|| format!("Failed to parse package id '{pkg_id}'"))?;
+        let key = args.group_by.map(|g| g.key(&pkg, details)).unwrap_or_default();
+        let row = vec![pkg.name, pkg.version, pkg.source.to_string()];
+        groups.entry(key).or_default().push(row);
+    }
+
+    let header = ["NAME", "VERSION", "SOURCE"];
+    if args.group_by.is_none() {
+        print_table(&header, groups.values().next().map_or(&[], Vec::as_slice), "");
+        return Ok(());
+    }
+    for (i, (key, rows)) in groups.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        let s = if rows.len() == 1 { "" } else { "s" };
+        println!("{key} ({} package{s})", rows.len());
+        print_table(&header, rows, "  ");
+    }
+    println!("\nTotal: {} packages", crates2.installs.len());
+    Ok(())
+}
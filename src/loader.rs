@@ -0,0 +1,52 @@
+//! Detect executables which can't start because of missing shared libraries.
+
+use std::path::Path;
+#[cfg(unix)]
+use std::process::Command;
+
+#[cfg(unix)]
+use anyhow::Context;
+use anyhow::Result;
+
+/// List the shared libraries needed by an executable which can't be found.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn missing_libraries(exe: &Path) -> Result<Vec<String>> {
+    let out = Command::new("ldd").arg(exe).output().context("Failed to run ldd")?;
+    // ldd exits non-zero for static executables, which can't be missing anything
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    Ok(stdout
+        .lines()
+        .filter_map(|line| {
+            let (lib, path) = line.trim().split_once(" => ")?;
+            path.trim().starts_with("not found").then(|| lib.trim().to_owned())
+        })
+        .collect())
+}
+
+/// List the shared libraries needed by an executable which can't be found.
+#[cfg(target_os = "macos")]
+pub fn missing_libraries(exe: &Path) -> Result<Vec<String>> {
+    let out = Command::new("otool").arg("-L").arg(exe).output().context("Failed to run otool")?;
+    if !out.status.success() {
+        anyhow::bail!("otool failed on '{}'", exe.display());
+    }
+    // the first line is the executable's name, then one indented library path per line followed
+    // by version info in parentheses. Paths relative to @rpath etc. can't be checked easily
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    Ok(stdout
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let lib = line.trim().split(" (").next()?;
+            (!lib.starts_with('@') && !Path::new(lib).exists()).then(|| lib.to_owned())
+        })
+        // since Big Sur, system libraries only exist in the dyld shared cache and not on disk
+        .filter(|lib| !lib.starts_with("/usr/lib/") && !lib.starts_with("/System/"))
+        .collect())
+}
+
+/// List the shared libraries needed by an executable which can't be found.
+#[cfg(not(unix))]
+pub fn missing_libraries(_exe: &Path) -> Result<Vec<String>> {
+    anyhow::bail!("checking for missing shared libraries isn't supported on this platform")
+}
@@ -0,0 +1,87 @@
+//! Checking whether installed packages are still maintained.
+//!
+//! A package is flagged when its repository on GitHub or GitLab is archived, or when RustSec has an
+//! "unmaintained" informational advisory for it, which is looked up in the OSV database.
+
+use std::fs;
+
+use anyhow::{Context, Result};
+use url::form_urlencoded::byte_serialize;
+use url::Url;
+
+use crate::http;
+use crate::package_data::*;
+
+/// The package's repository URL, from its Cargo.toml or crates.io
+fn repository(pkg: &Package) -> Result<Option<String>> {
+    if let PackageSource::Git { url, .. } = &pkg.source {
+        return Ok(Some(url.clone()));
+    }
+    if let Some(dir) = pkg.source_dir(&cargo_home()?) {
+        if let Ok(text) = fs::read_to_string(dir.join("Cargo.toml")) {
+            return Ok(manifest_field(&text, "repository"));
+        }
+    }
+    if pkg.source.is_crates_io() {
+        let data = http::get_json(&format!("https://crates.io/api/v1/crates/{}", pkg.name))?;
+        return Ok(data["crate"]["repository"].as_str().map(String::from));
+    }
+    Ok(None)
+}
+
+/// Check whether a repository is archived, if it's hosted somewhere we know how to ask
+fn is_archived(repo: &str) -> Result<bool> {
+    let Ok(url) = Url::parse(repo) else { return Ok(false) };
+    // only the owner and repo name, without e.g. "/tree/main/subdir"
+    let mut path = url.path().trim_matches('/').splitn(3, '/');
+    let (Some(owner), Some(name)) = (path.next(), path.next()) else { return Ok(false) };
+    let name = name.trim_end_matches(".git");
+    let api = match url.host_str() {
+        Some("github.com") => format!("https://api.github.com/repos/{owner}/{name}"),
+        Some("gitlab.com") => {
+            let id: String = byte_serialize(format!("{owner}/{name}").as_bytes()).collect();
+            format!("https://gitlab.com/api/v4/projects/{id}")
+        }
+        _ => return Ok(false),
+    };
+    Ok(http::get_json(&api)?["archived"].as_bool().unwrap_or(false))
+}
+
+/// Find RustSec "unmaintained" advisories which affect the installed version of a package
+fn unmaintained_advisories(pkg: &Package) -> Result<Vec<String>> {
+    let query = serde_json::json!({
+        "version": pkg.version,
+        "package": { "name": pkg.name, "ecosystem": "crates.io" },
+    });
+    let data = http::post_json("https://api.osv.dev/v1/query", &query)?;
+    let mut advisories = Vec::new();
+    for vuln in data["vulns"].as_array().into_iter().flatten() {
+        let informational = |v: &serde_json::Value| {
+            v["database_specific"]["informational"].as_str() == Some("unmaintained")
+        };
+        let unmaintained = informational(vuln)
+            || vuln["affected"].as_array().into_iter().flatten().any(informational);
+        if unmaintained && vuln["withdrawn"].is_null() {
+            let id = vuln["id"].as_str().unwrap_or("unknown advisory");
+            match vuln["summary"].as_str() {
+                Some(summary) => advisories.push(format!("{id} ({summary})")),
+                None => advisories.push(id.to_owned()),
+            }
+        }
+    }
+    Ok(advisories)
+}
+
+/// Find reasons to think a package is no longer maintained
+pub fn check(pkg: &Package) -> Result<Vec<String>> {
+    let mut problems = Vec::new();
+    if pkg.source.is_crates_io() {
+        problems.extend(unmaintained_advisories(pkg).context("Failed to check RustSec")?);
+    }
+    if let Some(repo) = repository(pkg).context("Failed to find the repository")? {
+        if is_archived(&repo).with_context(|| format!("Failed to check {repo}"))? {
+            problems.push(format!("{repo} is archived"));
+        }
+    }
+    Ok(problems)
+}
@@ -0,0 +1,137 @@
+//! Reading package manifests: finding the programs a package provides from the sources cargo
+//! unpacked when it built the package, and the version and workspace member of a package in a
+//! local directory.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+use crate::package_data::*;
+use crate::semver::Version;
+use crate::toml;
+
+/// Read the Cargo.toml in a directory
+fn read(dir: &Path) -> Result<Value> {
+    let path = dir.join("Cargo.toml");
+    let text = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read '{}'", path.display()))?;
+    toml::parse(&text).with_context(|| format!("Failed to parse '{}'", path.display()))
+}
+
+/// List the binary targets of the package in a directory, following cargo's rules for finding
+/// them: the `[[bin]]` tables, plus src/main.rs, src/bin/*.rs, and src/bin/*/main.rs unless
+/// `autobins = false`
+fn bin_targets(dir: &Path) -> Result<Vec<String>> {
+    let manifest = read(dir)?;
+    let package = &manifest["package"];
+
+    let mut bins = Vec::new();
+    let mut paths = Vec::new();
+    for bin in manifest["bin"].as_array().into_iter().flatten() {
+        if let Some(name) = bin["name"].as_str() {
+            bins.push(name.to_owned());
+        }
+        if let Some(path) = bin["path"].as_str() {
+            paths.push(dir.join(path));
+        }
+    }
+    if package["autobins"] != Value::Bool(false) {
+        let mut inferred = Vec::new();
+        if let Some(name) = package["name"].as_str() {
+            inferred.push((name.to_owned(), dir.join("src").join("main.rs")));
+        }
+        let entries = fs::read_dir(dir.join("src").join("bin")).into_iter().flatten().flatten();
+        for path in entries.map(|e| e.path()) {
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+            if path.is_dir() {
+                inferred.push((file_name.into_owned(), path.join("main.rs")));
+            } else if let Some(name) = file_name.strip_suffix(".rs") {
+                inferred.push((name.to_owned(), path.clone()));
+            }
+        }
+        for (name, main) in inferred {
+            if main.is_file() && !paths.contains(&main) && !bins.contains(&name) {
+                bins.push(name);
+            }
+        }
+    }
+    Ok(bins)
+}
+
+/// The programs an installed package provides, which may be more than were installed if it was
+/// installed with --bin
+pub fn provided_bins(pkg: &Package) -> Option<Vec<String>> {
+    let dir = pkg.source_dir(&cargo_home().ok()?)?;
+    bin_targets(&dir)
+        .map_err(|e| dbgmsg!("Couldn't find the programs {} provides: {e:#}", pkg.name))
+        .ok()
+}
+
+/// The version of the package in a directory, which may be inherited from its workspace. Returns
+/// None if it doesn't have one.
+pub fn package_version(dir: &Path) -> Result<Option<Version>> {
+    let version = &read(dir)?["package"]["version"];
+    if let Some(version) = version.as_str() {
+        return Ok(Some(version.parse()?));
+    }
+    if version["workspace"] != Value::Bool(true) {
+        return Ok(None);
+    }
+    for parent in dir.ancestors().skip(1).filter(|d| d.join("Cargo.toml").is_file()) {
+        let manifest = read(parent)?;
+        if manifest.get("workspace").is_some() {
+            let version = manifest["workspace"]["package"]["version"].as_str();
+            return version.map(str::parse).transpose();
+        }
+    }
+    Ok(None)
+}
+
+/// Find the directory of the package called `name` in a local directory, which is either the
+/// package itself or the root of a workspace which has it as a member. Returns None if it isn't
+/// either.
+pub fn package_dir(dir: &Path, name: &str) -> Result<Option<PathBuf>> {
+    let manifest = read(dir)?;
+    if manifest["package"]["name"].as_str() == Some(name) {
+        return Ok(Some(dir.to_owned()));
+    }
+    let workspace = &manifest["workspace"];
+    let excluded: Vec<PathBuf> = workspace["exclude"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .map(|p| dir.join(p))
+        .collect();
+    let root = glob::Pattern::escape(&dir.to_string_lossy());
+    for member in workspace["members"].as_array().into_iter().flatten().filter_map(Value::as_str) {
+        let member_dirs = glob::glob(&format!("{root}/{member}")).into_iter().flatten().flatten();
+        for member_dir in member_dirs.filter(|d| !excluded.contains(d)) {
+            let Ok(manifest) = read(&member_dir) else { continue };
+            if manifest["package"]["name"].as_str() == Some(name) {
+                return Ok(Some(member_dir));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// The directory to install a path package from, which is where it was installed from unless
+/// that's its workspace's root
+pub fn install_dir(dir: &Path, name: &str) -> PathBuf {
+    match package_dir(dir, name) {
+        Ok(Some(member)) => {
+            if member != dir {
+                dbgmsg!("Using {name} from '{}' in the workspace", member.display());
+            }
+            member
+        }
+        Ok(None) => dir.to_owned(),
+        Err(e) => {
+            dbgmsg!("Couldn't look for {name} in '{}': {e:#}", dir.display());
+            dir.to_owned()
+        }
+    }
+}
@@ -0,0 +1,478 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::env;
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{Context, Result};
+use clap::{Parser, ValueEnum};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config::Config;
+use crate::crev::Crev;
+use crate::http;
+use crate::index;
+use crate::list::{print_records, print_table, GroupBy, RecordFormat};
+use crate::maintenance;
+use crate::package_data::*;
+use crate::redirects::Redirects;
+use crate::semver::{Bump, Version};
+use crate::toolchain;
+use crate::util;
+use crate::version_source::{self, Available, GitLsRemote, LocalPath, SparseIndex, VersionSource};
+use crate::Failure;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Aligned columns with the installed and latest versions
+    Table,
+    /// Only package names, one per line, e.g. to pipe into `cargo update-installed -`
+    Names,
+    /// A `[[packages]]` array of tables
+    Toml,
+    /// A `packages` list of mappings, e.g. for Ansible variables
+    Yaml,
+    /// Comma-separated values with a header line, e.g. for spreadsheets
+    Csv,
+}
+
+/// The order outdated packages are listed in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Sort {
+    /// Alphabetically by name
+    Name,
+    /// Furthest behind by version first: major updates, then minor, then patch, and the most
+    /// releases behind first within each
+    Bump,
+    /// Furthest behind by release date first, how long after the installed version the latest
+    /// one was released. Only crates.io packages have release dates, the others come last.
+    Staleness,
+}
+
+impl Format {
+    fn records(self) -> Option<RecordFormat> {
+        match self {
+            Self::Table | Self::Names => None,
+            Self::Toml => Some(RecordFormat::Toml),
+            Self::Yaml => Some(RecordFormat::Yaml),
+            Self::Csv => Some(RecordFormat::Csv),
+        }
+    }
+}
+
+/// List installed packages which have newer versions available.
+///
+/// Packages from crates.io are checked against its index, and git packages against the latest
+/// commit of their branch or tag. Packages from other registries or local paths aren't checked.
+/// The release dates of crates.io packages' installed and latest versions are shown too, which
+/// takes an API request for each outdated package.
+///
+/// If crates.io can't be reached, cargo's local cache of its index is used instead, which may be
+/// out of date.
+///
+/// If you have a cargo-crev identity, the crev reviews of each latest version are shown too, from
+/// the proof repositories which `cargo crev repo fetch` has fetched.
+#[derive(Debug, Parser)]
+pub struct OutdatedArgs {
+    /// Organize the list into sections, with the number of packages in each. The TOML, YAML, and
+    /// CSV formats get a `group` field instead.
+    #[arg(long, value_enum, value_name = "KEY")]
+    group_by: Option<GroupBy>,
+
+    /// Output format.
+    #[arg(long, value_enum, default_value_t = Format::Table)]
+    format: Format,
+
+    /// The order to list packages in.
+    #[arg(long, value_enum, default_value_t = Sort::Name)]
+    sort: Sort,
+
+    /// Exit with status 6 if any packages are outdated.
+    #[arg(long)]
+    check: bool,
+
+    /// Only use cargo's local index cache, without any network access. Git packages aren't
+    /// checked. This is the default when CARGO_NET_OFFLINE is set.
+    #[arg(long)]
+    offline_check: bool,
+
+    /// Also check whether packages are still maintained, by looking for archived repositories on
+    /// GitHub and GitLab and RustSec "unmaintained" advisories. Set GITHUB_TOKEN to avoid GitHub's
+    /// low rate limit for anonymous requests.
+    #[arg(long, conflicts_with = "offline_check")]
+    check_maintenance: bool,
+
+    /// Only list packages whose installed version was released at least DURATION (e.g. "1y",
+    /// "26w") before the latest one. Only crates.io packages have release dates.
+    #[arg(long, value_name = "DURATION", value_parser = util::parse_duration, conflicts_with = "offline_check")]
+    older_than: Option<Duration>,
+
+    /// Only list packages which weren't already listed with the same latest version by an earlier
+    /// run, e.g. for a shell greeting.
+    #[arg(long)]
+    new_only: bool,
+
+    /// Show what moving to Rust VERSION (e.g. "1.85") would change instead: which packages were
+    /// built with an older rustc and would be candidates for rebuilding, and which crates.io
+    /// packages would be able to update further, or less far, because of the Rust version their
+    /// releases need.
+    #[arg(
+        long,
+        value_name = "VERSION",
+        value_parser = index::parse_rust_version,
+        conflicts_with_all = ["offline_check", "check", "new_only", "older_than"]
+    )]
+    simulate_rustc: Option<Version>,
+}
+
+const SEEN_FILE: &str = "outdated-seen.json";
+pub const LAST_CHECK_FILE: &str = "last-check.json";
+
+/// What the last check found, which `status` shows without checking again
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LastCheck {
+    pub checked: Option<String>,
+    /// The installed and newer version of each outdated package
+    pub outdated: BTreeMap<String, (String, String)>,
+}
+
+/// The latest versions which have been listed, by package name
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+struct Seen {
+    packages: BTreeMap<String, String>,
+}
+
+/// A newer version of a package, or the version it's pinned to
+struct Newer {
+    /// The version, or the start of the commit hash for git packages
+    version: String,
+    pinned: bool,
+    /// When the installed and newer versions were published, for crates.io packages
+    released: Option<(SystemTime, SystemTime)>,
+}
+
+impl Newer {
+    fn new(version: impl Into<String>) -> Self {
+        Self { version: version.into(), pinned: false, released: None }
+    }
+}
+
+/// Find the latest version of a package if it's newer than the installed one, or the version it's
+/// pinned to if that's different
+fn newer_version(
+    pkg: &Package,
+    pin: Option<&str>,
+    sources: &[&dyn VersionSource],
+) -> Result<Option<Newer>> {
+    if pin.is_some() {
+        return Ok(pin
+            .filter(|&p| p != pkg.version)
+            .map(|p| Newer { pinned: true, ..Newer::new(p) }));
+    }
+    // without the installed commit there's nothing to compare the latest one to
+    if matches!(pkg.source, PackageSource::Git { rev: None, .. }) {
+        return Ok(None);
+    }
+    Ok(match version_source::latest(sources, pkg)? {
+        Some(Available::Version(v)) => {
+            (v > pkg.version.parse()?).then(|| Newer::new(v.to_string()))
+        }
+        Some(Available::Commit(latest)) => match &pkg.source {
+            PackageSource::Git { rev: Some(rev), .. } if !latest.starts_with(rev.as_str()) => {
+                Some(Newer::new(&latest[..latest.len().min(8)]))
+            }
+            _ => None,
+        },
+        None => None,
+    })
+}
+
+/// Look up when two versions of a crates.io package were published. The index doesn't have this,
+/// so it takes an API request.
+fn release_dates(
+    name: &str,
+    installed: &str,
+    newer: &str,
+) -> Result<Option<(SystemTime, SystemTime)>> {
+    let data = http::get_json(&format!("https://crates.io/api/v1/crates/{name}"))?;
+    let date = |version: &str| {
+        data["versions"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|v| v["num"].as_str() == Some(version))
+            .and_then(|v| util::parse_timestamp(v["created_at"].as_str()?))
+    };
+    Ok(date(installed).zip(date(newer)))
+}
+
+/// How far behind a package is by version, if both versions are semver versions
+fn distance(pkg: &Package, newer: &Newer) -> Option<(Bump, u64, u64, u64)> {
+    let installed = pkg.version.parse::<Version>().ok()?;
+    let latest = newer.version.parse::<Version>().ok()?;
+    Some((
+        installed.bump_to(&latest),
+        latest.major.saturating_sub(installed.major),
+        latest.minor.saturating_sub(installed.minor),
+        latest.patch.saturating_sub(installed.patch),
+    ))
+}
+
+/// Format a version with its release date, e.g. "1.2.3 (2023-05-31)"
+fn with_date(version: &str, date: Option<SystemTime>) -> String {
+    match date {
+        Some(date) => format!("{version} ({})", &util::format_timestamp(date)[..10]),
+        None => version.to_owned(),
+    }
+}
+
+/// Warn about packages which look unmaintained
+fn report_unmaintained(packages: &[(Package, &PackageDetails)]) {
+    msg!("Checking whether packages are still maintained");
+    let problems = http::map_concurrent(packages, |(pkg, _)| maintenance::check(pkg));
+    let mut count = 0;
+    for ((pkg, _), problems) in packages.iter().zip(problems) {
+        match problems {
+            Ok(problems) => {
+                for problem in &problems {
+                    warnmsg!("Warning: {} looks unmaintained: {problem}", pkg.name);
+                }
+                count += usize::from(!problems.is_empty());
+            }
+            Err(e) => {
+                warnmsg!("Warning: failed to check whether {} is maintained: {e:#}", pkg.name)
+            }
+        }
+    }
+    match count {
+        0 => msg!("No unmaintained packages found"),
+        1 => msg!("1 package looks unmaintained, consider replacing it"),
+        n => msg!("{n} packages look unmaintained, consider replacing them"),
+    }
+}
+
+/// The check of `--every --check-only`, which only lists packages with newly found versions
+pub fn check_new(crates2: &Crates2, config: &Config) -> Result<()> {
+    run(&OutdatedArgs::parse_from(["outdated", "--new-only"]), crates2, config)
+}
+
+pub fn run(args: &OutdatedArgs, crates2: &Crates2, config: &Config) -> Result<()> {
+    if let Some(version) = &args.simulate_rustc {
+        return toolchain::run(crates2, version);
+    }
+    let redirects = Redirects::load().unwrap_or_else(|e| {
+        warnmsg!("Warning: {e:#}");
+        Redirects::default()
+    });
+    let mut packages = Vec::new();
+    for (pkg_id, details) in &crates2.installs {
+        let mut pkg = pkg_id
+            .parse::<Package>()
+            .with_context(|| format!("Failed to parse package id '{pkg_id}'"))?;
+        config.rewrite_git_source(&mut pkg);
+        redirects.apply(&mut pkg);
+        packages.push((pkg, details));
+    }
+
+    let offline =
+        args.offline_check || env::var("CARGO_NET_OFFLINE").is_ok_and(|v| v == "true" || v == "1");
+    if offline {
+        msg!("Checking {} packages for updates using the local index cache", packages.len());
+    } else {
+        msg!("Checking {} packages for updates", packages.len());
+    }
+    let want_dates = !offline
+        && (args.format == Format::Table
+            || args.older_than.is_some()
+            || args.sort == Sort::Staleness);
+    let index = SparseIndex::new(offline);
+    let mut sources: Vec<&dyn VersionSource> = vec![&index, &LocalPath];
+    if !offline {
+        sources.push(&GitLsRemote);
+    }
+    let start = Instant::now();
+    if !offline {
+        index::prefetch(
+            packages
+                .iter()
+                .filter(|(pkg, _)| pkg.source.is_crates_io() && config.pin(&pkg.name).is_none())
+                .map(|(pkg, _)| pkg.name.as_str()),
+        );
+    }
+    let mut latest = http::map_concurrent(&packages, |(pkg, _)| {
+        newer_version(pkg, config.pin(&pkg.name), &sources)
+    });
+    dbgmsg!("Checked {} packages in {:.1}s", packages.len(), start.elapsed().as_secs_f64());
+    if want_dates {
+        // looked up separately since the API is much more rate limited than the index, so waiting
+        // for it would hold up the version checks
+        let mut dated: Vec<(&Package, &mut Newer)> = packages
+            .iter()
+            .zip(latest.iter_mut())
+            .filter(|((pkg, _), _)| pkg.source.is_crates_io())
+            .filter_map(|((pkg, _), newer)| Some((pkg, newer.as_mut().ok()?.as_mut()?)))
+            .collect();
+        let dates = http::map_concurrent(&dated, |(pkg, newer)| {
+            release_dates(&pkg.name, &pkg.version, &newer.version)
+                .map_err(|e| dbgmsg!("Couldn't get the release dates of {}: {e:#}", pkg.name))
+                .ok()
+                .flatten()
+        });
+        for ((_, newer), released) in dated.iter_mut().zip(dates) {
+            newer.released = released;
+        }
+    }
+    let from_cache = index.cache_fallbacks();
+    if from_cache > 0 {
+        let (s, were) = if from_cache == 1 { ("", "was") } else { ("s", "were") };
+        warnmsg!(
+            "Warning: couldn't reach crates.io, so {from_cache} package{s} {were} checked using \
+             cargo's local index cache, which may be out of date"
+        );
+    }
+    let mut seen: Seen = util::load_state(SEEN_FILE).unwrap_or_else(|e| {
+        warnmsg!("Warning: {e:#}");
+        Seen::default()
+    });
+    let crev = if args.format == Format::Table {
+        Crev::load().unwrap_or_else(|e| {
+            warnmsg!("Warning: failed to read crev reviews: {e:#}");
+            None
+        })
+    } else {
+        None
+    };
+    let mut last_check = LastCheck {
+        checked: Some(util::format_timestamp(SystemTime::now())),
+        ..Default::default()
+    };
+    let mut groups: BTreeMap<String, Vec<Vec<String>>> = BTreeMap::new();
+    let mut records = Vec::new();
+    let mut count = 0;
+    let mut checked: Vec<_> = packages.iter().zip(latest).collect();
+    // packages are already in order of name, and the sorts are stable so ties stay that way
+    fn newer(latest: &Result<Option<Newer>>) -> Option<&Newer> {
+        latest.as_ref().ok().and_then(Option::as_ref)
+    }
+    match args.sort {
+        Sort::Name => (),
+        Sort::Bump => checked.sort_by_key(|((pkg, _), latest)| {
+            Reverse(newer(latest).and_then(|newer| distance(pkg, newer)))
+        }),
+        Sort::Staleness => checked.sort_by_key(|(_, latest)| {
+            Reverse(newer(latest).and_then(|newer| {
+                let (installed, latest) = newer.released?;
+                Some(latest.duration_since(installed).unwrap_or_default())
+            }))
+        }),
+    }
+    for ((pkg, details), latest) in checked {
+        let newer = match latest {
+            Ok(Some(newer)) => newer,
+            Ok(None) => {
+                // so that it's new again when it's next outdated
+                seen.packages.remove(&pkg.name);
+                continue;
+            }
+            Err(e) => {
+                warnmsg!("Warning: failed to check {}: {e:#}", pkg.name);
+                continue;
+            }
+        };
+        last_check.outdated.insert(pkg.name.clone(), (pkg.version.clone(), newer.version.clone()));
+        if let Some(min) = args.older_than {
+            // packages without release dates can't be shown to be old enough
+            let Some((installed, latest)) = newer.released else { continue };
+            if latest.duration_since(installed).unwrap_or_default() < min {
+                continue;
+            }
+        }
+        if args.new_only && seen.packages.get(&pkg.name) == Some(&newer.version) {
+            continue;
+        }
+        seen.packages.insert(pkg.name.clone(), newer.version.clone());
+        count += 1;
+        if args.format == Format::Names {
+            println!("{}", pkg.name);
+            continue;
+        }
+        if args.format.records().is_some() {
+            let mut record = vec![
+                ("name", Value::from(pkg.name.as_str())),
+                ("installed", pkg.version.as_str().into()),
+                ("latest", newer.version.as_str().into()),
+                ("pinned", newer.pinned.into()),
+                ("source", pkg.source.to_string().into()),
+            ];
+            if let Some(group_by) = args.group_by {
+                record.push(("group", group_by.key(pkg, details).into()));
+            }
+            records.push(record);
+            continue;
+        }
+        let key = args.group_by.map(|g| g.key(pkg, details)).unwrap_or_default();
+        let (installed_date, latest_date) = newer.released.unzip();
+        let mut latest = with_date(&newer.version, latest_date);
+        if newer.pinned {
+            latest += " (pinned)";
+        }
+        let mut row = vec![
+            pkg.name.clone(),
+            with_date(&pkg.version, installed_date),
+            latest,
+            pkg.source.to_string(),
+        ];
+        if let Some(crev) = &crev {
+            row.push(match pkg.source.is_crates_io() {
+                true => crev.reviews(&pkg.name, &newer.version).to_string(),
+                false => "-".into(),
+            });
+        }
+        groups.entry(key).or_default().push(row);
+    }
+
+    if let Err(e) = util::save_state(SEEN_FILE, &seen) {
+        warnmsg!("Warning: failed to save the listed versions: {e:#}");
+    }
+    if let Err(e) = util::save_state(LAST_CHECK_FILE, &last_check) {
+        warnmsg!("Warning: failed to save the results of the check: {e:#}");
+    }
+
+    let mut header = vec!["NAME", "INSTALLED", "LATEST", "SOURCE"];
+    if crev.is_some() {
+        header.push("REVIEWS");
+    }
+    if let Some(format) = args.format.records() {
+        print_records(format, &records);
+    }
+    if count == 0 && args.new_only {
+        msg!("No newly outdated packages");
+    } else if count == 0 {
+        msg!("All packages are up to date");
+    } else if args.format != Format::Table {
+        // already printed
+    } else if args.group_by.is_none() {
+        print_table(&header, groups.values().next().map_or(&[], Vec::as_slice), "");
+    } else {
+        for (i, (key, rows)) in groups.iter().enumerate() {
+            if i > 0 {
+                println!();
+            }
+            let s = if rows.len() == 1 { "" } else { "s" };
+            println!("{key} ({} outdated package{s})", rows.len());
+            print_table(&header, rows, "  ");
+        }
+        println!("\nTotal: {count} outdated packages");
+    }
+
+    if args.check_maintenance && !offline {
+        report_unmaintained(&packages);
+    }
+
+    if args.check && count > 0 {
+        return Err(Failure::Outdated.into());
+    }
+    Ok(())
+}
@@ -1,8 +1,11 @@
 use std::collections::BTreeMap;
 use std::env;
-use std::fs::File;
-use std::io::BufReader;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use anyhow::{anyhow, bail, ensure, Context, Error as AnyhowError, Result};
 use once_cell::sync::Lazy;
@@ -10,35 +13,113 @@ use regex::Regex;
 use serde::Deserialize;
 use url::Url;
 
+use crate::semver::Version;
 use crate::PushStr;
 
 /// Top-level deserialized struct of .crates2.json.
-/// Note: this metadata format probably isn't "stable" and future Cargo versions might break this.
-#[derive(Debug, Deserialize)]
+/// Note: this metadata format probably isn't "stable" and future Cargo versions might break this,
+/// so entries which can't be parsed are left out of `installs` unless [`STRICT`] is set.
+#[derive(Debug)]
 pub struct Crates2 {
     pub installs: BTreeMap<String, PackageDetails>,
+    /// The IDs of entries which couldn't be parsed, with why
+    pub invalid: Vec<(String, String)>,
+}
+
+#[derive(Deserialize)]
+struct RawCrates2 {
+    installs: BTreeMap<String, serde_json::Value>,
+}
+
+/// Whether an entry of .crates2.json which can't be parsed is an error, set by --strict
+pub static STRICT: AtomicBool = AtomicBool::new(false);
+
+/// Find Cargo's home directory, either from $CARGO_HOME or the default ~/.cargo
+pub fn cargo_home() -> Result<PathBuf> {
+    match env::var_os("CARGO_HOME") {
+        Some(s) => Ok(s.into()),
+        None => {
+            let mut dir = dirs::home_dir()
+                .ok_or_else(|| anyhow!("Unable to find home directory, and CARGO_HOME is unset"))?;
+            dir.push(".cargo");
+            Ok(dir)
+        }
+    }
 }
 
 impl Crates2 {
+    /// Path to Cargo's .crates2.json file
+    pub fn path() -> Result<PathBuf> {
+        Ok(cargo_home()?.join(".crates2.json"))
+    }
+
     /// Find and load Cargo's .crates2.json file
     pub fn load() -> Result<Self> {
-        let mut path = match env::var_os("CARGO_HOME") {
-            Some(s) => s.into(),
-            None => {
-                let mut dir = dirs::home_dir().ok_or_else(|| {
-                    anyhow!("Unable to find home directory, and CARGO_HOME is unset")
-                })?;
-                dir.push(".cargo");
-                dir
+        static WARNED: AtomicBool = AtomicBool::new(false);
+        let crates2 = Self::load_from(&Self::path()?)?;
+        if !crates2.invalid.is_empty() && !WARNED.swap(true, Ordering::Relaxed) {
+            let n = crates2.invalid.len();
+            let (s, they) = if n == 1 { ("y", "it") } else { ("ies", "they") };
+            warnmsg!(
+                "Warning: skipping {n} entr{s} of .crates2.json which can't be parsed, so {they} \
+                 won't be updated (see `doctor`, or use --strict to stop instead)"
+            );
+        }
+        Ok(crates2)
+    }
+
+    /// Packages with more than one entry, which old versions of cargo or editing the file by hand
+    /// can leave behind. Returns the ID of the newest entry of each, which is the one that counts,
+    /// and the IDs of the stale ones.
+    pub fn duplicates(&self) -> Vec<(&str, Vec<&str>)> {
+        let mut by_name: BTreeMap<String, Vec<(&str, Package)>> = BTreeMap::new();
+        for id in self.installs.keys() {
+            if let Ok(pkg) = id.parse::<Package>() {
+                by_name.entry(pkg.name.clone()).or_default().push((id, pkg));
             }
-        };
-        path.push(".crates2.json");
+        }
+        let mut duplicates = Vec::new();
+        for mut entries in by_name.into_values().filter(|e| e.len() > 1) {
+            entries.sort_by(|(_, a), (_, b)| {
+                match (a.version.parse::<Version>(), b.version.parse::<Version>()) {
+                    (Ok(a), Ok(b)) => a.cmp(&b),
+                    _ => a.version.cmp(&b.version),
+                }
+            });
+            let (newest, _) = entries.pop().unwrap();
+            duplicates.push((newest, entries.into_iter().map(|(id, _)| id).collect()));
+        }
+        duplicates
+    }
 
+    /// Load a .crates2.json file from somewhere else, e.g. a saved copy
+    pub fn load_from(path: &Path) -> Result<Self> {
         let file = BufReader::new(
-            File::open(&path).with_context(|| format!("Failed to open '{}'", path.display()))?,
+            File::open(path).with_context(|| format!("Failed to open '{}'", path.display()))?,
         );
-        serde_json::from_reader(file)
-            .with_context(|| format!("Failed to parse '{}'", path.display()))
+        let raw: RawCrates2 = serde_json::from_reader(file)
+            .with_context(|| format!("Failed to parse '{}'", path.display()))?;
+        let mut crates2 = Self { installs: BTreeMap::new(), invalid: Vec::new() };
+        for (pkg_id, details) in raw.installs {
+            let parsed = pkg_id
+                .parse::<Package>()
+                .and_then(|_| PackageDetails::deserialize(details).map_err(AnyhowError::from));
+            match parsed {
+                Ok(details) => {
+                    crates2.installs.insert(pkg_id, details);
+                }
+                Err(e) if STRICT.load(Ordering::Relaxed) => {
+                    return Err(e).with_context(|| {
+                        format!("Failed to parse '{pkg_id}' in '{}'", path.display())
+                    });
+                }
+                Err(e) => {
+                    dbgmsg!("Skipping '{pkg_id}' in '{}': {e:#}", path.display());
+                    crates2.invalid.push((pkg_id, format!("{e:#}")));
+                }
+            }
+        }
+        Ok(crates2)
     }
 }
 
@@ -46,10 +127,11 @@ impl Crates2 {
 pub enum PackageSource {
     /// Package installed from a registry with this URL
     Registry(String),
-    /// Package installed from git using this URL and Revision
-    Git { url: String, branch: Option<String>, tag: Option<String> },
+    /// Package installed from git using this URL and Revision. `rev` is the commit which was
+    /// installed, which isn't used for updating.
+    Git { url: String, branch: Option<String>, tag: Option<String>, rev: Option<String> },
     /// Package installed from the filesystem
-    Path(String),
+    Path(PathBuf),
 }
 
 impl FromStr for PackageSource {
@@ -72,7 +154,8 @@ impl FromStr for PackageSource {
         // now parse the rest as a url
         let mut url = Url::parse(url).context("Failed to parse package source URL")?;
 
-        // git URLs put the revision in the fragment, which we don't actually care about - yeet it
+        // git URLs put the installed revision in the fragment, save it and yeet it from the URL
+        let rev = url.fragment().map(String::from);
         url.set_fragment(None);
 
         // git URLs put the branch/tag into the query params, which we do want to save
@@ -90,18 +173,138 @@ impl FromStr for PackageSource {
 
         Ok(match kind {
             "registry" => Self::Registry(url.into()),
-            "git" => Self::Git { url: url.into(), branch, tag },
-            "path" => Self::Path(url.path().to_owned()),
+            "git" => Self::Git { url: url.into(), branch, tag, rev },
+            "path" => Self::Path(native_path(&url)?),
             k => bail!("Unknown package source kind '{k}'"),
         })
     }
 }
 
+/// Convert a file:// URL to a native path, e.g. "C:\\src\\foo" for "file:///C:/src/foo" on Windows.
+fn native_path(url: &Url) -> Result<PathBuf> {
+    let path = url.to_file_path().map_err(|_| anyhow!("Invalid path URL '{url}'"))?;
+    Ok(simplify_verbatim(path))
+}
+
+/// Paths recorded by cargo may have the `\\?\` prefix which lifts the MAX_PATH limit. Some tools
+/// don't handle these, so drop the prefix when the path is short enough to work without it.
+#[cfg(windows)]
+fn simplify_verbatim(path: PathBuf) -> PathBuf {
+    const MAX_PATH: usize = 260;
+    let simple = path.to_str().and_then(|s| match s.strip_prefix(r"\\?\") {
+        Some(rest) if rest.starts_with(r"UNC\") => Some(format!(r"\\{}", &rest[4..])),
+        Some(rest) => Some(rest.to_owned()),
+        None => None,
+    });
+    match simple {
+        Some(simple) if simple.len() < MAX_PATH => simple.into(),
+        _ => path,
+    }
+}
+
+#[cfg(not(windows))]
+fn simplify_verbatim(path: PathBuf) -> PathBuf {
+    path
+}
+
+/// Write a file by writing a temporary one next to it and renaming that into place, so that it's
+/// never left half written
+fn write_atomically(path: &Path, data: &str) -> Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    fs::write(&tmp, data).with_context(|| format!("Failed to write '{}'", tmp.display()))?;
+    fs::rename(&tmp, path).with_context(|| format!("Failed to write '{}'", path.display()))
+}
+
+/// Change cargo's records of installed packages in .crates2.json and .crates.toml. Each change is
+/// a package ID and the ID to record it as instead, or None to remove it. `edit` is called with
+/// the old ID and the details of each package which is kept, and may change them; the programs
+/// it leaves in "bins" are recorded in .crates.toml too.
+pub fn rewrite_installs(
+    changes: &[(&str, Option<&str>)],
+    edit: impl Fn(&str, &mut serde_json::Value),
+) -> Result<()> {
+    let path = Crates2::path()?;
+    let text = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read '{}'", path.display()))?;
+    let mut crates2: serde_json::Value = serde_json::from_str(&text)
+        .with_context(|| format!("Failed to parse '{}'", path.display()))?;
+    let installs = crates2["installs"]
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("Invalid '{}'", path.display()))?;
+    // the new lines of .crates.toml, by the start of the old ones
+    let mut lines = Vec::new();
+    for &(old, new) in changes {
+        // TOML basic strings are escaped the same way as JSON ones
+        let key = format!("{} = ", serde_json::to_string(old)?);
+        let details = installs.remove(old);
+        let Some(new) = new else {
+            lines.push((key, None));
+            continue;
+        };
+        let mut details =
+            details.ok_or_else(|| anyhow!("'{old}' isn't in '{}'", path.display()))?;
+        edit(old, &mut details);
+        let line = format!("{} = {}", serde_json::to_string(new)?, details["bins"]);
+        installs.insert(new.to_owned(), details);
+        lines.push((key, Some(line)));
+    }
+    write_atomically(&path, &serde_json::to_string(&crates2)?)?;
+
+    // the older .crates.toml has a line like `"<package id>" = ["<bin>", ...]`
+    let path = cargo_home()?.join(".crates.toml");
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e).context(format!("Failed to read '{}'", path.display())),
+    };
+    let text: Vec<&str> = text
+        .lines()
+        .filter_map(|l| match lines.iter().find(|(key, _)| l.starts_with(key.as_str())) {
+            Some((_, new)) => new.as_deref(),
+            None => Some(l),
+        })
+        .collect();
+    write_atomically(&path, &(text.join("\n") + "\n"))
+}
+
+/// Path of an installed program. Cargo records the names of executables without the ".exe" suffix
+/// on Windows in some versions, so add it if needed.
+pub fn bin_path(bin_dir: &Path, bin: &str) -> PathBuf {
+    if bin.ends_with(env::consts::EXE_SUFFIX) {
+        bin_dir.join(bin)
+    } else {
+        bin_dir.join(format!("{bin}{}", env::consts::EXE_SUFFIX))
+    }
+}
+
+/// A program's name without the ".exe" suffix on Windows
+pub fn exe_stem(name: &str) -> &str {
+    name.strip_suffix(env::consts::EXE_SUFFIX).unwrap_or(name)
+}
+
+/// Index URL which cargo records for packages installed from crates.io
+pub const CRATES_IO_INDEX: &str = "https://github.com/rust-lang/crates.io-index";
+
 impl PackageSource {
+    pub fn is_crates_io(&self) -> bool {
+        matches!(self, Self::Registry(url) if url == CRATES_IO_INDEX)
+    }
+
+    /// Name of the kind of source, as used in package IDs
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Registry(_) => "registry",
+            Self::Git { .. } => "git",
+            Self::Path(_) => "path",
+        }
+    }
+
     pub fn add_cargo_args(&self, args: &mut Vec<String>) {
         match self {
             Self::Registry(url) => args.push_str("--index").push_str(url),
-            Self::Git { url, branch, tag } => {
+            Self::Git { url, branch, tag, .. } => {
                 args.push_str("--git").push_str(url);
                 if let Some(b) = branch {
                     args.push_str("--branch").push_str(b);
@@ -111,11 +314,32 @@ impl PackageSource {
                 }
                 args
             }
-            Self::Path(path) => args.push_str("--path").push_str(path),
+            Self::Path(path) => args.push_str("--path").push_str(path.to_string_lossy()),
         };
     }
 }
 
+impl fmt::Display for PackageSource {
+    /// Short human-readable description, e.g. "crates.io" or "git https://...?branch=main"
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            _ if self.is_crates_io() => f.write_str("crates.io"),
+            Self::Registry(url) => write!(f, "registry {url}"),
+            Self::Git { url, branch, tag, .. } => {
+                write!(f, "git {url}")?;
+                if let Some(b) = branch {
+                    write!(f, "?branch={b}")?;
+                }
+                if let Some(t) = tag {
+                    write!(f, "?tag={t}")?;
+                }
+                Ok(())
+            }
+            Self::Path(path) => write!(f, "path {}", path.display()),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Package {
     pub name: String,
@@ -142,7 +366,55 @@ impl FromStr for Package {
     }
 }
 
-#[derive(Debug, Deserialize)]
+impl Package {
+    /// Try to find a local copy of this package's source code: cargo's extracted registry cache,
+    /// its git checkouts, or the original directory for path installs.
+    pub fn source_dir(&self, cargo_home: &Path) -> Option<PathBuf> {
+        let home = glob::Pattern::escape(&cargo_home.to_string_lossy());
+        match &self.source {
+            PackageSource::Registry(_) => {
+                let pattern = format!("{home}/registry/src/*/{}-{}", self.name, self.version);
+                glob::glob(&pattern).ok()?.flatten().next()
+            }
+            PackageSource::Git { url, .. } => {
+                // checkouts are named after the last component of the repo URL plus a hash, and
+                // the package could be anywhere in the repo if it's a workspace
+                let repo = url.trim_end_matches('/').rsplit('/').next()?.trim_end_matches(".git");
+                let pattern = format!("{home}/git/checkouts/{repo}-*/*/**/Cargo.toml");
+                glob::glob(&pattern).ok()?.flatten().find_map(|manifest| {
+                    let text = fs::read_to_string(&manifest).ok()?;
+                    (manifest_field(&text, "name")? == self.name
+                        && manifest_field(&text, "version")? == self.version)
+                        .then(|| manifest.parent().unwrap().to_owned())
+                })
+            }
+            PackageSource::Path(path) => Some(crate::manifest::install_dir(path, &self.name)),
+        }
+    }
+}
+
+/// Read a string field from the `[package]` table of a Cargo.toml manifest
+pub fn manifest_field(text: &str, key: &str) -> Option<String> {
+    let mut in_package = false;
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_package = line == "[package]";
+        } else if in_package {
+            if let Some((k, v)) = line.split_once('=') {
+                if k.trim() == key {
+                    let v = v.trim();
+                    return v.strip_prefix('"')?.strip_suffix('"').map(Into::into);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Per-package install details. Not every field is needed to rebuild the `cargo install` command.
+/// Missing fields are left empty and unknown ones ignored, in case cargo changes what it records.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
 pub struct PackageDetails {
     pub version_req: Option<String>,
     pub bins: Vec<String>,
@@ -166,6 +438,9 @@ impl PackageDetails {
             args.push_str("--no-default-features");
         }
         //args.push_str("--profile").push_str(&self.profile); // --profile is unstable, omit it
-        args.push_str("--target").push_str(&self.target);
+        // empty for packages only known from `cargo install --list`
+        if !self.target.is_empty() {
+            args.push_str("--target").push_str(&self.target);
+        }
     }
 }
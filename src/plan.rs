@@ -0,0 +1,159 @@
+//! Plan files, which record the exact commands an update run would execute so that they can be
+//! reviewed before `apply` runs them.
+
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::{ensure, Context, Result};
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::http;
+use crate::package_data::*;
+use crate::util;
+use crate::version_source::{self, SparseIndex};
+use crate::Job;
+
+/// Write the commands an update would run to a file instead of running them.
+///
+/// The usual options select the packages and how they're built, e.g.
+/// `cargo update-installed --locked -i 'cargo-*' plan update.json`. Nothing is installed until the
+/// plan is given to `apply`, which runs exactly the commands in it.
+#[derive(Debug, Parser)]
+pub struct PlanArgs {
+    /// Where to write the plan, or "-" for stdout.
+    #[arg(value_name = "FILE")]
+    pub file: PathBuf,
+}
+
+/// Run the commands in a plan file written by `plan`.
+///
+/// The commands are run as they are, without retrying failed builds with --locked or --force. A
+/// plan is refused if any of its packages have changed version since it was written.
+#[derive(Debug, Parser)]
+pub struct ApplyArgs {
+    #[arg(value_name = "FILE")]
+    pub file: PathBuf,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Plan {
+    pub created: String,
+    pub packages: Vec<PlannedPackage>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PlannedPackage {
+    pub name: String,
+    /// The version installed when the plan was written
+    pub installed: String,
+    /// The version the update is expected to install, if known: the pinned version, or the latest
+    /// one for crates.io packages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    /// The full command line, starting with the program
+    pub command: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub env: Vec<(String, String)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_dir: Option<PathBuf>,
+}
+
+/// Write a plan for running the jobs
+pub fn write(
+    path: &Path,
+    jobs: &[Job],
+    cargo_exe: &OsStr,
+    crates2: &Crates2,
+    config: &Config,
+) -> Result<()> {
+    let installed: Vec<Package> =
+        crates2.installs.keys().filter_map(|id| id.parse().ok()).collect();
+    let index = SparseIndex::default();
+    let targets = http::map_concurrent(jobs, |job| {
+        // already known for updates, including those held back by --cooldown
+        if let Some((target, _)) = &job.update {
+            return Some(target.to_string());
+        }
+        let pkg = installed.iter().find(|p| p.name == job.name)?;
+        version_source::update_target(&[&index], pkg, config.pin(&pkg.name))
+            .map(|v| v.map(|v| v.to_string()))
+            .map_err(|e| {
+                warnmsg!("Warning: couldn't find the latest version of {}: {e:#}", pkg.name)
+            })
+            .ok()
+            .flatten()
+    });
+
+    let packages = jobs
+        .iter()
+        .zip(targets)
+        .map(|(job, target)| {
+            let mut command = vec![job.cargo_exe(cargo_exe).to_string_lossy().into_owned()];
+            command.extend(job.args.iter().cloned());
+            PlannedPackage {
+                name: job.name.clone(),
+                installed: job.version.clone(),
+                target,
+                command,
+                env: job.env.iter().map(|(k, v)| (k.clone(), v.to_string_lossy().into())).collect(),
+                target_dir: job.target_dir.clone(),
+            }
+        })
+        .collect();
+    let plan = Plan { created: util::format_timestamp(SystemTime::now()), packages };
+    let data = serde_json::to_string_pretty(&plan)? + "\n";
+    if path == Path::new("-") {
+        print!("{data}");
+    } else {
+        fs::write(path, data).with_context(|| format!("Failed to write '{}'", path.display()))?;
+        msg!("Wrote a plan for {} packages to '{}'", jobs.len(), path.display());
+    }
+    Ok(())
+}
+
+/// Read a plan and turn it back into jobs, checking that it still matches what's installed
+pub fn load(path: &Path, crates2: &Crates2) -> Result<Vec<Job>> {
+    let data =
+        fs::read_to_string(path).with_context(|| format!("Failed to read '{}'", path.display()))?;
+    let plan: Plan = serde_json::from_str(&data)
+        .with_context(|| format!("Failed to parse '{}'", path.display()))?;
+    dbgmsg!("Applying the plan from {}", plan.created);
+
+    let installed: Vec<Package> =
+        crates2.installs.keys().filter_map(|id| id.parse().ok()).collect();
+    let stale: Vec<String> = plan
+        .packages
+        .iter()
+        .filter(|p| !installed.iter().any(|i| i.name == p.name && i.version == p.installed))
+        .map(|p| p.name.clone())
+        .collect();
+    ensure!(
+        stale.is_empty(),
+        "The plan is out of date, these packages have changed since it was written: {}",
+        stale.join(", ")
+    );
+
+    let mut jobs = Vec::new();
+    for package in plan.packages {
+        let mut command = package.command.into_iter();
+        let program = command.next().with_context(|| format!("No command for {}", package.name))?;
+        jobs.push(Job {
+            name: package.name,
+            version: package.installed,
+            args: command.collect(),
+            target_dir: package.target_dir,
+            env: package.env.into_iter().map(|(k, v)| (k, v.into())).collect(),
+            estimate: None,
+            cargo: Some(program.into()),
+            update: None,
+            retry_locked: false,
+            missing_bins: Vec::new(),
+            batch: None,
+        });
+    }
+    Ok(jobs)
+}
@@ -0,0 +1,123 @@
+//! Matching `cfg(...)` expressions like cargo's against the platform we're running on, for the
+//! `[target.'cfg(...)']` tables of the config file.
+//!
+//! The supported predicates are `unix`, `windows`, `target_os`, `target_family`, and
+//! `target_arch`, combined with `all(...)`, `any(...)`, and `not(...)`. As in cargo, any other
+//! name or key is false.
+
+use std::env::consts;
+
+use anyhow::{bail, ensure, Result};
+
+#[derive(Debug)]
+enum Expr {
+    Name(String),
+    KeyValue(String, String),
+    All(Vec<Expr>),
+    Any(Vec<Expr>),
+    Not(Box<Expr>),
+}
+
+struct Parser<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_space(&mut self) {
+        self.rest = self.rest.trim_start();
+    }
+
+    fn eat(&mut self, token: char) -> bool {
+        self.skip_space();
+        match self.rest.strip_prefix(token) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn expect(&mut self, token: char) -> Result<()> {
+        ensure!(self.eat(token), "expected `{token}` at `{}`", self.rest);
+        Ok(())
+    }
+
+    fn ident(&mut self) -> Result<&'a str> {
+        self.skip_space();
+        let end =
+            self.rest.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(self.rest.len());
+        ensure!(end > 0, "expected a name at `{}`", self.rest);
+        let (ident, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        Ok(ident)
+    }
+
+    fn string(&mut self) -> Result<String> {
+        self.expect('"')?;
+        let Some(end) = self.rest.find('"') else { bail!("unterminated string") };
+        let (s, rest) = self.rest.split_at(end);
+        self.rest = &rest[1..];
+        Ok(s.to_owned())
+    }
+
+    fn list(&mut self) -> Result<Vec<Expr>> {
+        self.expect('(')?;
+        let mut exprs = Vec::new();
+        while !self.eat(')') {
+            exprs.push(self.expr()?);
+            if !self.eat(',') {
+                self.expect(')')?;
+                break;
+            }
+        }
+        Ok(exprs)
+    }
+
+    fn expr(&mut self) -> Result<Expr> {
+        let ident = self.ident()?;
+        Ok(match ident {
+            "all" => Expr::All(self.list()?),
+            "any" => Expr::Any(self.list()?),
+            "not" => {
+                let mut exprs = self.list()?;
+                ensure!(exprs.len() == 1, "not() takes one expression");
+                Expr::Not(Box::new(exprs.remove(0)))
+            }
+            _ if self.eat('=') => Expr::KeyValue(ident.to_owned(), self.string()?),
+            _ => Expr::Name(ident.to_owned()),
+        })
+    }
+}
+
+impl Expr {
+    fn eval(&self) -> bool {
+        match self {
+            Self::Name(name) => match name.as_str() {
+                "unix" => cfg!(unix),
+                "windows" => cfg!(windows),
+                _ => false,
+            },
+            Self::KeyValue(key, value) => match key.as_str() {
+                "target_os" => value == consts::OS,
+                "target_family" => value == consts::FAMILY,
+                "target_arch" => value == consts::ARCH,
+                _ => false,
+            },
+            Self::All(exprs) => exprs.iter().all(Self::eval),
+            Self::Any(exprs) => exprs.iter().any(Self::eval),
+            Self::Not(expr) => !expr.eval(),
+        }
+    }
+}
+
+/// Check whether a `cfg(...)` expression is true for this machine
+pub fn matches(cfg: &str) -> Result<bool> {
+    let mut parser = Parser { rest: cfg };
+    ensure!(parser.ident()? == "cfg", "expected `cfg(...)`");
+    let mut exprs = parser.list()?;
+    parser.skip_space();
+    ensure!(parser.rest.is_empty(), "unexpected `{}` after `cfg(...)`", parser.rest);
+    ensure!(exprs.len() == 1, "cfg() takes one expression");
+    Ok(exprs.remove(0).eval())
+}
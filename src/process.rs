@@ -0,0 +1,227 @@
+//! Stopping `cargo install` together with all of the compilers it started.
+//!
+//! On Unix, cargo runs in its own process group, and termination signals we receive are forwarded
+//! to the whole group. On Windows, we put ourself in a job object which kills every process in it
+//! once the last handle to it is closed, i.e. when we exit for any reason.
+
+use std::io;
+use std::process::{Child, Command, ExitStatus};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Whether a signal was forwarded to a running cargo, so the run should stop
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::os::unix::process::CommandExt;
+    use std::sync::atomic::AtomicI32;
+
+    use super::*;
+
+    /// Process groups of the running cargo processes, with 0 marking unused slots. This is a fixed
+    /// array so that the signal handler can read it without locking.
+    static GROUPS: [AtomicI32; 64] = [const { AtomicI32::new(0) }; 64];
+
+    extern "C" fn handle_signal(sig: libc::c_int) {
+        let mut forwarded = false;
+        // SAFETY: only async-signal-safe functions are called here
+        unsafe {
+            for group in &GROUPS {
+                let group = group.load(Ordering::SeqCst);
+                if group > 0 {
+                    libc::killpg(group, sig);
+                    forwarded = true;
+                }
+            }
+            if forwarded {
+                INTERRUPTED.store(true, Ordering::SeqCst);
+            } else {
+                // nothing to clean up, so die from the signal as usual
+                libc::signal(sig, libc::SIG_DFL);
+                libc::raise(sig);
+            }
+        }
+    }
+
+    pub fn init() {
+        for sig in [libc::SIGINT, libc::SIGTERM, libc::SIGHUP] {
+            // SAFETY: handle_signal is a plain handler (no SA_SIGINFO) which is async-signal-safe
+            unsafe {
+                let mut action: libc::sigaction = std::mem::zeroed();
+                action.sa_sigaction = handle_signal as extern "C" fn(libc::c_int) as usize;
+                action.sa_flags = libc::SA_RESTART;
+                libc::sigemptyset(&mut action.sa_mask);
+                libc::sigaction(sig, &action, std::ptr::null_mut());
+            }
+        }
+    }
+
+    pub fn configure(cmd: &mut Command) {
+        cmd.process_group(0);
+    }
+
+    pub fn started(child: &Child) {
+        let pid = child.id() as i32;
+        // if all the slots are somehow full, the child just won't get forwarded signals
+        let _ = GROUPS
+            .iter()
+            .find(|slot| slot.compare_exchange(0, pid, Ordering::SeqCst, Ordering::SeqCst).is_ok());
+    }
+
+    pub fn finished(child: &Child) {
+        let pid = child.id() as i32;
+        let _ = GROUPS
+            .iter()
+            .find(|slot| slot.compare_exchange(pid, 0, Ordering::SeqCst, Ordering::SeqCst).is_ok());
+    }
+
+    pub fn kill_tree(child: &mut Child) -> io::Result<()> {
+        // SAFETY: killpg has no memory safety requirements
+        if unsafe { libc::killpg(child.id() as i32, libc::SIGKILL) } == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::ffi::c_void;
+    use std::ptr;
+
+    use super::*;
+
+    type Handle = *mut c_void;
+
+    #[repr(C)]
+    struct BasicLimitInformation {
+        per_process_user_time_limit: i64,
+        per_job_user_time_limit: i64,
+        limit_flags: u32,
+        minimum_working_set_size: usize,
+        maximum_working_set_size: usize,
+        active_process_limit: u32,
+        affinity: usize,
+        priority_class: u32,
+        scheduling_class: u32,
+    }
+
+    #[repr(C)]
+    struct ExtendedLimitInformation {
+        basic: BasicLimitInformation,
+        io_info: [u64; 6],
+        process_memory_limit: usize,
+        job_memory_limit: usize,
+        peak_process_memory_used: usize,
+        peak_job_memory_used: usize,
+    }
+
+    const JOB_OBJECT_EXTENDED_LIMIT_INFORMATION: i32 = 9;
+    const JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE: u32 = 0x2000;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn CreateJobObjectW(attributes: *mut c_void, name: *const u16) -> Handle;
+        fn SetInformationJobObject(job: Handle, class: i32, info: *mut c_void, len: u32) -> i32;
+        fn AssignProcessToJobObject(job: Handle, process: Handle) -> i32;
+        fn GetCurrentProcess() -> Handle;
+    }
+
+    pub fn init() {
+        // SAFETY: plain Win32 calls, with a correctly sized and zero-initialized info struct
+        unsafe {
+            let job = CreateJobObjectW(ptr::null_mut(), ptr::null());
+            if job.is_null() {
+                return;
+            }
+            let mut info: ExtendedLimitInformation = std::mem::zeroed();
+            info.basic.limit_flags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+            let ok = SetInformationJobObject(
+                job,
+                JOB_OBJECT_EXTENDED_LIMIT_INFORMATION,
+                &mut info as *mut _ as *mut c_void,
+                std::mem::size_of::<ExtendedLimitInformation>() as u32,
+            );
+            // child processes join the job automatically. The handle is deliberately never
+            // closed, Windows closes it when we exit and that kills everything left in the job.
+            if ok != 0 && AssignProcessToJobObject(job, GetCurrentProcess()) == 0 {
+                dbgmsg!("Failed to assign ourself to a job object: {}", io::Error::last_os_error());
+            }
+        }
+    }
+
+    pub fn configure(_cmd: &mut Command) {}
+
+    pub fn started(_child: &Child) {}
+
+    pub fn finished(_child: &Child) {}
+
+    pub fn kill_tree(child: &mut Child) -> io::Result<()> {
+        child.kill()
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod imp {
+    use super::*;
+
+    pub fn init() {}
+    pub fn configure(_cmd: &mut Command) {}
+    pub fn started(_child: &Child) {}
+    pub fn finished(_child: &Child) {}
+    pub fn kill_tree(child: &mut Child) -> io::Result<()> {
+        child.kill()
+    }
+}
+
+/// Set up signal handling or the job object, once at startup
+pub use imp::init;
+
+/// Spawn a command so that its whole process tree can be killed, and it receives forwarded
+/// interrupts until it's reaped with [`wait`], [`wait_unless`], or [`kill_tree`]
+pub fn spawn(cmd: &mut Command) -> io::Result<Child> {
+    imp::configure(cmd);
+    let child = cmd.spawn()?;
+    imp::started(&child);
+    Ok(child)
+}
+
+/// Wait for a child started with [`spawn`]
+pub fn wait(child: &mut Child) -> io::Result<ExitStatus> {
+    let status = child.wait();
+    imp::finished(child);
+    status
+}
+
+/// Wait for a child started with [`spawn`] like [`wait`], but kill it and all of its descendents
+/// once `cancelled` returns true, which is checked a few times a second
+pub fn wait_unless(child: &mut Child, cancelled: impl Fn() -> bool) -> io::Result<ExitStatus> {
+    while child.try_wait()?.is_none() {
+        if cancelled() {
+            // it might have exited in the meantime, which wait will still find out
+            let _ = kill_tree(child);
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    wait(child)
+}
+
+/// Run a command to completion, as with [`spawn`] and [`wait`]
+pub fn status(cmd: &mut Command) -> io::Result<ExitStatus> {
+    wait(&mut spawn(cmd)?)
+}
+
+/// Kill a child started with [`spawn`] and all of its descendents, then reap it
+pub fn kill_tree(child: &mut Child) -> io::Result<()> {
+    let res = imp::kill_tree(child);
+    let _ = wait(child);
+    res
+}
@@ -0,0 +1,77 @@
+//! Noticing when a crate seems to have changed hands before updating it.
+//!
+//! The crates.io account which published the version that would be installed is compared with the
+//! one which published the installed version, and the repository URL in the installed version's
+//! Cargo.toml with the one crates.io has now. Either changing is normal now and then, but it's also
+//! what a hijacked crate looks like, so unattended updates shouldn't install it without a look.
+
+use std::fs;
+
+use anyhow::Result;
+
+use crate::http;
+use crate::index;
+use crate::package_data::*;
+use crate::semver::Version;
+
+/// The repository URL from the installed version's Cargo.toml, if the source is still around
+fn installed_repository(pkg: &Package) -> Option<String> {
+    let dir = pkg.source_dir(&cargo_home().ok()?)?;
+    manifest_field(&fs::read_to_string(dir.join("Cargo.toml")).ok()?, "repository")
+}
+
+/// Repository URLs are often written slightly differently, e.g. with ".git" or a trailing slash
+fn same_repository(a: &str, b: &str) -> bool {
+    let normalize = |url: &str| {
+        url.trim_end_matches('/')
+            .trim_end_matches(".git")
+            .to_ascii_lowercase()
+            .replace("http://", "https://")
+    };
+    normalize(a) == normalize(b)
+}
+
+/// Describe the changes of publisher or repository between the installed version of a crates.io
+/// package and the one which an update would install
+pub fn changes(pkg: &Package, pin: Option<&str>) -> Result<Vec<String>> {
+    let current: Version = pkg.version.parse()?;
+    let target = match pin {
+        Some(pin) => pin.to_owned(),
+        None => match index::latest_version(&pkg.name, &current)? {
+            Some(latest) if latest > current => latest.to_string(),
+            _ => return Ok(Vec::new()),
+        },
+    };
+    if target == pkg.version {
+        return Ok(Vec::new());
+    }
+
+    let data = http::get_json(&format!("https://crates.io/api/v1/crates/{}", pkg.name))?;
+    let publisher = |version: &str| {
+        data["versions"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|v| v["num"].as_str() == Some(version))
+            .and_then(|v| v["published_by"]["login"].as_str())
+    };
+
+    let mut changes = Vec::new();
+    // versions from before crates.io recorded publishers don't have one
+    if let (Some(old), Some(new)) = (publisher(&pkg.version), publisher(&target)) {
+        if old != new {
+            changes.push(format!(
+                "{target} was published by {new}, but {} was published by {old}",
+                pkg.version
+            ));
+        }
+    }
+    if let (Some(old), Some(new)) =
+        (installed_repository(pkg), data["crate"]["repository"].as_str())
+    {
+        if !same_repository(&old, new) {
+            changes.push(format!("the repository changed from {old} to {new}"));
+        }
+    }
+    Ok(changes)
+}
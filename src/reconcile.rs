@@ -0,0 +1,194 @@
+//! Cross-checking `cargo install --list` with .crates2.json.
+//!
+//! `cargo install --list` prints what's recorded in .crates.toml, cargo's older metadata file,
+//! which it keeps alongside .crates2.json. The two normally agree, but old versions of cargo only
+//! wrote .crates.toml, and either file can go stale after manual changes or an interrupted install.
+//! Packages which are only in .crates.toml are never updated since this tool reads .crates2.json.
+
+use std::ffi::OsStr;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{ensure, Context, Result};
+use clap::Parser;
+use url::Url;
+
+use crate::adopt::untracked_bins;
+use crate::package_data::*;
+
+/// Compare `cargo install --list` with .crates2.json and report the differences.
+///
+/// Packages which only `cargo install --list` knows about aren't updated, unless update is run
+/// with --reconcile. Programs which neither knows about were probably installed with
+/// `cargo install --no-track`, see `adopt`.
+#[derive(Debug, Parser)]
+pub struct ReconcileArgs {}
+
+/// A package from `cargo install --list`
+#[derive(Debug)]
+pub struct ListedPackage {
+    pub name: String,
+    pub version: String,
+    /// The source as cargo prints it: nothing for crates.io, a URL with the revision after '#'
+    /// for git, a path, or a registry's name
+    pub source: Option<String>,
+    pub bins: Vec<String>,
+}
+
+impl ListedPackage {
+    /// Build the package ID which .crates2.json would use, if the source can be worked out
+    pub fn package_id(&self) -> Option<String> {
+        let source = match self.source.as_deref() {
+            None => format!("registry+{CRATES_IO_INDEX}"),
+            Some(path) if Path::new(path).is_absolute() => {
+                format!("path+{}", Url::from_file_path(path).ok()?)
+            }
+            Some(url) if url.contains("://") && url.contains('#') => format!("git+{url}"),
+            // other registries are shown by name, which can't be turned back into a URL here
+            Some(_) => return None,
+        };
+        Some(format!("{} {} ({source})", self.name, self.version))
+    }
+}
+
+/// Parse the output of `cargo install --list`, which looks like
+/// ```text
+/// bat v0.18.0:
+///     bat
+/// bcut v1.0.2 (https://github.com/aswild/bcut?branch=master#046894ca):
+///     bcut
+/// ```
+fn parse_list(text: &str) -> Result<Vec<ListedPackage>> {
+    let mut packages: Vec<ListedPackage> = Vec::new();
+    for line in text.lines().filter(|l| !l.trim().is_empty()) {
+        if line.starts_with(char::is_whitespace) {
+            let pkg = packages.last_mut().with_context(|| format!("Unexpected line '{line}'"))?;
+            pkg.bins.push(line.trim().to_owned());
+            continue;
+        }
+        let id = line.strip_suffix(':').with_context(|| format!("Unexpected line '{line}'"))?;
+        let (id, source) = match id.split_once(" (") {
+            Some((id, source)) => (id, source.strip_suffix(')').map(String::from)),
+            None => (id, None),
+        };
+        let (name, version) =
+            id.split_once(" v").with_context(|| format!("Unexpected line '{line}'"))?;
+        packages.push(ListedPackage {
+            name: name.to_owned(),
+            version: version.to_owned(),
+            source,
+            bins: Vec::new(),
+        });
+    }
+    Ok(packages)
+}
+
+/// Run `cargo install --list` and parse its output
+pub fn list_installed(cargo_exe: &OsStr) -> Result<Vec<ListedPackage>> {
+    let output = Command::new(cargo_exe)
+        .args(["install", "--list"])
+        .output()
+        .context("Failed to run `cargo install --list`")?;
+    ensure!(
+        output.status.success(),
+        "`cargo install --list` failed: {}",
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+    parse_list(&String::from_utf8_lossy(&output.stdout))
+        .context("Failed to parse the output of `cargo install --list`")
+}
+
+/// Find the packages which `cargo install --list` has but .crates2.json doesn't, as entries which
+/// can be added to it for an update run. Their features and other install options are unknown.
+pub fn extra_packages(
+    cargo_exe: &OsStr,
+    crates2: &Crates2,
+) -> Result<Vec<(String, PackageDetails)>> {
+    let installed: Vec<Package> =
+        crates2.installs.keys().filter_map(|id| id.parse().ok()).collect();
+    let mut extra = Vec::new();
+    for listed in list_installed(cargo_exe)? {
+        if installed.iter().any(|pkg| pkg.name == listed.name) {
+            continue;
+        }
+        let Some(pkg_id) = listed.package_id() else {
+            warnmsg!(
+                "Warning: can't update {}, its source '{}' isn't known",
+                listed.name,
+                listed.source.as_deref().unwrap_or_default()
+            );
+            continue;
+        };
+        dbgmsg!("Adding {pkg_id} from `cargo install --list`");
+        extra.push((pkg_id, PackageDetails { bins: listed.bins, ..Default::default() }));
+    }
+    Ok(extra)
+}
+
+pub fn run(_args: &ReconcileArgs, crates2: &Crates2, cargo_exe: &OsStr) -> Result<()> {
+    let listed = list_installed(cargo_exe)?;
+    let mut tracked = Vec::new();
+    for (pkg_id, details) in &crates2.installs {
+        let pkg = pkg_id
+            .parse::<Package>()
+            .with_context(|| format!("Failed to parse package id '{pkg_id}'"))?;
+        tracked.push((pkg, details));
+    }
+
+    let mut problems = Vec::new();
+    for listed in &listed {
+        let Some((pkg, details)) = tracked.iter().find(|(pkg, _)| pkg.name == listed.name) else {
+            problems.push(format!(
+                "{} {} is in `cargo install --list` but not .crates2.json, so it isn't updated \
+                 (use --reconcile to include it)",
+                listed.name, listed.version
+            ));
+            continue;
+        };
+        if pkg.version != listed.version {
+            problems.push(format!(
+                "{} is version {} in `cargo install --list` but {} in .crates2.json",
+                pkg.name, listed.version, pkg.version
+            ));
+        }
+        let mut bins: Vec<&str> = details.bins.iter().map(|b| exe_stem(b)).collect();
+        let mut listed_bins: Vec<&str> = listed.bins.iter().map(|b| exe_stem(b)).collect();
+        bins.sort();
+        listed_bins.sort();
+        if bins != listed_bins {
+            problems.push(format!(
+                "{} has programs {} in `cargo install --list` but {} in .crates2.json",
+                pkg.name,
+                listed_bins.join(", "),
+                bins.join(", ")
+            ));
+        }
+    }
+    for (pkg, _) in &tracked {
+        if !listed.iter().any(|l| l.name == pkg.name) {
+            problems.push(format!(
+                "{} {} is in .crates2.json but not `cargo install --list`",
+                pkg.name, pkg.version
+            ));
+        }
+    }
+
+    let bin_dir = cargo_home()?.join("bin");
+    for bin in untracked_bins(&bin_dir, crates2)? {
+        let stem = exe_stem(&bin);
+        if !listed.iter().any(|l| l.bins.iter().any(|b| exe_stem(b) == stem)) {
+            problems.push(format!(
+                "{bin} isn't tracked by cargo at all, it may have been installed with \
+                 `cargo install --no-track` (see `adopt`)"
+            ));
+        }
+    }
+
+    if problems.is_empty() {
+        msg!("`cargo install --list` and .crates2.json agree on {} packages", listed.len());
+    }
+    for problem in &problems {
+        println!("{problem}");
+    }
+    Ok(())
+}
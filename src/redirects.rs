@@ -0,0 +1,127 @@
+//! Following git repos which have moved, like a renamed GitHub repo. The old URL keeps working
+//! through a permanent redirect for a while, so an update run checks the http(s) git sources it's
+//! about to update, warns about any that have moved, and can record the new URL so that later runs
+//! and checks for new commits go there directly.
+//!
+//! Recorded URLs are kept in the state directory, apart from the `[git-rewrite]` rules of the
+//! config file, which are applied first.
+
+use std::collections::BTreeMap;
+use std::io;
+
+use anyhow::{Context, Result};
+use is_terminal::IsTerminal;
+use serde::{Deserialize, Serialize};
+
+use crate::http;
+use crate::package_data::*;
+use crate::util;
+
+const FILE_NAME: &str = "git-redirects.json";
+
+/// What git fetches first over http(s), which is redirected along with the rest of the repo
+const PROBE: &str = "/info/refs?service=git-upload-pack";
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Redirects {
+    /// The new URL of each repo which has moved, by its old one
+    moved: BTreeMap<String, String>,
+}
+
+impl Redirects {
+    pub fn load() -> Result<Self> {
+        util::load_state(FILE_NAME)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        util::save_state(FILE_NAME, self)
+    }
+
+    /// The new URL of a repo which has moved, following it through any further moves
+    fn resolve(&self, url: &str) -> Option<String> {
+        let mut new = self.moved.get(url)?;
+        // bounded, in case a hand-edited file has a loop
+        for _ in 0..5 {
+            match self.moved.get(new) {
+                Some(next) => new = next,
+                None => break,
+            }
+        }
+        Some(new.clone())
+    }
+
+    /// Point a git package at where its repo was recorded to have moved. Returns whether it was
+    /// changed.
+    pub fn apply(&self, pkg: &mut Package) -> bool {
+        let PackageSource::Git { url, .. } = &mut pkg.source else {
+            return false;
+        };
+        let Some(new) = self.resolve(url) else {
+            return false;
+        };
+        dbgmsg!("{} was recorded to have moved from {url} to {new}", pkg.name);
+        *url = new;
+        true
+    }
+
+    /// Check whether these git repos redirect somewhere else, and record the new URLs if `record`
+    /// is true or the user agrees. Nothing is recorded when `dry_run` is true.
+    pub fn check(&mut self, urls: &[String], record: bool, dry_run: bool) -> Result<()> {
+        let mut urls: Vec<&str> = urls
+            .iter()
+            .map(String::as_str)
+            .filter(|url| url.starts_with("https://") || url.starts_with("http://"))
+            .filter(|url| self.resolve(url).is_none())
+            .collect();
+        urls.sort_unstable();
+        urls.dedup();
+        if urls.is_empty() {
+            return Ok(());
+        }
+
+        let s = if urls.len() == 1 { "" } else { "s" };
+        dbgmsg!("Checking {} git repo{s} for redirects", urls.len());
+        let found = http::map_concurrent(&urls, |url| {
+            let probe = format!("{}{PROBE}", url.trim_end_matches('/'));
+            http::permanent_redirect(&probe)
+                .map_err(|e| dbgmsg!("Couldn't check {url} for redirects: {e:#}"))
+                .ok()
+                .flatten()
+                .and_then(|new| Some(new.strip_suffix(PROBE)?.to_owned()))
+                .filter(|new| new != url)
+        });
+        let moved: Vec<(&str, String)> =
+            urls.into_iter().zip(found).filter_map(|(url, new)| Some((url, new?))).collect();
+        if moved.is_empty() {
+            return Ok(());
+        }
+        for (url, new) in &moved {
+            warnmsg!("Warning: the git repo {url} has moved to {new}");
+        }
+
+        if dry_run {
+            return Ok(());
+        }
+        let record = record || (io::stdin().is_terminal() && ask(moved.len())?);
+        if !record {
+            msg!(
+                "Packages are still updated from the old URLs, set new ones with `[git-rewrite]` \
+                 in the config file or record them with --yes"
+            );
+            return Ok(());
+        }
+        self.moved.extend(moved.into_iter().map(|(url, new)| (url.to_owned(), new)));
+        self.save()?;
+        msg!("Recorded the new URLs, so packages will be updated from them");
+        Ok(())
+    }
+}
+
+fn ask(count: usize) -> Result<bool> {
+    let these = if count == 1 { "it" } else { "them" };
+    eprint!("Update from the new URLs and remember {these} for next time? [y/N] ");
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).context("Failed to read the answer")?;
+    Ok(matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes"))
+}
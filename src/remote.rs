@@ -0,0 +1,194 @@
+//! Running the update on other machines over SSH, for `--host`.
+//!
+//! Each host needs cargo-update-installed installed itself. It's run there with the same update
+//! options, except for the ones about local files, and its `--report` is sent back over SSH's
+//! stdout while its messages go to stderr as usual. Hosts are updated one after another so that
+//! their output doesn't get mixed up.
+
+use std::process::{Command, Stdio};
+
+use anyhow::{bail, ensure, Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::diagnose::Reason;
+use crate::list::print_table;
+use crate::{Args, Failure};
+
+/// Options about local files or this machine, which aren't passed on to the hosts
+const LOCAL_OPTIONS: &[&str] =
+    &["--config", "--profile", "--report", "--metrics-file", "--log-dir", "--build-dir", "--host"];
+
+/// Quote an argument for a POSIX shell
+fn quote(arg: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "-_./:=@,+%".contains(c);
+    if !arg.is_empty() && arg.chars().all(safe) {
+        return arg.to_owned();
+    }
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+/// The shell script run on a host, which prints the JSON report of the update on stdout
+fn script(args: &[String]) -> String {
+    let args: Vec<String> = args.iter().map(|a| quote(a)).collect();
+    format!(
+        r#"PATH="${{CARGO_HOME:-$HOME/.cargo}}/bin:$PATH"
+command -v cargo-update-installed >/dev/null || exit 127
+report=$(mktemp) || exit 1
+cargo update-installed {} --report "$report" >&2
+status=$?
+cat "$report"
+rm -f "$report"
+exit $status"#,
+        args.join(" ")
+    )
+}
+
+/// How the update went on one host
+struct HostResult {
+    host: String,
+    /// The host's JSON report, if it got far enough to write one
+    report: Option<Value>,
+    error: Option<String>,
+}
+
+fn update_host(host: &str, script: &str) -> HostResult {
+    let mut result = HostResult { host: host.to_owned(), report: None, error: None };
+    let output = Command::new("ssh")
+        .arg("--")
+        .arg(host)
+        .arg(script)
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output();
+    let output = match output {
+        Ok(output) => output,
+        Err(e) => {
+            result.error = Some(format!("failed to run ssh: {e}"));
+            return result;
+        }
+    };
+    result.report = serde_json::from_slice(&output.stdout).ok();
+    result.error = match output.status.code() {
+        Some(0) => None,
+        Some(127) => Some("cargo-update-installed isn't installed there".into()),
+        // ssh's own exit code when it can't connect
+        Some(255) => Some("couldn't connect".into()),
+        Some(3) if result.report.is_some() => None,
+        Some(code) => Some(format!("exited with status {code}")),
+        None => Some("killed by a signal".into()),
+    };
+    result
+}
+
+pub fn run(args: &Args) -> Result<()> {
+    ensure!(!args.tui, "--tui can't be used with --host");
+    let mut forwarded = Vec::new();
+    let mut cli_args = args.to_cli_args().into_iter();
+    while let Some(arg) = cli_args.next() {
+        if arg == "--" {
+            forwarded.push(arg);
+            forwarded.extend(cli_args.by_ref());
+        } else if LOCAL_OPTIONS.contains(&arg.as_str()) {
+            cli_args.next();
+        } else if arg == "--rustc-wrapper" {
+            // a program found in PATH can be on the hosts too, but a path is to this machine's
+            let wrapper = cli_args.next().unwrap_or_default();
+            if wrapper.contains(std::path::is_separator) {
+                dbgmsg!("Not passing --rustc-wrapper {wrapper} to the hosts, it's a local path");
+            } else {
+                forwarded.extend([arg, wrapper]);
+            }
+        } else {
+            forwarded.push(arg);
+        }
+    }
+    // nobody can answer on the other end
+    if !forwarded.iter().any(|a| a == "--yes") {
+        forwarded.insert(0, "--yes".into());
+    }
+    let script = script(&forwarded);
+    dbgmsg!("Running on each host:\n{script}");
+
+    let mut results = Vec::new();
+    for host in &args.hosts {
+        msg!("Updating on {host}");
+        results.push(update_host(host, &script));
+    }
+
+    let count = |res: &HostResult, key: &str| {
+        res.report.as_ref().and_then(|r| r[key].as_u64()).map_or("-".into(), |n| n.to_string())
+    };
+    let mut failed_packages = Vec::new();
+    let rows: Vec<Vec<String>> = results
+        .iter()
+        .map(|res| {
+            let packages = res.report.as_ref().and_then(|r| r["packages"].as_array());
+            let failed: Vec<String> = packages
+                .into_iter()
+                .flatten()
+                .filter(|p| p["outcome"] == "failed")
+                .filter_map(|p| {
+                    let name = p["name"].as_str()?;
+                    Some(match Reason::deserialize(&p["reason"]) {
+                        Ok(reason) => format!("{name} ({reason})"),
+                        Err(_) => name.to_owned(),
+                    })
+                })
+                .collect();
+            failed_packages.extend(failed.iter().map(|name| format!("{name} on {}", res.host)));
+            let status = match &res.error {
+                Some(e) => format!("error: {e}"),
+                None if failed.is_empty() => "ok".into(),
+                None => format!("failed: {}", failed.join(", ")),
+            };
+            vec![res.host.clone(), count(res, "updated"), count(res, "failed"), status]
+        })
+        .collect();
+    eprintln!();
+    print_table(&["HOST", "UPDATED", "FAILED", "STATUS"], &rows, "");
+
+    if let Some(path) = &args.report {
+        let hosts: Vec<Value> = results
+            .iter()
+            .map(|res| {
+                json!({
+                    "host": res.host,
+                    "success": res.error.is_none()
+                        && res.report.as_ref().is_some_and(|r| r["success"] == true),
+                    "error": res.error,
+                    "report": res.report,
+                })
+            })
+            .collect();
+        let data = serde_json::to_string_pretty(&json!({ "hosts": hosts }))?;
+        std::fs::write(path, data + "\n")
+            .with_context(|| format!("Failed to write '{}'", path.display()))?;
+    }
+
+    let broken: Vec<&str> =
+        results.iter().filter(|r| r.error.is_some()).map(|r| r.host.as_str()).collect();
+    if !broken.is_empty() {
+        bail!("Failed to update on {}", broken.join(", "));
+    }
+    if !failed_packages.is_empty() {
+        return Err(Failure::PackagesFailed(failed_packages).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoting() {
+        assert_eq!(quote("--jobs"), "--jobs");
+        assert_eq!(quote("/home/me/.config/c.toml"), "/home/me/.config/c.toml");
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("cargo-*"), "'cargo-*'");
+        assert_eq!(quote("a b"), "'a b'");
+        assert_eq!(quote("$(rm -rf ~)"), "'$(rm -rf ~)'");
+        assert_eq!(quote("it's"), r"'it'\''s'");
+    }
+}
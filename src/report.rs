@@ -0,0 +1,184 @@
+//! Results of an update run, and the machine-readable `--report` and `--metrics-file` files.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
+use serde::{Serialize, Serializer};
+use serde_json::json;
+
+use crate::diagnose::{self, Reason};
+use crate::package_data::*;
+use crate::util::format_timestamp;
+use crate::Job;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Outcome {
+    /// `cargo install` succeeded
+    Updated,
+    /// `cargo install` failed
+    Failed,
+    /// Skipped by the user, because the run was aborted, or held back by --check-publisher
+    Skipped,
+    /// Not started because the --deadline had passed, left for the next run
+    Deferred,
+    /// Not run because of --dry-run
+    DryRun,
+    /// Not selected by the --include/--exclude filters
+    Excluded,
+}
+
+fn as_secs<S: Serializer>(d: &Duration, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_f64(d.as_secs_f64())
+}
+
+/// The result of updating one package
+#[derive(Debug, Serialize)]
+pub struct JobResult {
+    pub name: String,
+    pub version: String,
+    /// Version installed after the update, filled in by [`find_new_versions`]
+    pub new_version: Option<String>,
+    pub outcome: Outcome,
+    #[serde(rename = "duration_secs", serialize_with = "as_secs")]
+    pub duration: Duration,
+    /// The full command line, empty for excluded packages
+    pub command: Vec<String>,
+    /// A flag which was added after the first attempt failed, e.g. "--locked"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fallback: Option<&'static str>,
+    /// Why the package failed, if its output was captured and looks familiar
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<Reason>,
+    /// Captured output of `cargo install`, only saved for failed packages
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    /// The file the full output was written to, with --log-dir
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log: Option<PathBuf>,
+}
+
+impl JobResult {
+    pub fn new(
+        cargo_exe: &std::ffi::OsStr,
+        job: &Job,
+        outcome: Outcome,
+        duration: Duration,
+        output: Option<String>,
+    ) -> Self {
+        let mut command = vec![job.cargo_exe(cargo_exe).to_string_lossy().into_owned()];
+        command.extend(job.args.iter().cloned());
+        let output = output.filter(|_| outcome == Outcome::Failed);
+        let reason = output.as_deref().and_then(diagnose::classify);
+        Self {
+            name: job.name.clone(),
+            version: job.version.clone(),
+            new_version: None,
+            outcome,
+            duration,
+            command,
+            fallback: None,
+            reason,
+            output,
+            log: None,
+        }
+    }
+
+    pub fn excluded(pkg: &Package) -> Self {
+        Self {
+            name: pkg.name.clone(),
+            version: pkg.version.clone(),
+            new_version: None,
+            outcome: Outcome::Excluded,
+            duration: Duration::ZERO,
+            command: Vec::new(),
+            fallback: None,
+            reason: None,
+            output: None,
+            log: None,
+        }
+    }
+
+    /// The package's name, with why it failed if that's known, e.g. "foo (linker error)"
+    pub fn failure_summary(&self) -> String {
+        match self.reason {
+            Some(reason) => format!("{} ({reason})", self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// Fill in the versions installed by successful jobs, once cargo has updated its metadata
+pub fn find_new_versions(results: &mut [JobResult]) -> Result<()> {
+    let crates2 = Crates2::load().context("Failed to reload .crates2.json")?;
+    let installed: Vec<Package> =
+        crates2.installs.keys().filter_map(|id| id.parse().ok()).collect();
+    for res in results.iter_mut().filter(|r| r.outcome == Outcome::Updated) {
+        res.new_version = installed.iter().find(|p| p.name == res.name).map(|p| p.version.clone());
+    }
+    Ok(())
+}
+
+/// Write the JSON report of a run
+pub fn write(
+    path: &Path,
+    started: SystemTime,
+    duration: Duration,
+    results: &[JobResult],
+) -> Result<()> {
+    let count = |o| results.iter().filter(|r| r.outcome == o).count();
+    let report = json!({
+        "started": format_timestamp(started),
+        "duration_secs": duration.as_secs_f64(),
+        "success": count(Outcome::Failed) == 0,
+        "updated": count(Outcome::Updated),
+        "failed": count(Outcome::Failed),
+        "deferred": count(Outcome::Deferred),
+        "packages": results,
+    });
+    let data = serde_json::to_string_pretty(&report)?;
+    fs::write(path, data + "\n").with_context(|| format!("Failed to write '{}'", path.display()))
+}
+
+/// Write the metrics of a run in the Prometheus text format, for node_exporter's textfile
+/// collector. The file is replaced atomically so that the collector never reads half of it.
+pub fn write_metrics(
+    path: &Path,
+    duration: Duration,
+    results: &[JobResult],
+    outdated: Option<usize>,
+) -> Result<()> {
+    let installed = Crates2::load().context("Failed to reload .crates2.json")?.installs.len();
+    let count = |o| results.iter().filter(|r| r.outcome == o).count();
+    let finished = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default();
+    let mut metrics = vec![
+        ("packages", "Number of installed packages", installed as f64),
+        ("updated_packages", "Packages updated by the last run", count(Outcome::Updated) as f64),
+        ("failed_packages", "Packages which failed to update", count(Outcome::Failed) as f64),
+        (
+            "deferred_packages",
+            "Packages left for later by --deadline",
+            count(Outcome::Deferred) as f64,
+        ),
+        ("last_run_timestamp_seconds", "When the last run finished", finished.as_secs() as f64),
+        ("last_run_duration_seconds", "How long the last run took", duration.as_secs_f64()),
+    ];
+    if let Some(outdated) = outdated {
+        metrics.push((
+            "outdated_packages",
+            "Packages with a newer version which weren't updated",
+            outdated as f64,
+        ));
+    }
+
+    let mut data = String::new();
+    for (name, help, value) in metrics {
+        let name = format!("cargo_update_installed_{name}");
+        data += &format!("# HELP {name} {help}\n# TYPE {name} gauge\n{name} {value}\n");
+    }
+    let tmp = path.with_extension("prom.tmp");
+    fs::write(&tmp, data).with_context(|| format!("Failed to write '{}'", tmp.display()))?;
+    fs::rename(&tmp, path).with_context(|| format!("Failed to write '{}'", path.display()))
+}
@@ -0,0 +1,101 @@
+//! Reporting the progress of an update run.
+//!
+//! The jobs tell a [`Reporter`] what's happening to each package instead of printing it
+//! themselves, which keeps how progress is shown apart from how jobs are run. [`Terminal`] prints
+//! the messages the command line has always printed, and `--tui` has its own implementation which
+//! shows the output of each package and can skip them.
+
+use crate::report::{JobResult, Outcome};
+use crate::{bump_println, util, Job};
+
+/// What happens during an update run. Jobs run in parallel with `--parallel`, so the methods can
+/// be called from several threads at once.
+pub trait Reporter: Sync {
+    /// A package's `cargo install` is about to start
+    fn started(&self, job: &Job);
+
+    /// `cargo install` is being run again for a package with an extra flag, e.g. "--locked"
+    fn retrying(&self, job: &Job, flag: &str);
+
+    /// A package's `cargo install` wrote some output. This is only reported while output is
+    /// captured, i.e. when running in parallel, writing a report, or if the reporter shows it.
+    fn output(&self, _name: &str, _data: &[u8]) {}
+
+    /// Whether the output of `cargo install` is shown by the reporter from [`Reporter::output`],
+    /// a line at a time, instead of going to the terminal. cargo's stdin isn't the terminal then.
+    fn shows_output(&self) -> bool {
+        false
+    }
+
+    /// Whether a package should be skipped after all, e.g. because the user asked for it. This is
+    /// checked before its `cargo install` starts and while it runs, which stops it.
+    fn cancelled(&self, _name: &str) -> bool {
+        false
+    }
+
+    /// A package was updated, or would have been with `--dry-run`
+    fn finished(&self, result: &JobResult);
+
+    /// A package failed to install
+    fn failed(&self, result: &JobResult);
+
+    /// A package wasn't updated because the run was stopped or reached its `--deadline`
+    fn skipped(&self, result: &JobResult);
+
+    /// `done` of the `total` packages have been finished with, one way or another
+    fn progress(&self, _done: usize, _total: usize) {}
+}
+
+/// Progress messages on stderr
+pub struct Terminal;
+
+impl Reporter for Terminal {
+    fn started(&self, job: &Job) {
+        let estimate =
+            job.estimate.map(|est| format!("usually takes {}", util::format_duration(est)));
+        match (&job.update, estimate) {
+            (Some((target, bump)), estimate) => bump_println(
+                *bump,
+                format_args!(
+                    "Updating {} {} -> {target} ({} update{})",
+                    job.name,
+                    job.version,
+                    bump.name(),
+                    estimate.map(|e| format!(", {e}")).unwrap_or_default()
+                ),
+            ),
+            (None, Some(estimate)) => msg!("Updating {} ({estimate})", job.name),
+            (None, None) => msg!("Updating {}", job.name),
+        }
+    }
+
+    fn retrying(&self, job: &Job, flag: &str) {
+        if flag == "--force" {
+            warnmsg!("Programs of '{}' are still missing, trying again with --force", job.name);
+        } else {
+            warnmsg!("Failed to install '{}', trying again with {flag}", job.name);
+        }
+    }
+
+    fn finished(&self, result: &JobResult) {
+        if result.outcome == Outcome::Updated {
+            let duration = util::format_duration(result.duration);
+            okmsg!("Finished {} in {duration}", result.name);
+        }
+    }
+
+    fn failed(&self, result: &JobResult) {
+        errmsg!("Error: failed to install '{}'", result.name);
+        if let Some(log) = &result.log {
+            errmsg!("  the output is in '{}'", log.display());
+        }
+    }
+
+    fn skipped(&self, result: &JobResult) {
+        if result.outcome == Outcome::Deferred {
+            skipmsg!("Deferred {}", result.name);
+        } else {
+            skipmsg!("Skipped {}", result.name);
+        }
+    }
+}
@@ -0,0 +1,186 @@
+//! Running the `cargo install` command of each job.
+//!
+//! The commands go through a [`CargoRunner`], so that they can be run some other way, e.g. inside
+//! `nix-shell` or a devcontainer, or not run at all by tests which only check the arguments.
+//! [`Direct`] runs cargo itself, and [`SystemdScope`] runs it in a systemd scope with resource
+//! limits for `--systemd-scope`.
+
+use std::ffi::OsStr;
+use std::io;
+use std::path::PathBuf;
+use std::process::{Child, Command};
+
+use anyhow::{bail, ensure, Result};
+
+use crate::{process, util, Args, Job};
+
+pub trait CargoRunner: Sync {
+    /// Build the command for a job, with its arguments and environment. `cargo` is the program to
+    /// run, which is the job's own one if it has one.
+    fn command(&self, cargo: &OsStr, job: &Job) -> Command {
+        let mut cmd = Command::new(cargo);
+        cmd.args(&job.args).envs(job.env.iter().map(|(k, v)| (k, v)));
+        cmd
+    }
+
+    /// Start a command built by [`command`](Self::command), once its stdio and the jobserver have
+    /// been set up. The child is waited for with `process::wait`.
+    fn spawn(&self, cmd: &mut Command) -> io::Result<Child> {
+        process::spawn(cmd)
+    }
+}
+
+/// Run cargo directly
+pub struct Direct;
+
+impl CargoRunner for Direct {}
+
+/// Run cargo with `systemd-run --user --scope`, with properties of the scope like "MemoryMax=4G"
+pub struct SystemdScope {
+    systemd_run: PathBuf,
+    properties: Vec<String>,
+}
+
+impl CargoRunner for SystemdScope {
+    fn command(&self, cargo: &OsStr, job: &Job) -> Command {
+        let mut cmd = Command::new(&self.systemd_run);
+        // the scope runs cargo as our child, so it keeps our environment and the jobserver
+        cmd.args(["--user", "--scope", "--quiet", "--collect"]);
+        for property in &self.properties {
+            cmd.arg("--property").arg(property);
+        }
+        cmd.arg("--").arg(cargo).args(&job.args).envs(job.env.iter().map(|(k, v)| (k, v)));
+        cmd
+    }
+}
+
+/// Parse a systemd unit property for --systemd-scope
+pub fn parse_property(s: &str) -> Result<String> {
+    match s.split_once('=') {
+        Some((name, value)) if !name.is_empty() && !value.is_empty() => Ok(s.to_owned()),
+        _ => bail!("expected PROPERTY=VALUE, e.g. MemoryMax=4G"),
+    }
+}
+
+/// The runner for the command-line options
+pub fn from_args(args: &Args) -> Result<Box<dyn CargoRunner>> {
+    if args.systemd_scope.is_empty() {
+        return Ok(Box::new(Direct));
+    }
+    ensure!(cfg!(target_os = "linux"), "--systemd-scope is only supported on Linux");
+    let Some(systemd_run) = util::find_program("systemd-run") else {
+        bail!("--systemd-scope needs systemd-run, which isn't installed");
+    };
+    Ok(Box::new(SystemdScope { systemd_run, properties: args.systemd_scope.clone() }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::process::Stdio;
+    use std::sync::Mutex;
+    use std::time::Instant;
+
+    use clap::Parser;
+
+    use super::*;
+    use crate::package_data::{Package, PackageDetails};
+    use crate::report::Outcome;
+    use crate::reporter::Terminal;
+
+    /// Records the arguments of each command, and runs something harmless in place of cargo
+    #[derive(Default)]
+    struct Recording {
+        commands: Mutex<Vec<Vec<String>>>,
+    }
+
+    impl CargoRunner for Recording {
+        fn spawn(&self, cmd: &mut Command) -> io::Result<Child> {
+            let args = cmd.get_args().map(|a| a.to_string_lossy().into_owned()).collect();
+            self.commands.lock().unwrap().push(args);
+            // rustc is there wherever the tests can run
+            let rustc = env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+            process::spawn(Command::new(rustc).arg("-V").stdout(Stdio::null()))
+        }
+    }
+
+    fn job(pkg_id: &str, details: PackageDetails) -> Job {
+        let pkg: Package = pkg_id.parse().unwrap();
+        let mut args = vec!["install".to_owned()];
+        crate::add_package_args(&mut args, &pkg, &details, &["--frozen".to_owned()]);
+        Job {
+            name: pkg.name,
+            version: pkg.version,
+            args,
+            target_dir: None,
+            env: Vec::new(),
+            estimate: None,
+            cargo: None,
+            update: None,
+            retry_locked: false,
+            missing_bins: Vec::new(),
+            batch: None,
+        }
+    }
+
+    #[test]
+    // the path package's directory would be written differently on Windows
+    #[cfg(unix)]
+    fn install_args() {
+        let jobs = vec![
+            job(
+                "ripgrep 13.0.0 (registry+https://github.com/rust-lang/crates.io-index)",
+                PackageDetails {
+                    features: vec!["pcre2".to_owned()],
+                    target: "x86_64-unknown-linux-gnu".to_owned(),
+                    ..Default::default()
+                },
+            ),
+            job(
+                "bcut 1.0.2 (git+https://github.com/aswild/bcut?branch=main#046894ca312298f2)",
+                PackageDetails { no_default_features: true, ..Default::default() },
+            ),
+            job("foo 0.1.0 (path+file:///nonexistent/foo)", PackageDetails::default()),
+        ];
+        let runner = Recording::default();
+        let args = crate::Args::parse_from(Vec::<String>::new());
+        let results = crate::run_jobs(
+            OsStr::new("cargo"),
+            jobs,
+            &args,
+            None,
+            Instant::now(),
+            &Terminal,
+            &runner,
+        )
+        .unwrap();
+        assert!(results.iter().all(|r| r.outcome == Outcome::Updated));
+
+        let commands = runner.commands.into_inner().unwrap();
+        let expected: [&[&str]; 3] = [
+            &[
+                "install",
+                "--features",
+                "pcre2",
+                "--target",
+                "x86_64-unknown-linux-gnu",
+                "--index",
+                "https://github.com/rust-lang/crates.io-index",
+                "--frozen",
+                "ripgrep",
+            ],
+            &[
+                "install",
+                "--no-default-features",
+                "--git",
+                "https://github.com/aswild/bcut",
+                "--branch",
+                "main",
+                "--frozen",
+                "bcut",
+            ],
+            &["install", "--path", "/nonexistent/foo", "--frozen", "foo"],
+        ];
+        assert_eq!(commands, expected.map(|args| args.to_vec()));
+    }
+}
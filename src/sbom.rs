@@ -0,0 +1,272 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::{Context, Result};
+use clap::{Parser, ValueEnum};
+use serde_json::{json, Value};
+use url::form_urlencoded;
+
+use crate::package_data::*;
+use crate::util::format_timestamp;
+
+const TOOL_NAME: &str = env!("CARGO_PKG_NAME");
+const TOOL_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum SbomFormat {
+    /// CycloneDX 1.4 JSON
+    Cyclonedx,
+    /// SPDX 2.3 JSON
+    Spdx,
+}
+
+/// Write a software bill of materials listing all installed packages.
+///
+/// When a package's Cargo.lock can be found (in cargo's registry source cache, or in the source
+/// directory for path installs), its locked dependencies are included too.
+#[derive(Debug, Parser)]
+pub struct SbomArgs {
+    /// Document format
+    #[arg(long, value_enum, default_value = "cyclonedx")]
+    format: SbomFormat,
+
+    /// Write the document to FILE instead of stdout
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+}
+
+/// An SBOM entry, either an installed package or one of its locked dependencies
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Component {
+    name: String,
+    version: String,
+    purl: Option<String>,
+}
+
+impl Component {
+    fn new(name: &str, version: &str, source: Option<&PackageSource>) -> Self {
+        let purl = match source {
+            None => None,
+            Some(src) if src.is_crates_io() => Some(format!("pkg:cargo/{name}@{version}")),
+            Some(PackageSource::Registry(url)) => {
+                Some(purl_with(name, version, "repository_url", url))
+            }
+            Some(PackageSource::Git { url, .. }) => {
+                Some(purl_with(name, version, "vcs_url", &format!("git+{url}")))
+            }
+            // local sources don't have a meaningful package URL
+            Some(PackageSource::Path(_)) => None,
+        };
+        Self { name: name.into(), version: version.into(), purl }
+    }
+
+    /// Unique reference used to link dependencies
+    fn bom_ref(&self) -> String {
+        self.purl.clone().unwrap_or_else(|| format!("{}@{}", self.name, self.version))
+    }
+
+    /// SPDX identifiers are restricted to letters, numbers, '.', and '-'
+    fn spdx_id(&self) -> String {
+        let id: String = format!("{}-{}", self.name, self.version)
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '.' { c } else { '-' })
+            .collect();
+        format!("SPDXRef-Package-{id}")
+    }
+}
+
+fn purl_with(name: &str, version: &str, key: &str, value: &str) -> String {
+    let value: String = form_urlencoded::byte_serialize(value.as_bytes()).collect();
+    format!("pkg:cargo/{name}@{version}?{key}={value}")
+}
+
+/// Read the `[[package]]` entries of a Cargo.lock file which come from a registry or git.
+/// Packages without a source are workspace members, i.e. the package itself or its siblings.
+fn parse_lockfile(text: &str) -> Vec<Component> {
+    let mut out = Vec::new();
+    let mut cur: Option<(String, String, Option<String>)> = None;
+    let mut flush = |cur: &mut Option<(String, String, Option<String>)>| {
+        if let Some((name, version, Some(source))) = cur.take() {
+            if let Ok(source) = source.parse::<PackageSource>() {
+                out.push(Component::new(&name, &version, Some(&source)));
+            }
+        }
+    };
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            flush(&mut cur);
+            if line == "[[package]]" {
+                cur = Some(Default::default());
+            }
+            continue;
+        }
+        let (Some(entry), Some((key, val))) = (cur.as_mut(), line.split_once(" = ")) else {
+            continue;
+        };
+        let val = val.trim_matches('"').to_owned();
+        match key {
+            "name" => entry.0 = val,
+            "version" => entry.1 = val,
+            "source" => entry.2 = Some(val),
+            _ => (),
+        }
+    }
+    flush(&mut cur);
+    out
+}
+
+/// Try to find the Cargo.lock which was used to install a package. Packages may be part of a
+/// workspace, where the lockfile is in a parent directory.
+fn find_lockfile(cargo_home: &Path, pkg: &Package) -> Option<PathBuf> {
+    let dir = pkg.source_dir(cargo_home)?;
+    let mut candidates = dir.ancestors().map(|dir| dir.join("Cargo.lock"));
+    match pkg.source {
+        // registry packages are always extracted on their own
+        PackageSource::Registry(_) => candidates.next().filter(|lock| lock.is_file()),
+        _ => candidates.find(|lock| lock.is_file()),
+    }
+}
+
+fn cyclonedx(timestamp: &str, packages: &BTreeMap<Component, Vec<Component>>) -> Value {
+    let mut components = BTreeMap::new();
+    let mut dependencies = Vec::new();
+    for (pkg, deps) in packages {
+        components.insert(pkg.bom_ref(), (pkg, "application"));
+        for dep in deps {
+            components.entry(dep.bom_ref()).or_insert((dep, "library"));
+        }
+        let depends_on: Vec<_> = deps.iter().map(Component::bom_ref).collect();
+        dependencies.push(json!({ "ref": pkg.bom_ref(), "dependsOn": depends_on }));
+    }
+
+    let components: Vec<_> = components
+        .into_iter()
+        .map(|(bom_ref, (c, kind))| {
+            let mut v = json!({
+                "type": kind,
+                "bom-ref": bom_ref,
+                "name": c.name,
+                "version": c.version,
+            });
+            if let Some(purl) = &c.purl {
+                v["purl"] = purl.as_str().into();
+            }
+            v
+        })
+        .collect();
+
+    json!({
+        "bomFormat": "CycloneDX",
+        "specVersion": "1.4",
+        "version": 1,
+        "metadata": {
+            "timestamp": timestamp,
+            "tools": [{ "name": TOOL_NAME, "version": TOOL_VERSION }],
+        },
+        "components": components,
+        "dependencies": dependencies,
+    })
+}
+
+fn spdx(timestamp: &str, packages: &BTreeMap<Component, Vec<Component>>) -> Value {
+    let mut spdx_packages = BTreeMap::new();
+    let mut relationships = Vec::new();
+    for (pkg, deps) in packages {
+        spdx_packages.insert(pkg.spdx_id(), pkg);
+        relationships.push(json!({
+            "spdxElementId": "SPDXRef-DOCUMENT",
+            "relationshipType": "DESCRIBES",
+            "relatedSpdxElement": pkg.spdx_id(),
+        }));
+        for dep in deps {
+            spdx_packages.insert(dep.spdx_id(), dep);
+            relationships.push(json!({
+                "spdxElementId": pkg.spdx_id(),
+                "relationshipType": "DEPENDS_ON",
+                "relatedSpdxElement": dep.spdx_id(),
+            }));
+        }
+    }
+
+    let spdx_packages: Vec<_> = spdx_packages
+        .into_iter()
+        .map(|(id, c)| {
+            let mut v = json!({
+                "SPDXID": id,
+                "name": c.name,
+                "versionInfo": c.version,
+                "downloadLocation": "NOASSERTION",
+                "filesAnalyzed": false,
+            });
+            if let Some(purl) = &c.purl {
+                v["externalRefs"] = json!([{
+                    "referenceCategory": "PACKAGE-MANAGER",
+                    "referenceType": "purl",
+                    "referenceLocator": purl,
+                }]);
+            }
+            v
+        })
+        .collect();
+
+    json!({
+        "spdxVersion": "SPDX-2.3",
+        "dataLicense": "CC0-1.0",
+        "SPDXID": "SPDXRef-DOCUMENT",
+        "name": "cargo-installed-packages",
+        "documentNamespace": format!("https://spdx.org/spdxdocs/{TOOL_NAME}-{timestamp}"),
+        "creationInfo": {
+            "created": timestamp,
+            "creators": [format!("Tool: {TOOL_NAME}-{TOOL_VERSION}")],
+        },
+        "packages": spdx_packages,
+        "relationships": relationships,
+    })
+}
+
+pub fn run(args: &SbomArgs, crates2: &Crates2) -> Result<()> {
+    let cargo_home = cargo_home()?;
+    let mut packages = BTreeMap::new();
+    for pkg_id in crates2.installs.keys() {
+        let pkg = pkg_id
+            .parse::<Package>()
+            .with_context(|| format!("Failed to parse package id '{pkg_id}'"))?;
+
+        let mut deps = Vec::new();
+        if let Some(lock) = find_lockfile(&cargo_home, &pkg) {
+            dbgmsg!("Reading dependencies of {} from {}", pkg.name, lock.display());
+            let text = fs::read_to_string(&lock)
+                .with_context(|| format!("Failed to read '{}'", lock.display()))?;
+            deps = parse_lockfile(&text);
+            deps.sort();
+            deps.dedup();
+        }
+        packages.insert(Component::new(&pkg.name, &pkg.version, Some(&pkg.source)), deps);
+    }
+
+    let timestamp = format_timestamp(SystemTime::now());
+    let doc = match args.format {
+        SbomFormat::Cyclonedx => cyclonedx(&timestamp, &packages),
+        SbomFormat::Spdx => spdx(&timestamp, &packages),
+    };
+
+    match &args.output {
+        Some(path) => {
+            let data = serde_json::to_string_pretty(&doc)?;
+            fs::write(path, data + "\n")
+                .with_context(|| format!("Failed to write '{}'", path.display()))?;
+            msg!("Wrote {}", path.display());
+        }
+        None => {
+            let mut out = io::stdout().lock();
+            serde_json::to_writer_pretty(&mut out, &doc)?;
+            writeln!(out)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,54 @@
+//! Reporting sccache's cache hit rate for a run.
+
+use std::ffi::OsStr;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+
+/// Compilation counts from `sccache --show-stats`
+#[derive(Debug, Clone, Copy)]
+pub struct Stats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// Whether a RUSTC_WRAPPER is sccache
+pub fn is_sccache(wrapper: &OsStr) -> bool {
+    Path::new(wrapper).file_stem().is_some_and(|stem| stem == "sccache")
+}
+
+/// Sum the per-language counts in one of sccache's stats
+fn total(stat: &Value) -> u64 {
+    stat["counts"].as_object().map_or(0, |counts| counts.values().filter_map(Value::as_u64).sum())
+}
+
+/// Get sccache's current statistics. These are cumulative since the sccache server started.
+pub fn stats(sccache: &OsStr) -> Result<Stats> {
+    let out = Command::new(sccache)
+        .args(["--show-stats", "--stats-format", "json"])
+        .output()
+        .context("Failed to run sccache")?;
+    if !out.status.success() {
+        bail!("sccache --show-stats failed: {}", String::from_utf8_lossy(&out.stderr).trim());
+    }
+    let data: Value =
+        serde_json::from_slice(&out.stdout).context("Invalid JSON from sccache --show-stats")?;
+    let stats = &data["stats"];
+    Ok(Stats { hits: total(&stats["cache_hits"]), misses: total(&stats["cache_misses"]) })
+}
+
+impl Stats {
+    /// Print the hits and misses since an earlier snapshot
+    pub fn report_since(&self, before: &Stats) {
+        let hits = self.hits.saturating_sub(before.hits);
+        let misses = self.misses.saturating_sub(before.misses);
+        if hits + misses == 0 {
+            msg!("sccache: no cacheable compilations");
+        } else {
+            let rate = 100.0 * hits as f64 / (hits + misses) as f64;
+            msg!("sccache: {hits} hits, {misses} misses ({rate:.0}% hit rate)");
+        }
+    }
+}
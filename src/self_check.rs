@@ -0,0 +1,70 @@
+//! Checking whether a newer version of cargo-update-installed itself has been released, since it's
+//! easy to forget about the program which updates everything else.
+//!
+//! The check is done at most once a day, and what was found is saved so that the hint keeps being
+//! shown in between. It can be turned off with `check-self = false` in the config's `[defaults]`,
+//! and isn't done when `CARGO_NET_OFFLINE` is set.
+
+use std::env;
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::index;
+use crate::semver::Version;
+use crate::util;
+
+const FILE_NAME: &str = "self-check.json";
+
+/// How long to wait before checking crates.io again
+const INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+struct SelfCheck {
+    checked: Option<String>,
+    /// The latest version on crates.io when it was checked
+    latest: Option<String>,
+}
+
+/// Find the latest release, from the saved check if it's recent enough
+fn latest_release(current: &Version) -> Option<Version> {
+    let mut saved: SelfCheck = util::load_state(FILE_NAME).unwrap_or_default();
+    let recent = saved
+        .checked
+        .as_deref()
+        .and_then(util::parse_timestamp)
+        .and_then(|t| SystemTime::now().duration_since(t).ok())
+        .is_some_and(|age| age < INTERVAL);
+    let offline = env::var("CARGO_NET_OFFLINE").is_ok_and(|v| v == "true" || v == "1");
+    if !recent && !offline {
+        dbgmsg!("Checking for a newer version of {}", env!("CARGO_PKG_NAME"));
+        // a failed check is tried again tomorrow rather than slowing down every run until then,
+        // and the last version found is kept in the meantime
+        saved.checked = Some(util::format_timestamp(SystemTime::now()));
+        match index::latest_version(env!("CARGO_PKG_NAME"), current) {
+            Ok(Some(latest)) => saved.latest = Some(latest.to_string()),
+            Ok(None) => (),
+            Err(e) => dbgmsg!("Failed to check for a newer version: {e:#}"),
+        }
+        if let Err(e) = util::save_state(FILE_NAME, &saved) {
+            dbgmsg!("Failed to save the check for a newer version: {e:#}");
+        }
+    }
+    saved.latest?.parse().ok()
+}
+
+/// Print a hint if there's a newer version of this program
+pub fn run() {
+    let Ok(current) = env!("CARGO_PKG_VERSION").parse::<Version>() else {
+        return;
+    };
+    if let Some(latest) = latest_release(&current).filter(|latest| *latest > current) {
+        msg!(
+            "{} {latest} is available, you have {current}; update it with `cargo update-installed \
+             {}`",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_NAME")
+        );
+    }
+}
@@ -0,0 +1,252 @@
+//! Just enough of semantic versioning to compare package versions.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Error, Result};
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    /// Pre-release identifiers, e.g. ["beta", "2"] for 1.0.0-beta.2. Build metadata is discarded.
+    pub pre: Vec<String>,
+}
+
+impl FromStr for Version {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || anyhow!("invalid version '{s}'");
+        let s_nobuild = s.split_once('+').map_or(s, |(v, _)| v);
+        let (core, pre) = match s_nobuild.split_once('-') {
+            Some((core, pre)) => (core, pre.split('.').map(String::from).collect()),
+            None => (s_nobuild, Vec::new()),
+        };
+        let mut parts = core.split('.').map(|p| p.parse::<u64>().map_err(|_| invalid()));
+        let version = Self {
+            major: parts.next().ok_or_else(invalid)??,
+            minor: parts.next().ok_or_else(invalid)??,
+            patch: parts.next().ok_or_else(invalid)??,
+            pre,
+        };
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(version)
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if !self.pre.is_empty() {
+            write!(f, "-{}", self.pre.join("."))?;
+        }
+        Ok(())
+    }
+}
+
+impl Version {
+    pub fn is_prerelease(&self) -> bool {
+        !self.pre.is_empty()
+    }
+
+    /// Classify an update from this version to a newer one. Like cargo, the first non-zero part
+    /// is the one which makes updates incompatible, so 0.3.0 to 0.4.0 is a major update.
+    pub fn bump_to(&self, newer: &Self) -> Bump {
+        let compatible = match (self.major, self.minor) {
+            (0, 0) => newer.major == 0 && newer.minor == 0 && newer.patch == self.patch,
+            (0, minor) => newer.major == 0 && newer.minor == minor,
+            (major, _) => newer.major == major,
+        };
+        if !compatible {
+            Bump::Major
+        } else if newer.minor != self.minor && self.major > 0 {
+            Bump::Minor
+        } else {
+            Bump::Patch
+        }
+    }
+}
+
+/// How big of a change an update is, ordered from smallest to biggest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum Bump {
+    /// Bug fixes, e.g. 1.2.3 -> 1.2.4 or 0.3.1 -> 0.3.2
+    Patch,
+    /// New features which should be compatible, e.g. 1.2.3 -> 1.3.0
+    Minor,
+    /// Possibly incompatible changes, e.g. 1.2.3 -> 2.0.0 or 0.3.1 -> 0.4.0
+    Major,
+}
+
+impl Bump {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Patch => "patch",
+            Self::Minor => "minor",
+            Self::Major => "major",
+        }
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch)
+            .cmp(&(other.major, other.minor, other.patch))
+            .then_with(|| match (self.pre.is_empty(), other.pre.is_empty()) {
+                // a pre-release comes before its release
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                (false, false) => cmp_pre(&self.pre, &other.pre),
+            })
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Compare pre-release identifiers: numeric ones numerically and before alphanumeric ones, and
+/// otherwise in ASCII order. A shorter list of equal identifiers comes first.
+fn cmp_pre(a: &[String], b: &[String]) -> Ordering {
+    for (x, y) in a.iter().zip(b) {
+        let ord = match (x.parse::<u64>(), y.parse::<u64>()) {
+            (Ok(x), Ok(y)) => x.cmp(&y),
+            (Ok(_), Err(_)) => Ordering::Less,
+            (Err(_), Ok(_)) => Ordering::Greater,
+            (Err(_), Err(_)) => x.cmp(y),
+        };
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+/// Check whether a release matches a version requirement like cargo's, e.g. "1.2", "^0.3.1",
+/// "~1.4", ">=1.0, <3", or "2.*". Pre-release parts of the requirement are ignored, so this is
+/// only meant for picking between releases.
+pub fn matches_req(req: &str, version: &Version) -> bool {
+    req.split(',').all(|comparator| matches_comparator(comparator.trim(), version))
+}
+
+fn matches_comparator(comparator: &str, version: &Version) -> bool {
+    let (op, rest) = match comparator.find(|c: char| c.is_ascii_digit() || c == '*') {
+        Some(i) => comparator.split_at(i),
+        None => return comparator.is_empty(),
+    };
+    let rest = rest.split(['-', '+']).next().unwrap_or(rest);
+    // missing or wildcard parts, as in "1" or "1.*", match any value
+    let mut parts = rest.split('.').map(|p| p.parse::<u64>().ok());
+    let (Some(major), minor, patch) =
+        (parts.next().flatten(), parts.next().flatten(), parts.next().flatten())
+    else {
+        return rest.starts_with('*');
+    };
+    let lower =
+        Version { major, minor: minor.unwrap_or(0), patch: patch.unwrap_or(0), pre: vec![] };
+    let next = |major, minor, patch| Version { major, minor, patch, pre: vec![] };
+    // the first version past those which match all the parts given
+    let past_given = match (minor, patch) {
+        (None, _) => next(major + 1, 0, 0),
+        (Some(minor), None) => next(major, minor + 1, 0),
+        (Some(minor), Some(patch)) => next(major, minor, patch + 1),
+    };
+    match op.trim() {
+        "=" => lower <= *version && *version < past_given,
+        ">" => *version >= past_given,
+        ">=" => *version >= lower,
+        "<" => *version < lower,
+        "<=" => *version < past_given,
+        "~" => {
+            let upper = match minor {
+                Some(minor) => next(major, minor + 1, 0),
+                None => next(major + 1, 0, 0),
+            };
+            lower <= *version && *version < upper
+        }
+        "" | "^" => {
+            // the first non-zero part given can't change
+            let upper = match (major, minor, patch) {
+                (0, Some(0), Some(patch)) => next(0, 0, patch + 1),
+                (0, Some(minor), _) => next(0, minor + 1, 0),
+                _ => next(major + 1, 0, 0),
+            };
+            lower <= *version && *version < upper
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(req: &str, version: &str) -> bool {
+        matches_req(req, &version.parse().unwrap())
+    }
+
+    fn bump(from: &str, to: &str) -> Bump {
+        from.parse::<Version>().unwrap().bump_to(&to.parse().unwrap())
+    }
+
+    #[test]
+    fn bumps() {
+        assert_eq!(bump("1.2.3", "1.2.4"), Bump::Patch);
+        assert_eq!(bump("1.2.3", "1.3.0"), Bump::Minor);
+        assert_eq!(bump("1.2.3", "2.0.0"), Bump::Major);
+        // the first non-zero part is the major one
+        assert_eq!(bump("0.3.1", "0.3.2"), Bump::Patch);
+        assert_eq!(bump("0.3.1", "0.4.0"), Bump::Major);
+        assert_eq!(bump("0.0.3", "0.0.4"), Bump::Major);
+        assert_eq!(bump("0.0.3", "0.1.0"), Bump::Major);
+        assert_eq!(bump("0.9.0", "1.0.0"), Bump::Major);
+        assert_eq!(bump("1.0.0-rc.1", "1.0.0"), Bump::Patch);
+    }
+
+    #[test]
+    fn caret_with_leading_zeros() {
+        assert!(matches("^0.0.3", "0.0.3"));
+        assert!(!matches("^0.0.3", "0.0.4"));
+        assert!(!matches("^0.0.3", "0.0.2"));
+        assert!(matches("0.0.3", "0.0.3"));
+        assert!(matches("^0.2.1", "0.2.9"));
+        assert!(!matches("^0.2.1", "0.3.0"));
+    }
+
+    #[test]
+    fn tilde_without_minor() {
+        assert!(matches("~1", "1.0.0"));
+        assert!(matches("~1", "1.9.3"));
+        assert!(!matches("~1", "2.0.0"));
+        assert!(!matches("~1", "0.9.0"));
+        assert!(matches("~1.4", "1.4.7"));
+        assert!(!matches("~1.4", "1.5.0"));
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(matches("*", "0.0.1"));
+        assert!(matches("*", "12.3.4"));
+        assert!(matches("2.*", "2.7.0"));
+        assert!(!matches("2.*", "3.0.0"));
+    }
+
+    #[test]
+    fn ranges() {
+        assert!(matches(">=1.0, <3", "1.0.0"));
+        assert!(matches(">=1.0, <3", "2.99.0"));
+        assert!(!matches(">=1.0, <3", "3.0.0"));
+        assert!(!matches(">=1.0, <3", "0.9.9"));
+        assert!(matches(">1.2, <=1.4", "1.4.9"));
+        assert!(!matches(">1.2, <=1.4", "1.2.5"));
+    }
+}
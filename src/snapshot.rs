@@ -0,0 +1,187 @@
+//! Saved copies of .crates2.json, to show how the installed packages change over time.
+//!
+//! A snapshot is taken at the start of each update run (unless nothing changed since the last one),
+//! named after the time it was taken, e.g. `2023-05-31T123456Z.json`.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use anyhow::{anyhow, Context, Result};
+use clap::Parser;
+
+use crate::package_data::*;
+use crate::util;
+
+/// How many snapshots to keep
+const MAX_SNAPSHOTS: usize = 100;
+
+/// Show how the installed packages changed since the last update run, or between two snapshots.
+///
+/// Snapshots of cargo's metadata are saved before each update run. A snapshot can be given as a
+/// date like "2023-05-31" or a full snapshot name, either of which picks the most recent snapshot
+/// taken on or before then.
+#[derive(Debug, Parser)]
+pub struct DiffArgs {
+    /// Snapshot to compare from [default: the most recent one]
+    from: Option<String>,
+
+    /// Snapshot to compare to [default: the currently installed packages]
+    to: Option<String>,
+
+    /// List the saved snapshots instead.
+    #[arg(long, conflicts_with_all = ["from", "to"])]
+    list: bool,
+}
+
+fn snapshot_dir() -> Result<PathBuf> {
+    Ok(util::state_dir()?.join("snapshots"))
+}
+
+/// The names of the saved snapshots, oldest first
+fn snapshots() -> Result<Vec<String>> {
+    let dir = snapshot_dir()?;
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).context(format!("Failed to read '{}'", dir.display())),
+    };
+    let mut names = Vec::new();
+    for entry in entries {
+        if let Some(name) = entry?.file_name().to_str().and_then(|n| n.strip_suffix(".json")) {
+            names.push(name.to_owned());
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// Save a snapshot of .crates2.json, unless it's the same as the most recent one
+pub fn save() -> Result<()> {
+    let data = fs::read(Crates2::path()?).context("Failed to read .crates2.json")?;
+    let dir = snapshot_dir()?;
+    let names = snapshots()?;
+    if let Some(last) = names.last() {
+        if fs::read(dir.join(format!("{last}.json"))).is_ok_and(|last| last == data) {
+            return Ok(());
+        }
+    }
+
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create '{}'", dir.display()))?;
+    // colons aren't allowed in file names on Windows
+    let name = util::format_timestamp(SystemTime::now()).replace(':', "");
+    let path = dir.join(format!("{name}.json"));
+    fs::write(&path, data).with_context(|| format!("Failed to write '{}'", path.display()))?;
+    dbgmsg!("Saved a snapshot to '{}'", path.display());
+
+    for old in names.iter().rev().skip(MAX_SNAPSHOTS - 1) {
+        let _ = fs::remove_file(dir.join(format!("{old}.json")));
+    }
+    Ok(())
+}
+
+/// Find the most recent snapshot on or before a date or time
+fn find(names: &[String], when: &str) -> Result<String> {
+    names
+        .iter()
+        .rev()
+        .find(|name| name.get(..when.len()).is_some_and(|prefix| prefix <= when))
+        .cloned()
+        .ok_or_else(|| anyhow!("No snapshots from on or before '{when}'"))
+}
+
+/// Load a snapshot's packages, indexed by name
+fn load(name: Option<&str>) -> Result<BTreeMap<String, (Package, PackageDetails)>> {
+    let crates2 = match name {
+        Some(name) => Crates2::load_from(&snapshot_dir()?.join(format!("{name}.json")))?,
+        None => Crates2::load()?,
+    };
+    let mut packages = BTreeMap::new();
+    for (pkg_id, details) in crates2.installs {
+        let pkg = pkg_id
+            .parse::<Package>()
+            .with_context(|| format!("Failed to parse package id '{pkg_id}'"))?;
+        packages.insert(pkg.name.clone(), (pkg, details));
+    }
+    Ok(packages)
+}
+
+fn features(details: &PackageDetails) -> String {
+    let mut features = details.features.join(",");
+    if details.all_features {
+        features = "all".into();
+    }
+    if details.no_default_features {
+        features += " (no default)";
+    }
+    if features.is_empty() {
+        "default".into()
+    } else {
+        features
+    }
+}
+
+pub fn run(args: &DiffArgs) -> Result<()> {
+    let names = snapshots()?;
+    if args.list {
+        for name in &names {
+            println!("{name}");
+        }
+        return Ok(());
+    }
+
+    let from = match &args.from {
+        Some(when) => find(&names, when)?,
+        None => names.last().cloned().ok_or_else(|| anyhow!("No snapshots have been saved yet"))?,
+    };
+    let to = args.to.as_deref().map(|when| find(&names, when)).transpose()?;
+    msg!("Changes from {from} to {}", to.as_deref().unwrap_or("now"));
+    let old = load(Some(&from))?;
+    let new = load(to.as_deref())?;
+
+    let mut changes = 0;
+    for (name, (pkg, _)) in &new {
+        if !old.contains_key(name) {
+            println!("+ {name} {} ({})", pkg.version, pkg.source);
+            changes += 1;
+        }
+    }
+    for (name, (pkg, _)) in &old {
+        if !new.contains_key(name) {
+            println!("- {name} {} ({})", pkg.version, pkg.source);
+            changes += 1;
+        }
+    }
+    for (name, (new_pkg, new_details)) in &new {
+        let Some((old_pkg, old_details)) = old.get(name) else { continue };
+        let mut diffs = Vec::new();
+        if old_pkg.version != new_pkg.version {
+            diffs.push(format!("{} -> {}", old_pkg.version, new_pkg.version));
+        }
+        if old_pkg.source.to_string() != new_pkg.source.to_string() {
+            diffs.push(format!("source {} -> {}", old_pkg.source, new_pkg.source));
+        } else if let (
+            PackageSource::Git { rev: Some(old_rev), .. },
+            PackageSource::Git { rev: Some(new_rev), .. },
+        ) = (&old_pkg.source, &new_pkg.source)
+        {
+            if old_rev != new_rev {
+                diffs.push(format!("rev {old_rev} -> {new_rev}"));
+            }
+        }
+        let (old_features, new_features) = (features(old_details), features(new_details));
+        if old_features != new_features {
+            diffs.push(format!("features {old_features} -> {new_features}"));
+        }
+        if !diffs.is_empty() {
+            println!("~ {name} {}", diffs.join(", "));
+            changes += 1;
+        }
+    }
+    if changes == 0 {
+        msg!("No changes");
+    }
+    Ok(())
+}
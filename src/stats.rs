@@ -0,0 +1,101 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use clap::{Parser, ValueEnum};
+use serde_json::json;
+
+use crate::history::History;
+use crate::list::print_table;
+use crate::util::format_duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Aligned columns, followed by the totals
+    Table,
+    /// A JSON object with the totals and each package's statistics
+    Json,
+}
+
+/// Show statistics about previous update runs.
+///
+/// Packages are listed by how often they've been updated to a new version, with how often they've
+/// been built, how many of those builds failed, and how long building them took. The statistics
+/// count the runs since they started being recorded, not any before that.
+#[derive(Debug, Parser)]
+pub struct StatsArgs {
+    /// Output format.
+    #[arg(long, value_enum, default_value_t = Format::Table)]
+    format: Format,
+}
+
+fn secs(secs: f64) -> String {
+    format_duration(Duration::try_from_secs_f64(secs).unwrap_or_default())
+}
+
+pub fn run(args: &StatsArgs) -> Result<()> {
+    let history = History::load()?;
+    let mut packages: Vec<_> = history.totals.iter().collect();
+    // most frequently updated first, then the ones which take the most time
+    packages.sort_by(|(a_name, a), (b_name, b)| {
+        b.updates
+            .cmp(&a.updates)
+            .then(b.build_secs.total_cmp(&a.build_secs))
+            .then(a_name.cmp(b_name))
+    });
+
+    let builds: u64 = packages.iter().map(|(_, t)| t.builds).sum();
+    let updates: u64 = packages.iter().map(|(_, t)| t.updates).sum();
+    let failures: u64 = packages.iter().map(|(_, t)| t.failures).sum();
+    let total_secs: f64 = packages.iter().map(|(_, t)| t.build_secs + t.failed_secs).sum();
+
+    if args.format == Format::Json {
+        let packages: Vec<_> = packages
+            .iter()
+            .map(|(name, t)| {
+                json!({
+                    "name": name,
+                    "updates": t.updates,
+                    "builds": t.builds,
+                    "failures": t.failures,
+                    "failure_rate": t.failures as f64 / (t.builds + t.failures).max(1) as f64,
+                    "average_build_secs": t.build_secs / t.builds.max(1) as f64,
+                    "total_secs": t.build_secs + t.failed_secs,
+                })
+            })
+            .collect();
+        let stats = json!({
+            "updates": updates,
+            "builds": builds,
+            "failures": failures,
+            "total_secs": total_secs,
+            "packages": packages,
+        });
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+        return Ok(());
+    }
+
+    if packages.is_empty() {
+        msg!("No update runs have been recorded yet");
+        return Ok(());
+    }
+    let rows: Vec<Vec<String>> = packages
+        .iter()
+        .map(|(name, t)| {
+            let attempts = t.builds + t.failures;
+            vec![
+                name.to_string(),
+                t.updates.to_string(),
+                t.builds.to_string(),
+                format!("{} ({:.0}%)", t.failures, t.failures as f64 * 100.0 / attempts as f64),
+                if t.builds > 0 { secs(t.build_secs / t.builds as f64) } else { "-".into() },
+                secs(t.build_secs + t.failed_secs),
+            ]
+        })
+        .collect();
+    print_table(&["NAME", "UPDATES", "BUILDS", "FAILED", "AVERAGE", "TOTAL"], &rows, "");
+    println!(
+        "\nTotal: {updates} updates, {builds} builds, {failures} failed, {} spent building",
+        secs(total_secs)
+    );
+    Ok(())
+}
@@ -0,0 +1,96 @@
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use clap::{Parser, ValueEnum};
+
+use crate::outdated::{LastCheck, LAST_CHECK_FILE};
+use crate::package_data::*;
+use crate::util;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// A short line like "3 outdated", or nothing if all packages are up to date
+    Line,
+    /// Only the number of outdated packages
+    Count,
+}
+
+/// Show how many packages were outdated at the last check, e.g. for a shell prompt.
+///
+/// This only reads what `outdated` last found, without any network access, so it takes a few
+/// milliseconds. Packages which have been updated since aren't counted. Run `outdated` regularly
+/// (e.g. from cron) to keep it current. Nothing is printed if there hasn't been a check yet.
+#[derive(Debug, Parser)]
+pub struct StatusArgs {
+    /// Output format.
+    #[arg(long, value_enum, default_value_t = Format::Line)]
+    format: Format,
+
+    /// Print nothing if the last check was more than DURATION (e.g. "1d") ago.
+    #[arg(long, value_name = "DURATION", value_parser = util::parse_duration)]
+    max_age: Option<Duration>,
+
+    /// Print a compact string for a status bar like tmux's or i3bar, e.g. "⬆ 3".
+    ///
+    /// Nothing is printed if all packages are up to date, and the output never has escape codes
+    /// or a newline in it, so it can be embedded as it is.
+    #[arg(long, conflicts_with = "format")]
+    short: bool,
+
+    /// The string --short prints, where "{count}" is the number of outdated packages, "{names}"
+    /// their names separated by commas, and "{age}" how long ago the last check was, e.g. "3h".
+    #[arg(long, value_name = "TEMPLATE", default_value = "⬆ {count}", requires = "short")]
+    template: String,
+}
+
+/// Fill in a --short template, leaving out any control characters so that a status bar can't be
+/// messed up
+fn render(template: &str, names: &[&str], age: Duration) -> String {
+    // only the largest unit, "3h" rather than "3h 12m"
+    let age = util::format_duration(age);
+    let age = age.split_whitespace().next().unwrap_or_default();
+    template
+        .replace("{count}", &names.len().to_string())
+        .replace("{names}", &names.join(","))
+        .replace("{age}", age)
+        .chars()
+        .filter(|c| !c.is_control())
+        .collect()
+}
+
+pub fn run(args: &StatusArgs, crates2: &Crates2) -> Result<()> {
+    let last_check: LastCheck = util::load_state(LAST_CHECK_FILE)?;
+    let Some(checked) = last_check.checked.as_deref().and_then(util::parse_timestamp) else {
+        return Ok(());
+    };
+    if let Some(max_age) = args.max_age {
+        if SystemTime::now().duration_since(checked).unwrap_or_default() > max_age {
+            return Ok(());
+        }
+    }
+
+    let installed: Vec<Package> =
+        crates2.installs.keys().filter_map(|id| id.parse().ok()).collect();
+    let outdated: Vec<&str> = last_check
+        .outdated
+        .iter()
+        .filter(|(name, (version, _))| {
+            installed.iter().any(|pkg| &pkg.name == *name && pkg.version == *version)
+        })
+        .map(|(name, _)| name.as_str())
+        .collect();
+    let count = outdated.len();
+    if args.short {
+        if count > 0 {
+            let age = SystemTime::now().duration_since(checked).unwrap_or_default();
+            print!("{}", render(&args.template, &outdated, age));
+        }
+        return Ok(());
+    }
+    match args.format {
+        Format::Count => println!("{count}"),
+        Format::Line if count > 0 => println!("{count} outdated"),
+        Format::Line => (),
+    }
+    Ok(())
+}
@@ -0,0 +1,265 @@
+//! Make the installed packages match a manifest file.
+//!
+//! The manifest is TOML with a `[packages]` table. Each package is either a version requirement,
+//! or a table which can set the source and build options:
+//! ```toml
+//! [packages]
+//! bat = "*"
+//! cargo-edit = "0.12"
+//! ripgrep = { features = ["pcre2"] }
+//! bcut = { git = "https://github.com/aswild/bcut", branch = "master" }
+//! mytool = { path = "~/src/mytool", locked = true }
+//! ```
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, ensure, Context, Result};
+use clap::Parser;
+use serde::Deserialize;
+
+use crate::install_flags::{Flags, InstallFlags};
+use crate::package_data::*;
+use crate::process;
+use crate::toml;
+use crate::verify;
+use crate::{Failure, PushStr};
+
+/// Install, update, and optionally uninstall packages to match a manifest file.
+///
+/// Packages in the manifest which aren't installed are installed, and the others are updated. A
+/// package installed from a different source than the manifest gives is reinstalled.
+#[derive(Debug, Parser)]
+pub struct SyncArgs {
+    /// The manifest file, see the documentation for the format.
+    #[arg(value_name = "FILE")]
+    manifest: PathBuf,
+
+    /// Also uninstall packages which aren't in the manifest.
+    #[arg(long)]
+    uninstall: bool,
+
+    /// Only show what would be done.
+    #[arg(short = 'n', long)]
+    dry_run: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Manifest {
+    /// Package specs, deserialized individually by [`Detailed::from_spec`] for better errors
+    packages: BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct Detailed {
+    version: Option<String>,
+    git: Option<String>,
+    branch: Option<String>,
+    tag: Option<String>,
+    rev: Option<String>,
+    path: Option<PathBuf>,
+    features: Vec<String>,
+    all_features: bool,
+    default_features: Option<bool>,
+    locked: bool,
+}
+
+impl Detailed {
+    /// Parse a package's spec, which is either a version string or a table
+    fn from_spec(name: &str, spec: serde_json::Value) -> Result<Self> {
+        match spec {
+            // "*" means any version, which is the same as not giving one
+            serde_json::Value::String(v) if v == "*" => Ok(Self::default()),
+            serde_json::Value::String(v) => Ok(Self { version: Some(v), ..Default::default() }),
+            spec => {
+                serde_json::from_value(spec).with_context(|| format!("Invalid spec for {name}"))
+            }
+        }
+    }
+
+    /// Resolve a path relative to the manifest's directory, or starting with "~/"
+    fn resolve_path(path: &Path, base: &Path) -> PathBuf {
+        match path.strip_prefix("~") {
+            Ok(rest) => dirs::home_dir().map_or_else(|| path.to_owned(), |home| home.join(rest)),
+            Err(_) => base.join(path),
+        }
+    }
+
+    /// Check the spec and work out which source it installs from
+    fn source(&self, name: &str, base: &Path) -> Result<PackageSource> {
+        ensure!(
+            self.git.is_none() || self.path.is_none(),
+            "{name}: git and path can't both be set"
+        );
+        ensure!(
+            self.git.is_some()
+                || (self.branch.is_none() && self.tag.is_none() && self.rev.is_none()),
+            "{name}: branch, tag, and rev can only be used with git"
+        );
+        ensure!(
+            self.version.is_none() || self.path.is_none(),
+            "{name}: version can't be used with path"
+        );
+        Ok(match (&self.git, &self.path) {
+            (Some(url), _) => PackageSource::Git {
+                url: url.clone(),
+                branch: self.branch.clone(),
+                tag: self.tag.clone(),
+                rev: self.rev.clone(),
+            },
+            (_, Some(path)) => PackageSource::Path(Self::resolve_path(path, base)),
+            _ => PackageSource::Registry(CRATES_IO_INDEX.to_owned()),
+        })
+    }
+
+    /// Build the `cargo install` arguments for this package
+    fn cargo_args(&self, name: &str, source: &PackageSource, force: bool) -> Vec<String> {
+        let mut args = vec!["install".to_owned()];
+        if force {
+            args.push_str("--force");
+        }
+        if self.locked {
+            args.push_str("--locked");
+        }
+        if let Some(v) = &self.version {
+            args.push_str("--version").push_str(v);
+        }
+        if !source.is_crates_io() {
+            source.add_cargo_args(&mut args);
+        }
+        if let Some(rev) = &self.rev {
+            args.push_str("--rev").push_str(rev);
+        }
+        if !self.features.is_empty() {
+            args.push_str("--features").push_str(self.features.join(","));
+        }
+        if self.all_features {
+            args.push_str("--all-features");
+        }
+        if self.default_features == Some(false) {
+            args.push_str("--no-default-features");
+        }
+        args.push_str(name);
+        args
+    }
+}
+
+/// Whether an installed package came from the source the manifest wants
+fn same_source(installed: &PackageSource, wanted: &PackageSource) -> bool {
+    match (installed, wanted) {
+        (PackageSource::Registry(a), PackageSource::Registry(b)) => a == b,
+        (PackageSource::Git { url: a, .. }, PackageSource::Git { url: b, .. }) => {
+            a.trim_end_matches(".git") == b.trim_end_matches(".git")
+        }
+        (PackageSource::Path(a), PackageSource::Path(b)) => {
+            a == b || fs::canonicalize(a).ok() == fs::canonicalize(b).ok()
+        }
+        _ => false,
+    }
+}
+
+fn load(path: &Path) -> Result<Manifest> {
+    let text =
+        fs::read_to_string(path).with_context(|| format!("Failed to read '{}'", path.display()))?;
+    let value =
+        toml::parse(&text).with_context(|| format!("Failed to parse '{}'", path.display()))?;
+    serde_json::from_value(value).with_context(|| format!("Invalid manifest '{}'", path.display()))
+}
+
+/// Run cargo with these arguments, returning whether it succeeded
+fn cargo(cargo_exe: &std::ffi::OsStr, args: &[String], dry_run: bool) -> Result<bool> {
+    dbgmsg!("{} {}", cargo_exe.to_string_lossy(), args.join(" "));
+    if dry_run {
+        return Ok(true);
+    }
+    let status = process::status(Command::new(cargo_exe).args(args))
+        .with_context(|| format!("Failed to execute `cargo {} ...`", args[0]))?;
+    Ok(status.success())
+}
+
+pub fn run(args: &SyncArgs, crates2: &Crates2) -> Result<()> {
+    let manifest = load(&args.manifest)?;
+    let base = args.manifest.parent().unwrap_or(Path::new("."));
+    let installed: BTreeMap<String, Package> = crates2
+        .installs
+        .keys()
+        .filter_map(|id| id.parse::<Package>().ok())
+        .map(|pkg| (pkg.name.clone(), pkg))
+        .collect();
+
+    // check the whole manifest before doing anything
+    let mut wanted = Vec::new();
+    for (name, spec) in manifest.packages {
+        let spec = Detailed::from_spec(&name, spec)?;
+        let source = spec.source(&name, base)?;
+        wanted.push((name, spec, source));
+    }
+
+    let cargo_exe = env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let mut install_flags = InstallFlags::load()?;
+    let mut failed = Vec::new();
+    let mut installed_now = Vec::new();
+    for (name, spec, source) in &wanted {
+        let force = match installed.get(name) {
+            None => {
+                msg!("Installing {name}");
+                false
+            }
+            // cargo only reinstalls from a different source with --force
+            Some(pkg) if !same_source(&pkg.source, source) => {
+                msg!("Reinstalling {name} from {source} (installed from {})", pkg.source);
+                true
+            }
+            Some(_) => {
+                msg!("Updating {name}");
+                false
+            }
+        };
+        if cargo(&cargo_exe, &spec.cargo_args(name, source, force), args.dry_run)? {
+            install_flags.record(name, Flags { locked: Some(spec.locked), ..Flags::default() });
+            installed_now.push(name.as_str());
+        } else {
+            errmsg!("Error: failed to install '{name}'");
+            failed.push(name.as_str());
+        }
+        if process::interrupted() {
+            install_flags.save()?;
+            return Err(Failure::Interrupted.into());
+        }
+    }
+
+    let extra: Vec<&str> = installed
+        .keys()
+        .map(String::as_str)
+        .filter(|name| !wanted.iter().any(|(n, ..)| n == name))
+        .collect();
+    if !extra.is_empty() && !args.uninstall {
+        msg!("Not in the manifest: {} (use --uninstall to remove them)", extra.join(", "));
+    } else if args.uninstall {
+        for name in extra {
+            msg!("Uninstalling {name}");
+            if cargo(&cargo_exe, &["uninstall".into(), name.into()], args.dry_run)? {
+                install_flags.remove(name);
+            } else {
+                errmsg!("Error: failed to uninstall '{name}'");
+                failed.push(name);
+            }
+        }
+    }
+    if !args.dry_run {
+        install_flags.save()?;
+        if let Err(e) = verify::record(&installed_now) {
+            warnmsg!("Warning: failed to record program hashes: {e:#}");
+        }
+    }
+
+    if !failed.is_empty() {
+        bail!("Failed to sync some packages: {}", failed.join(", "));
+    }
+    Ok(())
+}
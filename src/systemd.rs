@@ -0,0 +1,149 @@
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Parser;
+
+use crate::package_data::cargo_home;
+
+const UNIT_NAME: &str = "cargo-update-installed";
+
+/// Generate a systemd user service and timer which periodically run the update.
+///
+/// The service runs this program with the same update options given before the `systemd`
+/// subcommand, e.g. `cargo update-installed --locked -e 'cargo-*' systemd --enable`.
+#[derive(Debug, Parser)]
+pub struct SystemdArgs {
+    /// When to run the update, as a systemd OnCalendar expression.
+    #[arg(long, value_name = "SPEC", default_value = "weekly")]
+    on_calendar: String,
+
+    /// Directory to write the units to [default: ~/.config/systemd/user]
+    #[arg(long, value_name = "DIR")]
+    dir: Option<PathBuf>,
+
+    /// Print the units rather than writing them.
+    #[arg(long, conflicts_with = "enable")]
+    print: bool,
+
+    /// After writing the units, reload systemd and enable and start the timer.
+    #[arg(long)]
+    enable: bool,
+}
+
+/// Quote an argument for a systemd ExecStart line and escape specifiers and variable expansion
+fn quote(arg: &str) -> String {
+    let arg = arg.replace('%', "%%").replace('$', "$$");
+    if !arg.is_empty() && !arg.contains(|c: char| c.is_whitespace() || "\"'\\;".contains(c)) {
+        return arg;
+    }
+    let mut s = String::from("\"");
+    for c in arg.chars() {
+        if c == '"' || c == '\\' {
+            s.push('\\');
+        }
+        s.push(c);
+    }
+    s.push('"');
+    s
+}
+
+fn systemctl(args: &[&str]) -> Result<()> {
+    let status = Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .status()
+        .context("Failed to run systemctl")?;
+    if !status.success() {
+        bail!("`systemctl --user {}` failed", args.join(" "));
+    }
+    Ok(())
+}
+
+/// Write (or print) the units. `update_args` are the command-line options for the update itself.
+pub fn run(args: &SystemdArgs, update_args: &[String]) -> Result<()> {
+    let exe = env::current_exe().context("Failed to find the path of this program")?;
+    let exe =
+        exe.to_str().ok_or_else(|| anyhow!("Program path '{}' isn't UTF-8", exe.display()))?;
+    let bin_dir = cargo_home()?.join("bin");
+
+    let mut exec_start = quote(exe);
+    for arg in update_args {
+        write!(exec_start, " {}", quote(arg)).unwrap();
+    }
+
+    let mut service = String::new();
+    writeln!(service, "[Unit]\nDescription=Update packages installed by cargo\n").unwrap();
+    writeln!(service, "[Service]\nType=oneshot").unwrap();
+    // user services get a minimal PATH which usually doesn't include cargo
+    let mut path = env::var("PATH").unwrap_or_else(|_| "/usr/local/bin:/usr/bin:/bin".into());
+    if !env::split_paths(&path).any(|p| p == bin_dir) {
+        path = format!("{}:{path}", bin_dir.display());
+    }
+    writeln!(service, "Environment={}", quote(&format!("PATH={path}"))).unwrap();
+    if let Some(home) = env::var_os("CARGO_HOME") {
+        let home = format!("CARGO_HOME={}", home.to_string_lossy());
+        writeln!(service, "Environment={}", quote(&home)).unwrap();
+    }
+    writeln!(service, "ExecStart={exec_start}").unwrap();
+
+    let timer = format!(
+        "[Unit]\nDescription=Periodically update packages installed by cargo\n\n\
+         [Timer]\nOnCalendar={}\nPersistent=true\n\n\
+         [Install]\nWantedBy=timers.target\n",
+        args.on_calendar
+    );
+
+    if args.print {
+        println!("# {UNIT_NAME}.service\n{service}\n# {UNIT_NAME}.timer\n{timer}");
+        return Ok(());
+    }
+
+    let dir = match &args.dir {
+        Some(dir) => dir.clone(),
+        None => dirs::config_dir()
+            .ok_or_else(|| anyhow!("Unable to find the user config directory"))?
+            .join("systemd/user"),
+    };
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create '{}'", dir.display()))?;
+    for (ext, contents) in [("service", &service), ("timer", &timer)] {
+        let path = dir.join(format!("{UNIT_NAME}.{ext}"));
+        fs::write(&path, contents)
+            .with_context(|| format!("Failed to write '{}'", path.display()))?;
+        msg!("Wrote {}", path.display());
+    }
+
+    if args.enable {
+        systemctl(&["daemon-reload"])?;
+        systemctl(&["enable", "--now", &format!("{UNIT_NAME}.timer")])?;
+        msg!("Enabled {UNIT_NAME}.timer");
+    } else {
+        msg!(
+            "Enable it with `systemctl --user daemon-reload && \
+             systemctl --user enable --now {UNIT_NAME}.timer`"
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoting() {
+        assert_eq!(quote("--jobs"), "--jobs");
+        assert_eq!(quote("cargo-*"), "cargo-*");
+        assert_eq!(quote(""), "\"\"");
+        assert_eq!(quote("a b"), "\"a b\"");
+        assert_eq!(quote(r#"say "hi"\n"#), r#""say \"hi\"\\n""#);
+        assert_eq!(quote("it's"), "\"it's\"");
+        assert_eq!(quote("a;b"), "\"a;b\"");
+        // specifiers and variables are left for cargo-update-installed to see
+        assert_eq!(quote("100%"), "100%%");
+        assert_eq!(quote("$HOME/x"), "$$HOME/x");
+    }
+}
@@ -0,0 +1,153 @@
+//! The colors of messages, which can be changed in the `[colors]` table of the config file.
+//!
+//! A theme sets the color of each kind of message, and any of them can then be overridden, e.g.
+//! `theme = "light"` and `info = "black"`. Colors are termcolor's names ("black", "blue", "green",
+//! "red", "cyan", "magenta", "yellow", and "white", or "bright-" one of those), an ANSI 256-color
+//! number, "r,g,b", or "none" for the terminal's usual color. The messages about starting each
+//! update are colored by the kind of update, in colors which go with the theme.
+
+use std::str::FromStr;
+
+use once_cell::sync::OnceCell;
+use serde::Deserialize;
+use termcolor::{Color, ColorSpec};
+
+use crate::config::ColorsConfig;
+use crate::semver::Bump;
+
+/// The kinds of messages which have their own color
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Info,
+    Success,
+    Skip,
+    Warning,
+    Error,
+}
+
+/// A set of colors to start from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Preset {
+    /// Cyan messages, green for success, yellow warnings, and red errors
+    #[default]
+    Default,
+    /// Darker colors which can be read on a light background
+    Light,
+    /// Bright bold colors, except for plain info messages
+    HighContrast,
+    /// No colors at all
+    Plain,
+}
+
+/// A color from the config file, or None for the terminal's usual color
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct ColorName {
+    color: Option<Color>,
+    intense: bool,
+}
+
+impl TryFrom<String> for ColorName {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        if s == "none" {
+            return Ok(Self { color: None, intense: false });
+        }
+        let (name, intense) = match s.strip_prefix("bright-") {
+            Some(name) => (name, true),
+            None => (s.as_str(), false),
+        };
+        let color = Color::from_str(name).map_err(|e| format!("invalid color '{s}': {e}"))?;
+        Ok(Self { color: Some(color), intense })
+    }
+}
+
+const fn named(color: Color) -> ColorName {
+    ColorName { color: Some(color), intense: false }
+}
+
+const fn bright(color: Color) -> ColorName {
+    ColorName { color: Some(color), intense: true }
+}
+
+const NONE: ColorName = ColorName { color: None, intense: false };
+
+impl Preset {
+    /// The color of each style, and whether it's bold
+    fn colors(self, style: Style) -> (ColorName, bool) {
+        use Style::*;
+        match (self, style) {
+            (Self::Default, Info | Skip) => (named(Color::Cyan), false),
+            (Self::Default, Success) => (named(Color::Green), false),
+            (Self::Default, Warning) => (named(Color::Yellow), false),
+            (Self::Default, Error) => (named(Color::Red), false),
+            (Self::Light, Info) => (named(Color::Blue), false),
+            (Self::Light, Success) => (named(Color::Green), false),
+            (Self::Light, Skip) => (NONE, false),
+            (Self::Light, Warning) => (named(Color::Magenta), false),
+            (Self::Light, Error) => (named(Color::Red), false),
+            (Self::HighContrast, Info | Skip) => (NONE, true),
+            (Self::HighContrast, Success) => (bright(Color::Green), true),
+            (Self::HighContrast, Warning) => (bright(Color::Yellow), true),
+            (Self::HighContrast, Error) => (bright(Color::Red), true),
+            (Self::Plain, _) => (NONE, false),
+        }
+    }
+
+    /// The color of the messages about starting each kind of update, and whether they're bold
+    fn bump_colors(self, bump: Bump) -> (ColorName, bool) {
+        match (self, bump) {
+            (Self::Default | Self::Light, Bump::Patch) => (named(Color::Green), false),
+            (Self::Default, Bump::Minor) => (named(Color::Cyan), false),
+            (Self::Light, Bump::Minor) => (named(Color::Blue), false),
+            (Self::Default | Self::Light, Bump::Major) => (named(Color::Magenta), false),
+            (Self::HighContrast, Bump::Patch) => (bright(Color::Green), true),
+            (Self::HighContrast, Bump::Minor) => (bright(Color::Cyan), true),
+            (Self::HighContrast, Bump::Major) => (bright(Color::Magenta), true),
+            (Self::Plain, _) => (NONE, false),
+        }
+    }
+}
+
+/// The colors of each style, once the config file has been loaded
+static THEME: OnceCell<ColorsConfig> = OnceCell::new();
+
+/// Use the `[colors]` settings of the config file. Only the first call has any effect.
+pub fn configure(config: &ColorsConfig) {
+    THEME.set(config.clone()).ok();
+}
+
+/// The color and boldness to print a style of message with
+pub fn spec(style: Style) -> ColorSpec {
+    let config = THEME.get().cloned().unwrap_or_default();
+    let (mut color, mut bold) = config.theme.colors(style);
+    let custom = match style {
+        Style::Info => config.info,
+        Style::Success => config.success,
+        Style::Skip => config.skip,
+        Style::Warning => config.warning,
+        Style::Error => config.error,
+    };
+    color = custom.unwrap_or(color);
+    bold = config.bold.unwrap_or(bold);
+    let mut spec = ColorSpec::new();
+    spec.set_fg(color.color).set_intense(color.intense).set_bold(bold);
+    spec
+}
+
+/// The color and boldness to print the start of an update with, which depends on the kind of
+/// update so that major ones stand out
+pub fn bump_spec(bump: Bump) -> ColorSpec {
+    let config = THEME.get().cloned().unwrap_or_default();
+    let (color, bold) = config.theme.bump_colors(bump);
+    let mut spec = ColorSpec::new();
+    spec.set_fg(color.color).set_intense(color.intense).set_bold(config.bold.unwrap_or(bold));
+    spec
+}
+
+/// Whether the theme uses colors at all, for output which has colors of its own
+pub fn colors_enabled() -> bool {
+    THEME.get().is_none_or(|config| config.theme != Preset::Plain)
+}
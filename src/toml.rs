@@ -0,0 +1,486 @@
+//! A small TOML parser, enough for our config file.
+//!
+//! Documents are parsed into a [`serde_json::Value`] so they can be deserialized into structs with
+//! serde. Everything except dates and times is supported: tables, arrays of tables, dotted keys,
+//! inline tables, arrays, all four kinds of strings, integers, floats, and booleans.
+
+use anyhow::{anyhow, bail, Result};
+use serde_json::{Map, Value};
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+    lines: Lines,
+}
+
+/// The lines where the keys and table headers of a document are, for pointing at a setting in
+/// error messages
+#[derive(Debug, Default)]
+pub struct Lines(Vec<(Vec<String>, usize)>);
+
+impl Lines {
+    /// The line of a key, or else of the closest table containing it
+    pub fn line(&self, path: &[String]) -> Option<usize> {
+        (0..=path.len())
+            .rev()
+            .find_map(|len| self.0.iter().find(|(p, _)| p == &path[..len]).map(|(_, line)| *line))
+    }
+}
+
+/// Parse a TOML document
+pub fn parse(src: &str) -> Result<Value> {
+    parse_with_lines(src).map(|(value, _)| value)
+}
+
+/// Parse a TOML document, and remember where its keys are
+pub fn parse_with_lines(src: &str) -> Result<(Value, Lines)> {
+    let mut p = Parser { src, pos: 0, lines: Lines::default() };
+    let value = p.document().map_err(|e| anyhow!("line {}: {e}", p.line()))?;
+    Ok((value, p.lines))
+}
+
+impl Parser<'_> {
+    fn line(&self) -> usize {
+        self.src[..self.pos].matches('\n').count() + 1
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.src[self.pos..].chars().next()
+    }
+
+    fn rest(&self) -> &str {
+        &self.src[self.pos..]
+    }
+
+    fn eat(&mut self, s: &str) -> bool {
+        if self.rest().starts_with(s) {
+            self.pos += s.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, s: &str) -> Result<()> {
+        if !self.eat(s) {
+            bail!("expected '{s}'");
+        }
+        Ok(())
+    }
+
+    /// Skip spaces and tabs within a line
+    fn skip_ws(&mut self) {
+        while let Some(' ' | '\t') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    /// Skip whitespace, newlines, and comments
+    fn skip_ws_lines(&mut self) {
+        loop {
+            self.skip_ws();
+            if self.eat("\n") || self.eat("\r\n") {
+                continue;
+            }
+            if self.peek() == Some('#') {
+                self.skip_comment();
+                continue;
+            }
+            return;
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        let len = self.rest().find('\n').unwrap_or(self.rest().len());
+        self.pos += len;
+    }
+
+    /// After a key/value pair or table header, allow only a comment before the newline
+    fn end_of_line(&mut self) -> Result<()> {
+        self.skip_ws();
+        if self.peek() == Some('#') {
+            self.skip_comment();
+        }
+        if self.peek().is_none() || self.eat("\n") || self.eat("\r\n") {
+            Ok(())
+        } else {
+            bail!("expected a newline after value")
+        }
+    }
+
+    fn document(&mut self) -> Result<Value> {
+        let mut root = Map::new();
+        // path of the table which key/value pairs are currently added to
+        let mut current: Vec<String> = Vec::new();
+        loop {
+            self.skip_ws_lines();
+            if self.peek().is_none() {
+                return Ok(Value::Object(root));
+            }
+            if self.eat("[[") {
+                self.skip_ws();
+                current = self.key()?;
+                self.skip_ws();
+                self.expect("]]")?;
+                let (last, parents) = current.split_last().unwrap();
+                let parent = table_at(&mut root, parents)?;
+                let entry = parent.entry(last.clone()).or_insert_with(|| Value::Array(Vec::new()));
+                let Value::Array(arr) = entry else {
+                    bail!(
+                        "'{}' is already defined and isn't an array of tables",
+                        current.join(".")
+                    );
+                };
+                arr.push(Value::Object(Map::new()));
+                self.lines.0.push((current.clone(), self.line()));
+            } else if self.eat("[") {
+                self.skip_ws();
+                current = self.key()?;
+                self.skip_ws();
+                self.expect("]")?;
+                table_at(&mut root, &current)?;
+                self.lines.0.push((current.clone(), self.line()));
+            } else {
+                let line = self.line();
+                let table = table_at(&mut root, &current)?;
+                let key = self.key_value(table)?;
+                self.lines.0.push(([current.as_slice(), &key].concat(), line));
+            }
+            self.end_of_line()?;
+        }
+    }
+
+    /// Parse `key = value` and insert it into a table, returning the key
+    fn key_value(&mut self, table: &mut Map<String, Value>) -> Result<Vec<String>> {
+        let key = self.key()?;
+        self.skip_ws();
+        self.expect("=")?;
+        self.skip_ws();
+        let value = self.value()?;
+        let (last, parents) = key.split_last().unwrap();
+        let table = table_at(table, parents)?;
+        if table.contains_key(last) {
+            bail!("duplicate key '{}'", key.join("."));
+        }
+        table.insert(last.clone(), value);
+        Ok(key)
+    }
+
+    /// Parse a possibly dotted key
+    fn key(&mut self) -> Result<Vec<String>> {
+        let mut parts = Vec::new();
+        loop {
+            let part = match self.peek() {
+                Some('"') => self.basic_string()?,
+                Some('\'') => self.literal_string()?,
+                _ => {
+                    let len = self
+                        .rest()
+                        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+                        .unwrap_or(self.rest().len());
+                    if len == 0 {
+                        bail!("expected a key");
+                    }
+                    let part = self.rest()[..len].to_owned();
+                    self.pos += len;
+                    part
+                }
+            };
+            parts.push(part);
+            self.skip_ws();
+            if !self.eat(".") {
+                return Ok(parts);
+            }
+            self.skip_ws();
+        }
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        match self.peek() {
+            Some('"') if self.rest().starts_with("\"\"\"") => {
+                self.ml_basic_string().map(Value::from)
+            }
+            Some('"') => self.basic_string().map(Value::from),
+            Some('\'') if self.rest().starts_with("'''") => {
+                self.ml_literal_string().map(Value::from)
+            }
+            Some('\'') => self.literal_string().map(Value::from),
+            Some('[') => self.array(),
+            Some('{') => self.inline_table(),
+            Some(_) => self.scalar(),
+            None => bail!("expected a value"),
+        }
+    }
+
+    fn array(&mut self) -> Result<Value> {
+        self.expect("[")?;
+        let mut items = Vec::new();
+        loop {
+            self.skip_ws_lines();
+            if self.eat("]") {
+                return Ok(Value::Array(items));
+            }
+            items.push(self.value()?);
+            self.skip_ws_lines();
+            if !self.eat(",") {
+                self.skip_ws_lines();
+                self.expect("]")?;
+                return Ok(Value::Array(items));
+            }
+        }
+    }
+
+    fn inline_table(&mut self) -> Result<Value> {
+        self.expect("{")?;
+        let mut table = Map::new();
+        self.skip_ws();
+        if self.eat("}") {
+            return Ok(Value::Object(table));
+        }
+        loop {
+            self.skip_ws();
+            self.key_value(&mut table)?;
+            self.skip_ws();
+            if self.eat("}") {
+                return Ok(Value::Object(table));
+            }
+            self.expect(",")?;
+        }
+    }
+
+    /// Booleans and numbers
+    fn scalar(&mut self) -> Result<Value> {
+        let len = self
+            .rest()
+            .find(|c: char| c.is_whitespace() || matches!(c, ',' | ']' | '}' | '#'))
+            .unwrap_or(self.rest().len());
+        let word = &self.rest()[..len];
+        let value = match word {
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            _ => parse_number(word).ok_or_else(|| anyhow!("invalid value '{word}'"))?,
+        };
+        self.pos += len;
+        Ok(value)
+    }
+
+    fn basic_string(&mut self) -> Result<String> {
+        self.expect("\"")?;
+        let mut out = String::new();
+        loop {
+            match self.peek() {
+                None | Some('\n') => bail!("unterminated string"),
+                Some('"') => {
+                    self.pos += 1;
+                    return Ok(out);
+                }
+                Some('\\') => out.push(self.escape()?),
+                Some(c) => {
+                    out.push(c);
+                    self.pos += c.len_utf8();
+                }
+            }
+        }
+    }
+
+    fn ml_basic_string(&mut self) -> Result<String> {
+        self.expect("\"\"\"")?;
+        // a newline right after the opening quotes is trimmed
+        let _ = self.eat("\n") || self.eat("\r\n");
+        let mut out = String::new();
+        loop {
+            if self.eat("\"\"\"") {
+                // up to two extra quotes are allowed right before the closing delimiter
+                while self.eat("\"") {
+                    out.push('"');
+                }
+                return Ok(out);
+            }
+            match self.peek() {
+                None => bail!("unterminated string"),
+                Some('\\') => {
+                    let after = self.rest()[1..].trim_start_matches([' ', '\t']);
+                    if after.starts_with('\n') || after.starts_with("\r\n") {
+                        // line ending backslash: skip all whitespace up to the next text
+                        self.pos += 1;
+                        self.pos += self.rest().len() - self.rest().trim_start().len();
+                    } else {
+                        out.push(self.escape()?);
+                    }
+                }
+                Some(c) => {
+                    out.push(c);
+                    self.pos += c.len_utf8();
+                }
+            }
+        }
+    }
+
+    /// Parse a backslash escape sequence in a basic string
+    fn escape(&mut self) -> Result<char> {
+        self.expect("\\")?;
+        let c = self.peek().ok_or_else(|| anyhow!("unterminated string"))?;
+        self.pos += c.len_utf8();
+        let unicode_len = match c {
+            'b' => return Ok('\u{8}'),
+            't' => return Ok('\t'),
+            'n' => return Ok('\n'),
+            'f' => return Ok('\u{c}'),
+            'r' => return Ok('\r'),
+            '"' => return Ok('"'),
+            '\\' => return Ok('\\'),
+            'u' => 4,
+            'U' => 8,
+            c => bail!("invalid escape sequence '\\{c}'"),
+        };
+        let hex =
+            self.rest().get(..unicode_len).ok_or_else(|| anyhow!("invalid unicode escape"))?;
+        let c = u32::from_str_radix(hex, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| anyhow!("invalid unicode escape '\\{c}{hex}'"))?;
+        self.pos += unicode_len;
+        Ok(c)
+    }
+
+    fn literal_string(&mut self) -> Result<String> {
+        self.expect("'")?;
+        let len = self.rest().find(['\'', '\n']).ok_or_else(|| anyhow!("unterminated string"))?;
+        if self.rest()[len..].starts_with('\n') {
+            bail!("unterminated string");
+        }
+        let s = self.rest()[..len].to_owned();
+        self.pos += len + 1;
+        Ok(s)
+    }
+
+    fn ml_literal_string(&mut self) -> Result<String> {
+        self.expect("'''")?;
+        let _ = self.eat("\n") || self.eat("\r\n");
+        let len = self.rest().find("'''").ok_or_else(|| anyhow!("unterminated string"))?;
+        let mut s = self.rest()[..len].to_owned();
+        self.pos += len + 3;
+        while self.eat("'") {
+            s.push('\'');
+        }
+        Ok(s)
+    }
+}
+
+/// Find or create the table at a path of keys
+fn table_at<'a>(
+    root: &'a mut Map<String, Value>,
+    path: &[String],
+) -> Result<&'a mut Map<String, Value>> {
+    let mut table = root;
+    for key in path {
+        let entry = table.entry(key.clone()).or_insert_with(|| Value::Object(Map::new()));
+        table = match entry {
+            Value::Object(t) => t,
+            // keys under an array of tables go into its most recent element
+            Value::Array(arr) => match arr.last_mut() {
+                Some(Value::Object(t)) => t,
+                _ => bail!("'{key}' is not a table"),
+            },
+            _ => bail!("'{key}' is not a table"),
+        };
+    }
+    Ok(table)
+}
+
+fn parse_number(word: &str) -> Option<Value> {
+    // underscores are only allowed between digits
+    if word.starts_with('_') || word.ends_with('_') || word.contains("__") {
+        return None;
+    }
+    let clean = word.replace('_', "");
+    let (sign, digits) = match clean.strip_prefix('-') {
+        Some(d) => (-1, d),
+        None => (1, clean.strip_prefix('+').unwrap_or(&clean)),
+    };
+    for (prefix, radix) in [("0x", 16), ("0o", 8), ("0b", 2)] {
+        if let Some(d) = digits.strip_prefix(prefix) {
+            return i64::from_str_radix(d, radix).ok().map(|n| (sign * n).into());
+        }
+    }
+    // this also rejects inf and nan, which JSON can't represent
+    if !digits.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    if let Ok(n) = clean.parse::<i64>() {
+        return Some(n.into());
+    }
+    clean.parse::<f64>().ok().and_then(serde_json::Number::from_f64).map(Value::Number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn escapes() {
+        let doc = parse(r#"s = "a\tb\n\"c\" \\ \u00e9 \U0001F600""#).unwrap();
+        assert_eq!(doc["s"], "a\tb\n\"c\" \\ \u{e9} \u{1F600}");
+        assert!(parse(r#"s = "\q""#).is_err());
+        assert!(parse(r#"s = "\u12""#).is_err());
+        assert!(parse(r#"s = "\uD800""#).is_err());
+    }
+
+    #[test]
+    fn invalid_escape_of_multibyte_char() {
+        let err = parse(r#"x = "\é""#).unwrap_err();
+        assert_eq!(err.to_string(), r"line 1: invalid escape sequence '\é'");
+    }
+
+    #[test]
+    fn multi_line_strings() {
+        let doc = parse(concat!(
+            "a = \"\"\"\none\ntwo\"\"\"\n",
+            "b = \"\"\"one \\\n    two\"\"\"\n",
+            "c = '''\nraw \\n'''\n",
+            "d = \"\"\"quoted\"\"\"\"\"\n",
+        ))
+        .unwrap();
+        assert_eq!(doc["a"], "one\ntwo");
+        assert_eq!(doc["b"], "one two");
+        assert_eq!(doc["c"], "raw \\n");
+        assert_eq!(doc["d"], "quoted\"\"");
+        assert!(parse("a = \"\"\"never closed").is_err());
+    }
+
+    #[test]
+    fn dotted_keys() {
+        let doc = parse("a.b = 1\n\"quoted.key\".c = 2\n[t]\nx . y = true\n").unwrap();
+        assert_eq!(
+            doc,
+            json!({ "a": { "b": 1 }, "quoted.key": { "c": 2 }, "t": { "x": { "y": true } } })
+        );
+        assert!(parse("a = 1\na.b = 2\n").is_err());
+    }
+
+    #[test]
+    fn arrays_of_tables() {
+        let doc =
+            parse("[[bin]]\nname = \"a\"\n[[bin]]\nname = \"b\"\n[bin.extra]\nx = 1\n").unwrap();
+        assert_eq!(doc, json!({ "bin": [{ "name": "a" }, { "name": "b", "extra": { "x": 1 } }] }));
+        assert!(parse("bin = 1\n[[bin]]\n").is_err());
+    }
+
+    #[test]
+    fn duplicate_keys() {
+        let err = parse("a = 1\nb = 2\na = 3\n").unwrap_err();
+        assert_eq!(err.to_string(), "line 3: duplicate key 'a'");
+        assert!(parse("t = { x = 1, x = 2 }").is_err());
+        assert!(parse("[t]\nx.y = 1\nx.y = 2\n").is_err());
+    }
+
+    #[test]
+    fn key_lines() {
+        let (_, lines) = parse_with_lines("a = 1\n\n[t]\nb = 2\n").unwrap();
+        let path = |p: &[&str]| p.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(lines.line(&path(&["a"])), Some(1));
+        assert_eq!(lines.line(&path(&["t", "b"])), Some(4));
+        assert_eq!(lines.line(&path(&["t", "missing"])), Some(3));
+    }
+}
@@ -0,0 +1,117 @@
+//! Simulating a toolchain upgrade for `outdated --simulate-rustc`, to see what it would change
+//! before doing it.
+//!
+//! Packages built with an older rustc than the new one are candidates for rebuilding with it. The
+//! crates.io index records the `rust-version` of each release, so the latest version which can be
+//! installed with the current rustc and with the new one can be compared.
+
+use anyhow::{Context, Result};
+
+use crate::http;
+use crate::index;
+use crate::list::print_table;
+use crate::package_data::*;
+use crate::semver::Version;
+use crate::util;
+
+/// The latest version which `rustc` can build, ignoring pre-releases unless `installed` is one
+fn latest_for(
+    versions: &[(Version, Option<Version>)],
+    installed: &Version,
+    rustc: &Version,
+) -> Option<Version> {
+    versions
+        .iter()
+        .filter(|(v, _)| !v.is_prerelease() || installed.is_prerelease())
+        .filter(|(_, needs)| needs.as_ref().is_none_or(|needs| needs <= rustc))
+        .map(|(v, _)| v.clone())
+        .max()
+}
+
+/// The version of rustc which cargo recorded building a package with. That's the output of
+/// `rustc -vV`, or just its first line without "rustc" in some older records.
+fn built_with(rustc: &str) -> Option<String> {
+    util::release_of(rustc).or_else(|| {
+        let first = rustc.lines().next()?;
+        first.trim_start_matches("rustc ").split_whitespace().next().map(str::to_owned)
+    })
+}
+
+fn or_dash(version: &Option<Version>) -> String {
+    version.as_ref().map_or("-".into(), Version::to_string)
+}
+
+pub fn run(crates2: &Crates2, simulated: &Version) -> Result<()> {
+    let current = util::rustc_version().and_then(|v| index::parse_rust_version(&v));
+    let current = current.context("Couldn't find the version of the current rustc")?;
+    msg!("Simulating Rust {simulated}, the current rustc is {current}");
+
+    let mut packages = Vec::new();
+    for (pkg_id, details) in &crates2.installs {
+        let pkg = pkg_id
+            .parse::<Package>()
+            .with_context(|| format!("Failed to parse package id '{pkg_id}'"))?;
+        packages.push((pkg, details));
+    }
+    index::prefetch(
+        packages.iter().filter(|(p, _)| p.source.is_crates_io()).map(|(p, _)| p.name.as_str()),
+    );
+    let versions = http::map_concurrent(&packages, |(pkg, _)| {
+        if !pkg.source.is_crates_io() {
+            return None;
+        }
+        index::rust_versions(&pkg.name)
+            .map_err(|e| warnmsg!("Warning: failed to check {}: {e:#}", pkg.name))
+            .ok()
+    });
+
+    let (mut rebuild, mut newer, mut older) = (0, 0, 0);
+    let mut rows = Vec::new();
+    for ((pkg, details), versions) in packages.iter().zip(versions) {
+        let built_with = built_with(&details.rustc);
+        let built_with_version = built_with.as_deref().and_then(|v| v.parse::<Version>().ok());
+        let mut changes = Vec::new();
+        if built_with_version.is_some_and(|v| v < *simulated) {
+            changes.push("rebuild".to_owned());
+            rebuild += 1;
+        }
+
+        let (now, then) = match (&versions, pkg.version.parse::<Version>()) {
+            (Some(versions), Ok(installed)) => (
+                latest_for(versions, &installed, &current),
+                latest_for(versions, &installed, simulated),
+            ),
+            _ => (None, None),
+        };
+        if then > now {
+            changes.push(format!("newly installable: {}", or_dash(&then)));
+            newer += 1;
+        } else if then < now {
+            changes.push(format!(
+                "needs a newer Rust than {simulated}, only up to {}",
+                or_dash(&then)
+            ));
+            older += 1;
+        }
+        rows.push(vec![
+            pkg.name.clone(),
+            pkg.version.clone(),
+            built_with.unwrap_or_else(|| "-".into()),
+            or_dash(&now),
+            or_dash(&then),
+            changes.join(", "),
+        ]);
+    }
+    let with = format!("LATEST WITH {simulated}");
+    print_table(&["NAME", "INSTALLED", "BUILT WITH", "LATEST NOW", &with, "CHANGE"], &rows, "");
+
+    let packages = |n: usize| if n == 1 { "1 package".into() } else { format!("{n} packages") };
+    msg!("{} would be candidates for rebuilding with Rust {simulated}", packages(rebuild));
+    if newer > 0 {
+        msg!("{} would have newer versions which can be installed", packages(newer));
+    }
+    if older > 0 {
+        warnmsg!("{} would be held back to older versions by their rust-version", packages(older));
+    }
+    Ok(())
+}
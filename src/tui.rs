@@ -0,0 +1,650 @@
+//! Interactive terminal UI for `--tui` mode.
+//!
+//! This is a deliberately small full-screen interface built directly on ANSI escape sequences and
+//! termios raw mode, so it's only available on Unix-like systems. It shows the list of packages
+//! with their current status, and the captured `cargo install` output of the selected package and
+//! of the others being installed in parallel, each in its own pane. The jobs are run by the usual
+//! worker pool, which reports to the UI as a [`Reporter`] and is told which packages were skipped.
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::mem::MaybeUninit;
+use std::os::unix::io::FromRawFd;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use anyhow::{ensure, Context, Result};
+use is_terminal::IsTerminal;
+
+use crate::process;
+use crate::report::{JobResult, Outcome};
+use crate::reporter::Reporter;
+use crate::util::format_duration;
+use crate::Job;
+
+/// How often to redraw the screen when nothing happens
+const TICK: Duration = Duration::from_millis(100);
+
+const HELP: &str = "↑/↓ select  PgUp/PgDn scroll  End follow  s skip  r retry  q abort/quit";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Pending,
+    Running,
+    Succeeded,
+    Failed,
+    Skipped,
+}
+
+impl Status {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Running => "running",
+            Self::Succeeded => "ok",
+            Self::Failed => "FAILED",
+            Self::Skipped => "skipped",
+        }
+    }
+
+    /// SGR color code for this status
+    fn color(self) -> &'static str {
+        match self {
+            Self::Pending => "2",
+            Self::Running => "1;36",
+            Self::Succeeded => "32",
+            Self::Failed => "1;31",
+            Self::Skipped => "33",
+        }
+    }
+}
+
+enum Key {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    End,
+    Skip,
+    Retry,
+    Quit,
+}
+
+enum Event {
+    Key(Key),
+    Started(String),
+    Retrying(String, String),
+    Output(String, String),
+    Finished(String, Status),
+    /// The worker pool is done with the jobs it was given
+    Done(Result<Vec<JobResult>>),
+}
+
+/// Passes what happens to the jobs on to the main loop, and tells the worker pool which packages
+/// were skipped
+struct Channel {
+    tx: Sender<Event>,
+    cancelled: Mutex<HashSet<String>>,
+}
+
+impl Channel {
+    fn send(&self, event: Event) {
+        // the main loop only goes away once the jobs are done
+        let _ = self.tx.send(event);
+    }
+
+    fn set_cancelled(&self, name: &str, cancel: bool) {
+        let mut cancelled = self.cancelled.lock().unwrap();
+        if cancel {
+            cancelled.insert(name.to_owned());
+        } else {
+            cancelled.remove(name);
+        }
+    }
+}
+
+impl Reporter for Channel {
+    fn started(&self, job: &Job) {
+        self.send(Event::Started(job.name.clone()));
+    }
+
+    fn retrying(&self, job: &Job, flag: &str) {
+        self.send(Event::Retrying(job.name.clone(), flag.to_owned()));
+    }
+
+    fn output(&self, name: &str, data: &[u8]) {
+        let line = String::from_utf8_lossy(data);
+        self.send(Event::Output(name.to_owned(), line.trim_end_matches(['\r', '\n']).to_owned()));
+    }
+
+    fn shows_output(&self) -> bool {
+        true
+    }
+
+    fn cancelled(&self, name: &str) -> bool {
+        self.cancelled.lock().unwrap().contains(name)
+    }
+
+    fn finished(&self, result: &JobResult) {
+        let status =
+            if result.outcome == Outcome::Updated { Status::Succeeded } else { Status::Skipped };
+        self.send(Event::Finished(result.name.clone(), status));
+    }
+
+    fn failed(&self, result: &JobResult) {
+        self.send(Event::Finished(result.name.clone(), Status::Failed));
+    }
+
+    fn skipped(&self, result: &JobResult) {
+        self.send(Event::Finished(result.name.clone(), Status::Skipped));
+    }
+}
+
+struct Entry {
+    job: Job,
+    status: Status,
+    output: Vec<String>,
+    /// When its `cargo install` started, while it's running
+    started: Option<Instant>,
+    /// Whether the worker pool has it and hasn't finished with it yet
+    queued: bool,
+    /// The result of the last time it was run
+    result: Option<JobResult>,
+}
+
+struct App {
+    entries: Vec<Entry>,
+    /// How many packages are installed at once, and so how many output panes there can be
+    slots: u32,
+    selected: usize,
+    /// Whether the selection tracks the package started last. Cleared once the user moves it.
+    follow_selection: bool,
+    /// How many lines the selected package's output is scrolled up from the bottom
+    scroll: usize,
+    /// The entries given to the worker pool, while it's running
+    round: Option<Vec<usize>>,
+    quit: bool,
+}
+
+impl App {
+    fn find(&self, name: &str) -> Option<usize> {
+        self.entries.iter().position(|e| e.job.name == name)
+    }
+
+    fn count(&self, status: Status) -> usize {
+        self.entries.iter().filter(|e| e.status == status).count()
+    }
+
+    fn select(&mut self, idx: usize) {
+        if idx != self.selected {
+            self.selected = idx;
+            self.scroll = 0;
+        }
+    }
+
+    /// Skip an entry, stopping it if it's running
+    fn skip(&mut self, idx: usize, channel: &Channel) {
+        let entry = &mut self.entries[idx];
+        if matches!(entry.status, Status::Pending | Status::Running) {
+            entry.status = Status::Skipped;
+            if entry.queued {
+                channel.set_cancelled(&entry.job.name, true);
+            }
+        }
+    }
+
+    fn handle_key(&mut self, key: Key, page: usize, channel: &Channel) {
+        match key {
+            Key::Up => {
+                self.follow_selection = false;
+                self.select(self.selected.saturating_sub(1));
+            }
+            Key::Down => {
+                self.follow_selection = false;
+                self.select((self.selected + 1).min(self.entries.len() - 1));
+            }
+            Key::PageUp => {
+                let max = self.entries[self.selected].output.len().saturating_sub(1);
+                self.scroll = (self.scroll + page).min(max);
+            }
+            Key::PageDown => self.scroll = self.scroll.saturating_sub(page),
+            Key::End => {
+                self.scroll = 0;
+                self.follow_selection = true;
+            }
+            Key::Skip => self.skip(self.selected, channel),
+            Key::Retry => {
+                let entry = &mut self.entries[self.selected];
+                if matches!(entry.status, Status::Failed | Status::Skipped) {
+                    if entry.queued {
+                        // skipped but not finished with yet, so the worker pool can still run it
+                        channel.set_cancelled(&entry.job.name, false);
+                        entry.status =
+                            if entry.started.is_some() { Status::Running } else { Status::Pending };
+                    } else {
+                        entry.status = Status::Pending;
+                        entry.output.clear();
+                    }
+                }
+            }
+            Key::Quit => {
+                for idx in 0..self.entries.len() {
+                    self.skip(idx, channel);
+                }
+                self.quit = true;
+            }
+        }
+    }
+
+    fn handle_event(&mut self, event: Event, page: usize, channel: &Channel) -> Result<()> {
+        match event {
+            Event::Key(key) => self.handle_key(key, page, channel),
+            Event::Started(name) => {
+                let Some(idx) = self.find(&name) else { return Ok(()) };
+                let entry = &mut self.entries[idx];
+                entry.started = Some(Instant::now());
+                if entry.status == Status::Pending {
+                    entry.status = Status::Running;
+                }
+                if self.follow_selection {
+                    self.select(idx);
+                }
+            }
+            Event::Retrying(name, flag) => {
+                let Some(idx) = self.find(&name) else { return Ok(()) };
+                let message = if flag == "--force" {
+                    "Programs are still missing, trying again with --force".to_owned()
+                } else {
+                    format!("Failed, trying again with {flag}")
+                };
+                self.entries[idx].output.push(message);
+            }
+            Event::Output(name, line) => {
+                if let Some(idx) = self.find(&name) {
+                    self.entries[idx].output.push(line);
+                }
+            }
+            Event::Finished(name, status) => {
+                let Some(idx) = self.find(&name) else { return Ok(()) };
+                let entry = &mut self.entries[idx];
+                entry.started = None;
+                entry.queued = false;
+                entry.status = status;
+            }
+            Event::Done(results) => {
+                let round = self.round.take().unwrap_or_default();
+                for (idx, result) in round.into_iter().zip(results?) {
+                    let entry = &mut self.entries[idx];
+                    // in case the worker pool didn't report on it
+                    if entry.queued {
+                        entry.queued = false;
+                        entry.started = None;
+                        entry.status = Status::Skipped;
+                    }
+                    entry.result = Some(result);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Hand the pending entries which haven't been yet to the worker pool, if it isn't running
+    fn next_round(&mut self, channel: &Channel) -> Option<Vec<Job>> {
+        if self.round.is_some() || self.quit {
+            return None;
+        }
+        let round: Vec<usize> = (0..self.entries.len())
+            .filter(|&idx| self.entries[idx].status == Status::Pending)
+            .collect();
+        if round.is_empty() {
+            return None;
+        }
+        let jobs = round
+            .iter()
+            .map(|&idx| {
+                let entry = &mut self.entries[idx];
+                entry.queued = true;
+                channel.set_cancelled(&entry.job.name, false);
+                entry.job.clone()
+            })
+            .collect();
+        self.round = Some(round);
+        Some(jobs)
+    }
+
+    /// Estimate how much longer the remaining packages will take, from their install history
+    fn remaining(&self) -> Option<Duration> {
+        let estimates = self.entries.iter().filter_map(|e| {
+            let est = e.job.estimate?;
+            match (e.status, e.started) {
+                (Status::Pending, _) => Some(est),
+                (Status::Running, Some(started)) => Some(est.saturating_sub(started.elapsed())),
+                _ => None,
+            }
+        });
+        let total = crate::makespan(estimates, self.slots);
+        (!total.is_zero()).then_some(total)
+    }
+
+    /// The entries to show the output of: the selected one, then the others which are running
+    fn panes(&self) -> Vec<usize> {
+        let running = (0..self.entries.len())
+            .filter(|&idx| idx != self.selected && self.entries[idx].status == Status::Running);
+        let mut panes: Vec<usize> = std::iter::once(self.selected).chain(running).collect();
+        panes.truncate(self.slots.max(1) as usize);
+        panes
+    }
+
+    fn draw(&self, out: &mut impl Write) -> io::Result<()> {
+        let (rows, cols) = terminal_size();
+        // layout: title, package list, an output pane with a separator for each package shown,
+        // help line
+        let list_height = self.entries.len().min((rows / 3).max(1));
+        let panes = self.panes();
+        let pane_rows = rows.saturating_sub(list_height + 2);
+        let mut row = 1..;
+        let mut row = || row.next().unwrap();
+
+        let mut title = format!(
+            "cargo update-installed: {} packages, {} updated, {} failed, {} skipped",
+            self.entries.len(),
+            self.count(Status::Succeeded),
+            self.count(Status::Failed),
+            self.count(Status::Skipped),
+        );
+        if let Some(remaining) = self.remaining() {
+            title += &format!(", about {} left", format_duration(remaining));
+        }
+        put_line(out, row(), &title, "1", cols)?;
+
+        // scroll the package list so that the selected entry is visible
+        let first = (self.selected + 1).saturating_sub(list_height);
+        for (idx, entry) in self.entries.iter().enumerate().skip(first).take(list_height) {
+            let marker = if idx == self.selected { '>' } else { ' ' };
+            let (color, label) = (entry.status.color(), entry.status.label());
+            write!(out, "\x1b[{};1H{marker} \x1b[{color}m{label:>7}\x1b[0m ", row())?;
+            let mut text = format!("{} {}", entry.job.name, entry.job.version);
+            let elapsed = entry.started.filter(|_| entry.status == Status::Running);
+            match (elapsed.map(|s| s.elapsed()), entry.job.estimate) {
+                (Some(elapsed), Some(est)) => {
+                    text += &format!("  {} / ~{}", format_duration(elapsed), format_duration(est))
+                }
+                (Some(elapsed), None) => text += &format!("  {}", format_duration(elapsed)),
+                (None, Some(est)) if entry.status == Status::Pending => {
+                    text += &format!("  ~{}", format_duration(est))
+                }
+                _ => (),
+            }
+            write!(out, "{}\x1b[K", truncate(&text, cols.saturating_sub(10)))?;
+        }
+
+        for (i, &idx) in panes.iter().enumerate() {
+            // the first pane gets whatever doesn't divide evenly
+            let height = pane_rows / panes.len() + if i == 0 { pane_rows % panes.len() } else { 0 };
+            let entry = &self.entries[idx];
+            let sep = format!("── {} ", entry.job.name);
+            let sep = format!("{sep}{}", "─".repeat(cols.saturating_sub(sep.chars().count())));
+            // the selected package's separator stands out
+            put_line(out, row(), &sep, if i == 0 { "1" } else { "2" }, cols)?;
+
+            let body = height.saturating_sub(1);
+            let scroll = if i == 0 { self.scroll } else { 0 };
+            let end = entry.output.len().saturating_sub(scroll);
+            let start = end.saturating_sub(body);
+            for line in 0..body {
+                let text = entry.output[start..end].get(line).map_or("", |s| s.as_str());
+                put_line(out, row(), text, "0", cols)?;
+            }
+        }
+
+        let help = if self.round.is_none() && self.count(Status::Pending) == 0 {
+            format!("All done. {HELP}")
+        } else {
+            HELP.to_owned()
+        };
+        put_line(out, row(), &help, "7", cols)?;
+        out.flush()
+    }
+}
+
+/// Draw one line of text at the given row, clearing anything after it
+fn put_line(
+    out: &mut impl Write,
+    row: usize,
+    text: &str,
+    sgr: &str,
+    cols: usize,
+) -> io::Result<()> {
+    write!(out, "\x1b[{row};1H\x1b[{sgr}m{}\x1b[0m\x1b[K", truncate(text, cols))
+}
+
+fn truncate(s: &str, width: usize) -> String {
+    s.chars().map(|c| if c == '\t' { ' ' } else { c }).take(width).collect()
+}
+
+/// Get the terminal size as (rows, columns)
+fn terminal_size() -> (usize, usize) {
+    let mut ws = MaybeUninit::<libc::winsize>::zeroed();
+    // SAFETY: TIOCGWINSZ writes a winsize struct to the provided pointer
+    let ret = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, ws.as_mut_ptr()) };
+    // SAFETY: the struct was zero-initialized, so it's valid even if the ioctl failed
+    let ws = unsafe { ws.assume_init() };
+    if ret == 0 && ws.ws_row > 0 && ws.ws_col > 0 {
+        (ws.ws_row.into(), ws.ws_col.into())
+    } else {
+        (24, 80)
+    }
+}
+
+/// RAII guard which puts the terminal in raw mode on the alternate screen, and restores it on drop.
+/// Messages written to stderr in the meantime, e.g. warnings, would scribble over the screen, so
+/// stderr goes to a pipe instead and whatever was written to it is printed once it's restored.
+struct RawTerminal {
+    orig: libc::termios,
+    /// The original stderr
+    stderr: libc::c_int,
+    stderr_reader: Option<JoinHandle<Vec<u8>>>,
+}
+
+impl RawTerminal {
+    fn enter() -> io::Result<Self> {
+        let mut orig = MaybeUninit::<libc::termios>::uninit();
+        // SAFETY: tcgetattr initializes orig when it returns 0
+        let orig = unsafe {
+            if libc::tcgetattr(libc::STDIN_FILENO, orig.as_mut_ptr()) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            orig.assume_init()
+        };
+        let (stderr, mut pipe) = redirect_stderr()?;
+        let stderr_reader = thread::spawn(move || {
+            let mut data = Vec::new();
+            let _ = pipe.read_to_end(&mut data);
+            data
+        });
+        // from here on, dropping it puts everything back
+        let term = Self { orig, stderr, stderr_reader: Some(stderr_reader) };
+        let mut raw = orig;
+        // SAFETY: raw is a valid termios struct
+        unsafe {
+            libc::cfmakeraw(&mut raw);
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        // switch to the alternate screen and hide the cursor
+        let mut out = io::stdout();
+        write!(out, "\x1b[?1049h\x1b[?25l\x1b[2J")?;
+        out.flush()?;
+        Ok(term)
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let mut out = io::stdout();
+        let _ = write!(out, "\x1b[0m\x1b[?25h\x1b[?1049l");
+        let _ = out.flush();
+        // SAFETY: orig is the termios struct returned by tcgetattr, and stderr is a file
+        // descriptor which is only closed here. Putting it back closes the pipe's write end, so
+        // the reader gets to the end.
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.orig);
+            libc::dup2(self.stderr, libc::STDERR_FILENO);
+            libc::close(self.stderr);
+        }
+        if let Some(data) = self.stderr_reader.take().and_then(|r| r.join().ok()) {
+            let _ = io::stderr().write_all(&data);
+        }
+    }
+}
+
+/// Point stderr at a new pipe, returning a copy of the original stderr and the pipe's read end
+fn redirect_stderr() -> io::Result<(libc::c_int, File)> {
+    let mut fds = [0; 2];
+    // SAFETY: pipe writes two file descriptors to the array, which are only used here, and are
+    // marked close-on-exec so that the cargo processes don't hold on to them
+    unsafe {
+        if libc::pipe(fds.as_mut_ptr()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let [read, write] = fds;
+        let stderr = libc::fcntl(libc::STDERR_FILENO, libc::F_DUPFD_CLOEXEC, 0);
+        let ok = stderr >= 0
+            && libc::fcntl(read, libc::F_SETFD, libc::FD_CLOEXEC) == 0
+            && libc::dup2(write, libc::STDERR_FILENO) >= 0;
+        let err = io::Error::last_os_error();
+        libc::close(write);
+        if !ok {
+            libc::close(read);
+            if stderr >= 0 {
+                libc::close(stderr);
+            }
+            return Err(err);
+        }
+        Ok((stderr, File::from_raw_fd(read)))
+    }
+}
+
+/// Read keypresses from stdin and send them to the main loop
+fn spawn_input_thread(tx: Sender<Event>) {
+    thread::spawn(move || {
+        let mut stdin = io::stdin();
+        let mut buf = [0u8; 16];
+        while let Ok(n) = stdin.read(&mut buf) {
+            if n == 0 {
+                break;
+            }
+            let key = match &buf[..n] {
+                b"\x1b[A" | b"\x1bOA" | b"k" => Key::Up,
+                b"\x1b[B" | b"\x1bOB" | b"j" => Key::Down,
+                b"\x1b[5~" | b"b" => Key::PageUp,
+                b"\x1b[6~" | b" " => Key::PageDown,
+                b"\x1b[F" | b"\x1bOF" | b"\x1b[4~" | b"G" => Key::End,
+                b"s" => Key::Skip,
+                b"r" => Key::Retry,
+                b"q" | b"\x03" => Key::Quit,
+                _ => continue,
+            };
+            if tx.send(Event::Key(key)).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Draw the screen, then handle the events which come in before the next tick
+fn step(
+    app: &mut App,
+    out: &mut impl Write,
+    rx: &Receiver<Event>,
+    channel: &Channel,
+) -> Result<()> {
+    app.draw(out)?;
+
+    let page = terminal_size().0 / 2;
+    match rx.recv_timeout(TICK) {
+        Ok(ev) => {
+            // handle everything that's queued up before redrawing
+            for ev in std::iter::once(ev).chain(rx.try_iter()) {
+                app.handle_event(ev, page, channel)?;
+            }
+        }
+        Err(RecvTimeoutError::Timeout) => (),
+        Err(RecvTimeoutError::Disconnected) => unreachable!("the main loop holds a sender"),
+    }
+
+    // the worker pool stops by itself, and what it was running gets reported as skipped
+    if process::interrupted() && !app.quit {
+        app.handle_key(Key::Quit, 0, channel);
+    }
+    Ok(())
+}
+
+/// Run all jobs in the interactive UI with `run_jobs`, which runs up to `slots` of them at a time,
+/// returning the final result of each one. Packages retried while it's running are run again once
+/// it's done with the others.
+pub fn run(
+    jobs: Vec<Job>,
+    slots: u32,
+    run_jobs: impl Fn(Vec<Job>, &dyn Reporter) -> Result<Vec<JobResult>> + Sync,
+) -> Result<Vec<JobResult>> {
+    ensure!(
+        io::stdin().is_terminal() && io::stdout().is_terminal(),
+        "--tui requires an interactive terminal"
+    );
+    if jobs.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let (tx, rx): (Sender<Event>, Receiver<Event>) = mpsc::channel();
+    let channel = Channel { tx: tx.clone(), cancelled: Mutex::default() };
+    let entries = jobs.into_iter().map(|job| Entry {
+        job,
+        status: Status::Pending,
+        output: Vec::new(),
+        started: None,
+        queued: false,
+        result: None,
+    });
+    let mut app = App {
+        entries: entries.collect(),
+        slots,
+        selected: 0,
+        follow_selection: true,
+        scroll: 0,
+        round: None,
+        quit: false,
+    };
+
+    let term = RawTerminal::enter().context("Failed to set up the terminal")?;
+    spawn_input_thread(tx.clone());
+    let mut out = io::stdout().lock();
+
+    thread::scope(|s| -> Result<()> {
+        loop {
+            if let Some(jobs) = app.next_round(&channel) {
+                let (tx, channel, run_jobs) = (tx.clone(), &channel, &run_jobs);
+                s.spawn(move || {
+                    let _ = tx.send(Event::Done(run_jobs(jobs, channel)));
+                });
+            }
+            // the jobs are skipped once quitting, but the worker pool has to finish with them
+            if app.quit && app.round.is_none() {
+                return Ok(());
+            }
+            if let Err(e) = step(&mut app, &mut out, &rx, &channel) {
+                // stop the jobs rather than waiting for them without showing anything
+                app.handle_key(Key::Quit, 0, &channel);
+                return Err(e);
+            }
+        }
+    })?;
+
+    drop(out);
+    drop(term);
+    Ok(app.entries.into_iter().filter_map(|e| e.result).collect())
+}
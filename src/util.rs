@@ -0,0 +1,280 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::package_data::cargo_home;
+
+/// Directory for files kept between runs, e.g. `~/.local/state/cargo-update-installed`
+pub fn state_dir() -> Result<PathBuf> {
+    let dir = dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .ok_or_else(|| anyhow!("Unable to find a directory to store state in"))?;
+    Ok(dir.join(env!("CARGO_PKG_NAME")))
+}
+
+/// Find a program in cargo's bin directory or the PATH, like cargo does for subcommands
+pub fn find_program(name: &str) -> Option<PathBuf> {
+    let file = format!("{name}{}", env::consts::EXE_SUFFIX);
+    let path = env::var_os("PATH").unwrap_or_default();
+    let dirs = cargo_home().ok().map(|home| home.join("bin")).into_iter();
+    dirs.chain(env::split_paths(&path)).map(|dir| dir.join(&file)).find(|p| p.is_file())
+}
+
+/// The machine's hostname
+#[cfg(unix)]
+pub fn hostname() -> Option<String> {
+    let mut buf = [0u8; 256];
+    // SAFETY: the buffer is valid for its length
+    if unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) } != 0 {
+        return None;
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    String::from_utf8(buf[..len].to_vec()).ok().filter(|h| !h.is_empty())
+}
+
+/// The machine's hostname
+#[cfg(not(unix))]
+pub fn hostname() -> Option<String> {
+    env::var("COMPUTERNAME").ok()
+}
+
+/// The host's target triple, e.g. "x86_64-unknown-linux-gnu", as reported by `rustc -vV`
+pub fn host_target() -> Result<String> {
+    let rustc = env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let output = Command::new(rustc).arg("-vV").output().context("Failed to run `rustc -vV`")?;
+    ensure!(output.status.success(), "`rustc -vV` failed");
+    let text = String::from_utf8_lossy(&output.stdout);
+    text.lines()
+        .find_map(|line| line.strip_prefix("host: "))
+        .map(|host| host.trim().to_owned())
+        .ok_or_else(|| anyhow!("`rustc -vV` didn't show the host target"))
+}
+
+/// The version of the current rustc, e.g. "1.80.1"
+pub fn rustc_version() -> Result<String> {
+    let rustc = env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let output = Command::new(rustc).arg("-vV").output().context("Failed to run `rustc -vV`")?;
+    ensure!(output.status.success(), "`rustc -vV` failed");
+    let text = String::from_utf8_lossy(&output.stdout);
+    release_of(&text).ok_or_else(|| anyhow!("`rustc -vV` didn't show the version"))
+}
+
+/// The release from the output of `rustc -vV`, which is also what cargo records for each package
+pub fn release_of(version_info: &str) -> Option<String> {
+    version_info
+        .lines()
+        .find_map(|line| line.strip_prefix("release: "))
+        .map(|r| r.trim().to_owned())
+}
+
+/// Load a JSON file from the state directory, or the default value if it doesn't exist yet
+pub fn load_state<T: DeserializeOwned + Default>(name: &str) -> Result<T> {
+    let path = state_dir()?.join(name);
+    let data = match fs::read(&path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(T::default()),
+        Err(e) => return Err(e).context(format!("Failed to read '{}'", path.display())),
+    };
+    serde_json::from_slice(&data).with_context(|| format!("Failed to parse '{}'", path.display()))
+}
+
+/// Save a JSON file in the state directory
+pub fn save_state<T: Serialize>(name: &str, value: &T) -> Result<()> {
+    let path = state_dir()?.join(name);
+    fs::create_dir_all(path.parent().unwrap())
+        .with_context(|| format!("Failed to create '{}'", path.parent().unwrap().display()))?;
+    let data = serde_json::to_string(value)?;
+    fs::write(&path, data + "\n").with_context(|| format!("Failed to write '{}'", path.display()))
+}
+
+/// Parse a human-friendly duration like "90s", "30m", "1h30m", "7d", "2w", or "1y".
+/// A bare number is interpreted as seconds.
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let s = s.trim();
+    ensure!(!s.is_empty(), "empty duration");
+    if let Ok(secs) = s.parse::<u64>() {
+        return Ok(Duration::from_secs(secs));
+    }
+
+    let mut total = 0u64;
+    let mut rest = s;
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        ensure!(digits > 0, "invalid duration '{s}': expected a number");
+        let num: u64 = rest[..digits].parse().map_err(|_| anyhow!("invalid duration '{s}'"))?;
+        rest = &rest[digits..];
+
+        let unit_len = rest.find(|c: char| c.is_ascii_digit()).unwrap_or(rest.len());
+        let mult = match &rest[..unit_len] {
+            "s" | "sec" | "secs" => 1,
+            "m" | "min" | "mins" => 60,
+            "h" | "hr" | "hrs" => 60 * 60,
+            "d" | "day" | "days" => 24 * 60 * 60,
+            "w" | "week" | "weeks" => 7 * 24 * 60 * 60,
+            "y" | "year" | "years" => 365 * 24 * 60 * 60,
+            "" => bail!("invalid duration '{s}': missing unit after {num}"),
+            u => bail!("invalid duration '{s}': unknown unit '{u}'"),
+        };
+        rest = &rest[unit_len..];
+        total = num
+            .checked_mul(mult)
+            .and_then(|n| total.checked_add(n))
+            .ok_or_else(|| anyhow!("duration '{s}' is too large"))?;
+    }
+    Ok(Duration::from_secs(total))
+}
+
+/// Parse a duration like [`parse_duration`] for how often to do something, which can't be zero
+pub fn parse_interval(s: &str) -> Result<Duration> {
+    let interval = parse_duration(s)?;
+    ensure!(!interval.is_zero(), "the interval can't be zero");
+    Ok(interval)
+}
+
+/// A size in bytes, written like "500M" or "2G" in the config file (in powers of 1024)
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Size(pub u64);
+
+impl std::str::FromStr for Size {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        ensure!(digits > 0, "invalid size '{s}': expected a number");
+        let num: u64 = s[..digits].parse().map_err(|_| anyhow!("invalid size '{s}'"))?;
+        let mult: u64 = match s[digits..].trim_start() {
+            "" | "B" => 1,
+            "K" | "KB" | "KiB" => 1 << 10,
+            "M" | "MB" | "MiB" => 1 << 20,
+            "G" | "GB" | "GiB" => 1 << 30,
+            u => bail!("invalid size '{s}': unknown unit '{u}'"),
+        };
+        num.checked_mul(mult).map(Size).ok_or_else(|| anyhow!("size '{s}' is too large"))
+    }
+}
+
+impl TryFrom<String> for Size {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+/// Format a duration compactly using its two most significant units, e.g. "1h 30m" or "45s"
+pub fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    let (days, hours, mins, secs) = (secs / 86400, (secs / 3600) % 24, (secs / 60) % 60, secs % 60);
+    match (days, hours, mins) {
+        (0, 0, 0) => format!("{secs}s"),
+        (0, 0, _) if secs == 0 => format!("{mins}m"),
+        (0, 0, _) => format!("{mins}m {secs}s"),
+        (0, _, 0) => format!("{hours}h"),
+        (0, _, _) => format!("{hours}h {mins}m"),
+        (_, 0, _) => format!("{days}d"),
+        _ => format!("{days}d {hours}h"),
+    }
+}
+
+/// Convert a count of days since 1970-01-01 to a (year, month, day) date in the Gregorian
+/// calendar, using Howard Hinnant's `civil_from_days` algorithm.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Convert a (year, month, day) date in the Gregorian calendar to a count of days since
+/// 1970-01-01, the inverse of [`civil_from_days`]
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = i64::from((month + 9) % 12);
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Parse an RFC 3339 timestamp like "2023-05-31T12:34:56.789+00:00", ignoring fractional seconds
+pub fn parse_timestamp(s: &str) -> Option<SystemTime> {
+    let num = |range: std::ops::Range<usize>| s.get(range)?.parse::<i64>().ok();
+    let (year, month, day) = (num(0..4)?, num(5..7)?, num(8..10)?);
+    let (hour, min, sec) = (num(11..13)?, num(14..16)?, num(17..19)?);
+    let mut secs =
+        days_from_civil(year, month as u32, day as u32) * 86400 + hour * 3600 + min * 60 + sec;
+    // the offset is at the end, after any fractional seconds
+    let rest = s[19..].trim_start_matches(|c: char| c == '.' || c.is_ascii_digit());
+    if let Some(sign @ ('+' | '-')) = rest.chars().next() {
+        let offset = rest[1..].get(..2)?.parse::<i64>().ok()? * 3600
+            + rest.get(4..6)?.parse::<i64>().ok()? * 60;
+        secs -= if sign == '+' { offset } else { -offset };
+    }
+    Some(UNIX_EPOCH + Duration::from_secs(u64::try_from(secs).ok()?))
+}
+
+/// Format a time as an RFC 3339 UTC timestamp, e.g. "2023-05-31T12:34:56Z"
+pub fn format_timestamp(t: SystemTime) -> String {
+    let secs = match t.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64),
+    };
+    let (year, month, day) = civil_from_days(secs.div_euclid(86400));
+    let tod = secs.rem_euclid(86400);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        tod / 3600,
+        (tod / 60) % 60,
+        tod % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(s: &str) -> u64 {
+        parse_duration(s).unwrap().as_secs()
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(secs("90"), 90);
+        assert_eq!(secs(" 45s "), 45);
+        assert_eq!(secs("1h30m"), 90 * 60);
+        assert_eq!(secs("2days"), 2 * 24 * 60 * 60);
+        assert_eq!(secs("1w1d"), 8 * 24 * 60 * 60);
+        assert_eq!(secs("1y"), 365 * 24 * 60 * 60);
+        assert_eq!(secs("0"), 0);
+    }
+
+    #[test]
+    fn invalid_durations() {
+        for s in ["", "h", "5x", "1m5", "1.5h", "-1s", "99999999999999999999s", "600000000000y"] {
+            assert!(parse_duration(s).is_err(), "{s:?} was accepted");
+        }
+    }
+
+    #[test]
+    fn intervals_are_not_zero() {
+        assert!(parse_interval("0").is_err());
+        assert!(parse_interval("0s").is_err());
+        assert_eq!(parse_interval("12h").unwrap().as_secs(), 12 * 60 * 60);
+    }
+}
@@ -0,0 +1,405 @@
+//! Checking that installed packages match what cargo recorded about them.
+//!
+//! The SHA-256 hash of each program is recorded after it's installed, so that changes made outside
+//! of cargo (tampering, corruption, or copying another build over it) can be noticed.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+
+use crate::http;
+use crate::index;
+use crate::package_data::*;
+use crate::semver::Version;
+use crate::sha256;
+use crate::util;
+use crate::version_source::{GitLsRemote, VersionSource};
+
+const FILE_NAME: &str = "hashes.json";
+
+/// Check that installed packages are as cargo recorded them, and can still be updated.
+///
+/// Every recorded program must exist, be executable, be built for the recorded target, and not
+/// have been modified since it was installed. Each package's source must still be there, and the
+/// features it was installed with must still exist in the latest version. Problems are listed
+/// with the most serious first, each with a suggested fix.
+///
+/// Hashes are recorded after every successful update or install, so packages which haven't been
+/// updated since this feature was added don't have one yet. Use --record-missing to record the
+/// current state of those.
+#[derive(Debug, Parser)]
+pub struct VerifyArgs {
+    /// Only check these packages.
+    #[arg(value_name = "NAME")]
+    names: Vec<String>,
+
+    /// Record hashes for programs which don't have one. Existing hashes are never replaced, so
+    /// reinstall a package to accept changes to it.
+    #[arg(long)]
+    record_missing: bool,
+
+    /// Don't check the packages' sources over the network.
+    #[arg(long)]
+    offline: bool,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+struct Hashes {
+    /// SHA-256 hashes of each package's programs, by package and program name
+    packages: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl Hashes {
+    fn load() -> Result<Self> {
+        util::load_state(FILE_NAME)
+    }
+
+    fn save(&self) -> Result<()> {
+        util::save_state(FILE_NAME, self)
+    }
+}
+
+/// Record the hashes of the programs of packages which were just installed
+pub fn record(names: &[&str]) -> Result<()> {
+    if names.is_empty() {
+        return Ok(());
+    }
+    let crates2 = Crates2::load().context("Failed to reload .crates2.json")?;
+    let bin_dir = cargo_home()?.join("bin");
+    let mut hashes = Hashes::load()?;
+    for (pkg_id, details) in &crates2.installs {
+        let Ok(pkg) = pkg_id.parse::<Package>() else { continue };
+        if !names.contains(&pkg.name.as_str()) {
+            continue;
+        }
+        let mut bins = BTreeMap::new();
+        for bin in &details.bins {
+            let path = bin_path(&bin_dir, bin);
+            let hash = sha256::file_hex(&path)
+                .with_context(|| format!("Failed to hash '{}'", path.display()))?;
+            bins.insert(bin.clone(), hash);
+        }
+        hashes.packages.insert(pkg.name, bins);
+    }
+    hashes.save()
+}
+
+/// How serious a problem is, most serious first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Severity {
+    /// Something installed is broken
+    Error,
+    /// The package works but updating it is likely to fail
+    Warning,
+}
+
+/// Something wrong with an installed package, and how to fix it
+struct Problem {
+    severity: Severity,
+    package: String,
+    what: String,
+    fix: String,
+}
+
+impl Problem {
+    fn new(severity: Severity, pkg: &Package, what: String, fix: String) -> Self {
+        Self { severity, package: pkg.name.clone(), what, fix }
+    }
+}
+
+/// The CPU architecture a program was built for, from its ELF, Mach-O, or PE header, in the terms
+/// of [`target_arch`]
+fn binary_arch(path: &Path) -> Option<&'static str> {
+    let mut header = Vec::new();
+    File::open(path).ok()?.take(4096).read_to_end(&mut header).ok()?;
+    let u16_at = |i: usize, big: bool| {
+        let b: [u8; 2] = header.get(i..i + 2)?.try_into().ok()?;
+        Some(if big { u16::from_be_bytes(b) } else { u16::from_le_bytes(b) })
+    };
+    if header.starts_with(b"\x7fELF") {
+        return match u16_at(18, header.get(5) == Some(&2))? {
+            3 => Some("x86"),
+            62 => Some("x86_64"),
+            40 => Some("arm"),
+            183 => Some("aarch64"),
+            243 => Some("riscv"),
+            8 => Some("mips"),
+            20 => Some("powerpc"),
+            21 => Some("powerpc64"),
+            22 => Some("s390x"),
+            258 => Some("loongarch64"),
+            _ => None,
+        };
+    }
+    if header.starts_with(&[0xcf, 0xfa, 0xed, 0xfe])
+        || header.starts_with(&[0xce, 0xfa, 0xed, 0xfe])
+    {
+        return match u32::from_le_bytes(header.get(4..8)?.try_into().ok()?) {
+            7 => Some("x86"),
+            0x0100_0007 => Some("x86_64"),
+            12 => Some("arm"),
+            0x0100_000c => Some("aarch64"),
+            _ => None,
+        };
+    }
+    if header.starts_with(b"MZ") {
+        let pe = u32::from_le_bytes(header.get(0x3c..0x40)?.try_into().ok()?) as usize;
+        if header.get(pe..pe + 4)? != b"PE\0\0" {
+            return None;
+        }
+        return match u16_at(pe + 4, false)? {
+            0x14c => Some("x86"),
+            0x8664 => Some("x86_64"),
+            0x1c4 => Some("arm"),
+            0xaa64 => Some("aarch64"),
+            _ => None,
+        };
+    }
+    None
+}
+
+/// The CPU architecture of a target triple, e.g. "x86" for "i686-unknown-linux-gnu"
+fn target_arch(target: &str) -> Option<&'static str> {
+    let arch = target.split('-').next()?;
+    Some(match arch {
+        "x86_64" => "x86_64",
+        "i386" | "i586" | "i686" => "x86",
+        "aarch64" | "arm64" | "arm64ec" => "aarch64",
+        a if a.starts_with("arm") || a.starts_with("thumb") => "arm",
+        a if a.starts_with("riscv") => "riscv",
+        a if a.starts_with("mips") => "mips",
+        "powerpc" => "powerpc",
+        "powerpc64" | "powerpc64le" => "powerpc64",
+        "s390x" => "s390x",
+        "loongarch64" => "loongarch64",
+        _ => return None,
+    })
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata().is_ok_and(|m| m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(_path: &Path) -> bool {
+    true
+}
+
+/// Check that a package's source is still there, and that its features still exist in the latest
+/// version, which is what an update will build
+fn check_source(pkg: &Package, details: &PackageDetails, offline: bool) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    match &pkg.source {
+        PackageSource::Path(dir) => {
+            if !dir.join("Cargo.toml").is_file() {
+                problems.push(Problem::new(
+                    Severity::Warning,
+                    pkg,
+                    format!("its source directory '{}' is gone", dir.display()),
+                    format!(
+                        "reinstall it from where it is now with `cargo install --path <dir>`, or \
+                         exclude it from updates with `--exclude {}`",
+                        pkg.name
+                    ),
+                ));
+            }
+        }
+        _ if offline => (),
+        src if src.is_crates_io() => {
+            let current = match pkg.version.parse::<Version>() {
+                Ok(version) => version,
+                Err(e) => {
+                    dbgmsg!("{}: {e:#}", pkg.name);
+                    return problems;
+                }
+            };
+            match index::latest_features(&pkg.name, &current) {
+                Ok(None) => problems.push(Problem::new(
+                    Severity::Warning,
+                    pkg,
+                    "crates.io has no versions of it which can be installed".into(),
+                    "check whether it was renamed or replaced, and install that instead".into(),
+                )),
+                Ok(Some((latest, features))) => {
+                    // "dep/feature" enables a feature of a dependency, which has to be optional
+                    let (gone, kept): (Vec<&String>, Vec<&String>) =
+                        details.features.iter().partition(|f| {
+                            let name = f.split('/').next().unwrap_or_default();
+                            !features.contains(name.trim_start_matches("dep:"))
+                        });
+                    if !gone.is_empty() {
+                        let gone: Vec<&str> = gone.iter().map(|f| f.as_str()).collect();
+                        let kept: Vec<&str> = kept.iter().map(|f| f.as_str()).collect();
+                        let features = if kept.is_empty() {
+                            String::new()
+                        } else {
+                            format!(" --features {}", kept.join(","))
+                        };
+                        problems.push(Problem::new(
+                            Severity::Warning,
+                            pkg,
+                            format!(
+                                "version {latest} doesn't have the feature{} {}",
+                                if gone.len() == 1 { "" } else { "s" },
+                                gone.join(", ")
+                            ),
+                            format!(
+                                "reinstall it without them: `cargo install {}{features}`",
+                                pkg.name
+                            ),
+                        ));
+                    }
+                }
+                Err(e) => problems.push(Problem::new(
+                    Severity::Warning,
+                    pkg,
+                    format!("couldn't look it up on crates.io: {e:#}"),
+                    "check the network connection, or whether the package was removed".into(),
+                )),
+            }
+        }
+        PackageSource::Git { url, .. } => {
+            if let Err(e) = GitLsRemote.latest(pkg) {
+                problems.push(Problem::new(
+                    Severity::Warning,
+                    pkg,
+                    format!("its git repository can't be reached: {e:#}"),
+                    format!(
+                        "if the repository moved, reinstall it with `cargo install --git <new url> \
+                         {}`; otherwise check access to {url}",
+                        pkg.name
+                    ),
+                ));
+            }
+        }
+        PackageSource::Registry(_) => (),
+    }
+    problems
+}
+
+pub fn run(args: &VerifyArgs, crates2: &Crates2) -> Result<()> {
+    let bin_dir = cargo_home()?.join("bin");
+    let mut hashes = Hashes::load()?;
+    let (mut checked, mut recorded, mut unrecorded) = (0, 0, 0);
+    let mut problems = Vec::new();
+    let mut packages = Vec::new();
+    for (pkg_id, details) in &crates2.installs {
+        let pkg = pkg_id
+            .parse::<Package>()
+            .with_context(|| format!("Failed to parse package id '{pkg_id}'"))?;
+        if !args.names.is_empty() && !args.names.contains(&pkg.name) {
+            continue;
+        }
+        let reinstall = format!("reinstall it with `cargo update-installed --force {}`", pkg.name);
+        let target = target_arch(&details.target);
+        for bin in &details.bins {
+            let path = bin_path(&bin_dir, bin);
+            if !path.exists() {
+                problems.push(Problem::new(
+                    Severity::Error,
+                    &pkg,
+                    format!("'{}' is missing", path.display()),
+                    reinstall.clone(),
+                ));
+                continue;
+            }
+            if !is_executable(&path) {
+                problems.push(Problem::new(
+                    Severity::Error,
+                    &pkg,
+                    format!("'{}' isn't executable", path.display()),
+                    format!("`chmod +x {}`", path.display()),
+                ));
+            }
+            if let (Some(target), Some(arch)) = (target, binary_arch(&path)) {
+                if target != arch {
+                    problems.push(Problem::new(
+                        Severity::Error,
+                        &pkg,
+                        format!(
+                            "'{}' is built for {arch}, but was installed for {}",
+                            path.display(),
+                            details.target
+                        ),
+                        reinstall.clone(),
+                    ));
+                }
+            }
+            let hash = sha256::file_hex(&path)
+                .with_context(|| format!("Failed to hash '{}'", path.display()))?;
+            match hashes.packages.get(&pkg.name).and_then(|bins| bins.get(bin)) {
+                Some(expected) if *expected == hash => {
+                    dbgmsg!("{}: '{}' is unchanged", pkg.name, path.display());
+                    checked += 1;
+                }
+                Some(_) => problems.push(Problem::new(
+                    Severity::Error,
+                    &pkg,
+                    format!("'{}' was modified after it was installed", path.display()),
+                    format!("check what changed it, then {reinstall}"),
+                )),
+                None if args.record_missing => {
+                    dbgmsg!("{}: recording hash of '{}'", pkg.name, path.display());
+                    hashes.packages.entry(pkg.name.clone()).or_default().insert(bin.clone(), hash);
+                    recorded += 1;
+                }
+                None => {
+                    dbgmsg!("{}: no hash recorded for '{}'", pkg.name, path.display());
+                    unrecorded += 1;
+                }
+            }
+        }
+        packages.push((pkg, details));
+    }
+
+    if !args.offline {
+        msg!("Checking the sources of {} packages", packages.len());
+        index::prefetch(
+            packages.iter().filter(|(p, _)| p.source.is_crates_io()).map(|(p, _)| p.name.as_str()),
+        );
+    }
+    for found in
+        http::map_concurrent(&packages, |(pkg, details)| check_source(pkg, details, args.offline))
+    {
+        problems.extend(found);
+    }
+
+    if recorded > 0 {
+        hashes.save()?;
+        msg!("Recorded hashes of {recorded} programs");
+    }
+    if unrecorded > 0 {
+        warnmsg!("{unrecorded} programs have no recorded hash, use --record-missing to add them");
+    }
+    problems.sort_by(|a, b| (a.severity, &a.package).cmp(&(b.severity, &b.package)));
+    for problem in &problems {
+        match problem.severity {
+            Severity::Error => errmsg!("error: {}: {}", problem.package, problem.what),
+            Severity::Warning => warnmsg!("warning: {}: {}", problem.package, problem.what),
+        }
+        eprintln!("  fix: {}", problem.fix);
+    }
+    let mut broken: Vec<&str> = problems
+        .iter()
+        .filter(|p| p.severity == Severity::Error)
+        .map(|p| p.package.as_str())
+        .collect();
+    if !broken.is_empty() {
+        broken.dedup();
+        bail!("Some packages' programs are missing or broken: {}", broken.join(", "));
+    }
+    msg!("Verified {checked} programs");
+    if !problems.is_empty() {
+        let s = if problems.len() == 1 { "" } else { "s" };
+        warnmsg!("Found {} problem{s} which may stop updates", problems.len());
+    }
+    Ok(())
+}
@@ -0,0 +1,163 @@
+//! Looking up the latest version available from where a package was installed from.
+//!
+//! Each kind of package source has a [`VersionSource`]: crates.io's sparse index, `git ls-remote`
+//! for git repos, and the manifest of local path packages. `outdated` and `info` report what
+//! [`latest`] finds, and update runs install what [`update_target`] finds, so another registry
+//! would be supported by an implementation of its own in the lists passed to them.
+
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use crate::index;
+use crate::manifest;
+use crate::package_data::*;
+use crate::semver::Version;
+
+/// The latest version of a package
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Available {
+    /// A released version
+    Version(Version),
+    /// The full hash of the latest commit, for git packages
+    Commit(String),
+}
+
+/// Somewhere to look up the latest version of packages. Packages are checked concurrently, so this
+/// has to be Sync.
+pub trait VersionSource: Sync {
+    /// Whether this can look up packages from `source`
+    fn handles(&self, source: &PackageSource) -> bool;
+
+    /// Find the latest version of a package, or None if it isn't found. Pre-releases should only
+    /// be returned if the installed version is one.
+    fn latest(&self, pkg: &Package) -> Result<Option<Available>>;
+}
+
+/// crates.io's sparse index, falling back to cargo's local cache of it if the index can't be
+/// reached
+#[derive(Debug, Default)]
+pub struct SparseIndex {
+    /// Only use the local index cache
+    offline: bool,
+    from_cache: AtomicUsize,
+}
+
+impl SparseIndex {
+    pub fn new(offline: bool) -> Self {
+        Self { offline, from_cache: AtomicUsize::new(0) }
+    }
+
+    /// How many packages were looked up in the local index cache because the network failed
+    pub fn cache_fallbacks(&self) -> usize {
+        self.from_cache.load(Ordering::Relaxed)
+    }
+}
+
+impl VersionSource for SparseIndex {
+    fn handles(&self, source: &PackageSource) -> bool {
+        source.is_crates_io()
+    }
+
+    fn latest(&self, pkg: &Package) -> Result<Option<Available>> {
+        let current: Version = pkg.version.parse()?;
+        let latest = if self.offline {
+            index::cached_latest_version(&pkg.name, &current)?
+        } else {
+            match index::latest_version(&pkg.name, &current) {
+                Ok(latest) => latest,
+                Err(e) => {
+                    // report the network error if the cache doesn't have it either
+                    let latest = index::cached_latest_version(&pkg.name, &current)
+                        .map_err(|_| e.context("Failed to check crates.io"))?;
+                    self.from_cache.fetch_add(1, Ordering::Relaxed);
+                    latest
+                }
+            }
+        };
+        Ok(latest.map(Available::Version))
+    }
+}
+
+/// Find the latest commit on a git repo's branch, tag, or default branch
+fn latest_git_rev(url: &str, branch: Option<&str>, tag: Option<&str>) -> Result<String> {
+    let refname = match (branch, tag) {
+        (Some(b), _) => format!("refs/heads/{b}"),
+        (_, Some(t)) => format!("refs/tags/{t}"),
+        _ => "HEAD".into(),
+    };
+    dbgmsg!("git ls-remote {url} {refname}");
+    let out = Command::new("git")
+        .args(["ls-remote", url, &refname])
+        .output()
+        .context("Failed to run git ls-remote")?;
+    if !out.status.success() {
+        bail!("git ls-remote failed: {}", String::from_utf8_lossy(&out.stderr).trim());
+    }
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let rev = stdout.split_whitespace().next().ok_or_else(|| anyhow!("{refname} not found"))?;
+    Ok(rev.to_owned())
+}
+
+/// The latest commit on a git package's branch, tag, or default branch, using `git ls-remote`
+#[derive(Debug, Default)]
+pub struct GitLsRemote;
+
+impl VersionSource for GitLsRemote {
+    fn handles(&self, source: &PackageSource) -> bool {
+        matches!(source, PackageSource::Git { .. })
+    }
+
+    fn latest(&self, pkg: &Package) -> Result<Option<Available>> {
+        let PackageSource::Git { url, branch, tag, .. } = &pkg.source else {
+            return Ok(None);
+        };
+        let rev = latest_git_rev(url, branch.as_deref(), tag.as_deref())?;
+        Ok(Some(Available::Commit(rev)))
+    }
+}
+
+/// The version in the Cargo.toml of a package installed from a local path
+#[derive(Debug, Default)]
+pub struct LocalPath;
+
+impl VersionSource for LocalPath {
+    fn handles(&self, source: &PackageSource) -> bool {
+        matches!(source, PackageSource::Path(_))
+    }
+
+    fn latest(&self, pkg: &Package) -> Result<Option<Available>> {
+        let PackageSource::Path(dir) = &pkg.source else {
+            return Ok(None);
+        };
+        let dir = manifest::install_dir(dir, &pkg.name);
+        Ok(manifest::package_version(&dir)?.map(Available::Version))
+    }
+}
+
+/// Find the latest version of a package using the first of `sources` which handles its source.
+/// Returns None if none of them do.
+pub fn latest(sources: &[&dyn VersionSource], pkg: &Package) -> Result<Option<Available>> {
+    match sources.iter().find(|s| s.handles(&pkg.source)) {
+        Some(source) => source.latest(pkg),
+        None => Ok(None),
+    }
+}
+
+/// Find the version an update would install for a package: the version it's pinned to, or the
+/// latest release from the first of `sources` which handles it. Git packages don't have one, since
+/// they're updated to the latest commit.
+pub fn update_target(
+    sources: &[&dyn VersionSource],
+    pkg: &Package,
+    pin: Option<&str>,
+) -> Result<Option<Version>> {
+    if let Some(pin) = pin {
+        return Ok(Some(pin.parse()?));
+    }
+    match latest(sources, pkg)? {
+        Some(Available::Version(version)) => Ok(Some(version)),
+        Some(Available::Commit(_)) | None => Ok(None),
+    }
+}
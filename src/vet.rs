@@ -0,0 +1,135 @@
+//! Checking updates against a cargo-vet audit store, the `supply-chain` directory of a project
+//! which uses cargo-vet.
+//!
+//! A version counts as vetted if it has a full audit or exemption, or if a chain of delta audits
+//! leads to it from one which does. Audits imported from other organizations in imports.lock count
+//! too. Only the built-in criteria are understood: `safe-to-deploy` implies `safe-to-run`, and any
+//! other criteria have to match exactly. Violations aren't checked.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use anyhow::{ensure, Context, Result};
+use serde::Deserialize;
+
+use crate::toml;
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Criteria {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Criteria {
+    /// Whether these criteria satisfy `wanted`
+    fn satisfy(&self, wanted: &str) -> bool {
+        let ok = |c: &str| c == wanted || (wanted == "safe-to-run" && c == "safe-to-deploy");
+        match self {
+            Self::One(c) => ok(c),
+            Self::Many(cs) => cs.iter().any(|c| ok(c)),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Audit {
+    criteria: Criteria,
+    /// The version of a full audit
+    version: Option<String>,
+    /// The versions of a delta audit, like "1.0.0 -> 1.1.0"
+    delta: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct AuditsFile {
+    audits: BTreeMap<String, Vec<Audit>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ImportsFile {
+    audits: BTreeMap<String, AuditsFile>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Exemption {
+    version: String,
+    criteria: Criteria,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ConfigFile {
+    exemptions: BTreeMap<String, Vec<Exemption>>,
+}
+
+/// The audits and exemptions of an audit store, by crate name
+#[derive(Debug, Default)]
+pub struct Store {
+    audits: BTreeMap<String, Vec<Audit>>,
+    exemptions: BTreeMap<String, Vec<Exemption>>,
+}
+
+/// Read and parse a TOML file of the store, which is treated as empty if it doesn't exist
+fn load_file<T: serde::de::DeserializeOwned + Default>(path: &Path) -> Result<T> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(T::default()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read '{}'", path.display())),
+    };
+    let value =
+        toml::parse(&text).with_context(|| format!("Failed to parse '{}'", path.display()))?;
+    serde_json::from_value(value).with_context(|| format!("Failed to parse '{}'", path.display()))
+}
+
+impl Store {
+    pub fn load(dir: &Path) -> Result<Self> {
+        ensure!(dir.is_dir(), "The cargo-vet store '{}' doesn't exist", dir.display());
+        let mut audits = load_file::<AuditsFile>(&dir.join("audits.toml"))?.audits;
+        let imports: ImportsFile = load_file(&dir.join("imports.lock"))?;
+        for (_, imported) in imports.audits {
+            for (name, list) in imported.audits {
+                audits.entry(name).or_default().extend(list);
+            }
+        }
+        let exemptions = load_file::<ConfigFile>(&dir.join("config.toml"))?.exemptions;
+        Ok(Self { audits, exemptions })
+    }
+
+    /// Whether a version of a crate has been vetted for the criteria
+    pub fn is_vetted(&self, name: &str, version: &str, criteria: &str) -> bool {
+        let audits = self.audits.get(name).map_or(&[][..], Vec::as_slice);
+        let exemptions = self.exemptions.get(name).map_or(&[][..], Vec::as_slice);
+        let audits: Vec<&Audit> = audits.iter().filter(|a| a.criteria.satisfy(criteria)).collect();
+
+        let mut vetted: BTreeSet<&str> =
+            audits.iter().filter_map(|a| a.version.as_deref()).collect();
+        vetted.extend(
+            exemptions.iter().filter(|e| e.criteria.satisfy(criteria)).map(|e| e.version.as_str()),
+        );
+        let deltas: Vec<(&str, &str)> = audits
+            .iter()
+            .filter_map(|a| {
+                let (from, to) = a.delta.as_deref()?.split_once("->")?;
+                Some((from.trim(), to.trim()))
+            })
+            .collect();
+        // follow delta audits from the vetted versions until nothing new is reached
+        loop {
+            let before = vetted.len();
+            for (from, to) in &deltas {
+                if vetted.contains(from) {
+                    vetted.insert(to);
+                }
+            }
+            if vetted.len() == before {
+                break;
+            }
+        }
+        vetted.contains(version)
+    }
+}
@@ -0,0 +1,64 @@
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+
+use crate::list::print_table;
+use crate::package_data::*;
+
+/// Show which installed packages provide programs.
+#[derive(Debug, Parser)]
+pub struct WhichArgs {
+    /// Program names, or paths to them.
+    #[arg(value_name = "PROGRAM", required = true)]
+    programs: Vec<String>,
+}
+
+pub fn run(args: &WhichArgs, crates2: &Crates2) -> Result<()> {
+    let bin_dir = cargo_home()?.join("bin");
+    let mut packages = Vec::new();
+    for (pkg_id, details) in &crates2.installs {
+        let pkg = pkg_id
+            .parse::<Package>()
+            .with_context(|| format!("Failed to parse package id '{pkg_id}'"))?;
+        packages.push((pkg, details));
+    }
+
+    let mut rows = Vec::new();
+    let mut unknown = Vec::new();
+    for program in &args.programs {
+        // accept a path like `$(which rg)` too
+        let name = Path::new(program).file_name().and_then(|n| n.to_str()).unwrap_or(program);
+        let name = exe_stem(name);
+        let found = packages.iter().find_map(|(pkg, details)| {
+            Some((pkg, details.bins.iter().find(|b| exe_stem(b) == name)?))
+        });
+        let Some((pkg, bin)) = found else {
+            if bin_path(&bin_dir, name).exists() {
+                errmsg!(
+                    "'{name}' is in '{}' but wasn't installed by cargo, see `adopt`",
+                    bin_dir.display()
+                );
+            } else {
+                errmsg!("No installed package provides '{name}'");
+            }
+            unknown.push(name);
+            continue;
+        };
+        rows.push(vec![
+            name.to_owned(),
+            pkg.name.clone(),
+            pkg.version.clone(),
+            pkg.source.to_string(),
+            bin_path(&bin_dir, bin).display().to_string(),
+        ]);
+    }
+
+    if !rows.is_empty() {
+        print_table(&["PROGRAM", "PACKAGE", "VERSION", "SOURCE", "PATH"], &rows, "");
+    }
+    if !unknown.is_empty() {
+        bail!("Programs not provided by any package: {}", unknown.join(", "));
+    }
+    Ok(())
+}